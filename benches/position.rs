@@ -8,7 +8,7 @@
 
 use a4::{
     core::{self, Color, Move},
-    movegen,
+    movegen::{self, MoveList},
     position::Position,
 };
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
@@ -46,6 +46,19 @@ fn criterion_benchmark(c: &mut Criterion) {
         });
     });
 
+    // Same as kiwipete-movegen-all, but generating into stack storage rather than a freshly allocated vector.
+    c.bench_function("kiwipete-movegen-all-movelist", |b| {
+        let pos = Position::from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/Pp2P3/2N2Q1p/1PPBBPPP/R3K2R b KQkq a3 0 1",
+        )
+        .unwrap();
+        b.iter(|| {
+            let mut moves = MoveList::new();
+            movegen::generate_move_list(black_box(Color::Black), black_box(&pos), &mut moves);
+            black_box(moves);
+        });
+    });
+
     c.bench_function("kiwipete-movegen-quiet", |b| {
         let pos = Position::from_fen(
             "r3k2r/p1ppqpb1/bn2pnp1/3PN3/Pp2P3/2N2Q1p/1PPBBPPP/R3K2R b KQkq a3 0 1",
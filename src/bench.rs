@@ -0,0 +1,80 @@
+// Copyright 2022 Sean Gillespie.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Searches a fixed set of positions to a fixed depth, for speed regression testing. Since the search is
+//! deterministic, the total node count doubles as a signature of the search: changes that aren't meant to change the
+//! search shouldn't change it.
+
+use std::time::{Duration, Instant};
+
+use crate::{
+    position::Position,
+    search::{self, SearchOptions},
+};
+
+/// Default depth to search each bench position to.
+pub const DEFAULT_BENCH_DEPTH: u32 = 4;
+
+/// Positions searched by the benchmark. These cover openings, middlegames with tactics, and endgames.
+const BENCH_POSITIONS: &[&str] = &[
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 11",
+    "rnbqkb1r/pp3ppp/4pn2/2pp4/3P1B2/4PN2/PPP2PPP/RN1QKB1R w KQkq - 0 5",
+    "2r3k1/5pp1/p3p2p/1p6/3P4/P3PP2/1r4PP/2R2RK1 w - - 0 25",
+    "rq3rk1/ppp2ppp/1bnpb3/3N2B1/3NP3/7P/PPPQ1PP1/2KR3R w - - 7 14",
+    "r1bq1r1k/1pp1n1pp/1p1p4/4p2Q/4Pp2/1BNP4/PPP2PPP/3R1RK1 w - - 2 14",
+    "r3r1k1/2p2ppp/p1p1bn2/8/1q2P3/2NPQN2/PPP3PP/R4RK1 b - - 2 15",
+    "6k1/6p1/6Pp/ppp5/3pn2P/1P3K2/1PP2P2/8 b - - 0 1",
+    "8/8/1p2k1p1/3p3p/1p1P1P1P/1P2PK2/8/8 w - - 3 54",
+    "5k2/7R/4P2p/5K2/p1r2P1p/8/8/8 b - - 0 1",
+];
+
+/// Aggregate results of a benchmark run.
+#[derive(Clone, Debug)]
+pub struct BenchResult {
+    pub nodes: u64,
+    pub elapsed: Duration,
+}
+
+impl BenchResult {
+    /// Nodes searched per second over the whole run.
+    pub fn nps(&self) -> u64 {
+        (self.nodes as f64 / self.elapsed.as_secs_f64()) as u64
+    }
+}
+
+/// Searches every bench position to the given depth and returns the total number of nodes searched. Searches share
+/// the global transposition table; callers that want reproducible node counts should clear it first.
+pub fn run(depth: u32) -> BenchResult {
+    let start = Instant::now();
+    let mut nodes = 0;
+    for fen in BENCH_POSITIONS {
+        let pos = Position::from_fen(fen).expect("invalid bench position");
+        let options = SearchOptions {
+            depth,
+            ..Default::default()
+        };
+
+        nodes += search::search(&pos, &options).stats.nodes_searched;
+    }
+
+    BenchResult {
+        nodes,
+        elapsed: Instant::now().duration_since(start),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn bench_depth_one() {
+        let result = super::run(1);
+        assert!(result.nodes > 0);
+    }
+}
@@ -19,6 +19,5 @@ struct Options {
 fn main() {
     let ops = Options::from_args();
     let pos = Position::from_fen(ops.fen).unwrap();
-    let eval = eval::evaluate(&pos);
-    println!("{}", eval);
+    print!("{}", eval::evaluate_detailed(&pos));
 }
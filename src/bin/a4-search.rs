@@ -29,6 +29,24 @@ struct Options {
     /// Maximum depth to search to with a non-specialized search.
     #[structopt(short, long, default_value = "6")]
     depth: u32,
+    /// Skip losing captures in quiescence search.
+    #[structopt(long)]
+    see_pruning: bool,
+    /// Skip captures in quiescence search that can't raise alpha.
+    #[structopt(long)]
+    delta_pruning: bool,
+    /// Skip underpromotions to rooks and bishops below the root.
+    #[structopt(long)]
+    underpromotion_pruning: bool,
+    /// Skip quiet moves at frontier nodes that can't raise alpha.
+    #[structopt(long)]
+    futility_pruning: bool,
+    /// Prune shallow nodes whose static evaluation is well above beta.
+    #[structopt(long)]
+    reverse_futility_pruning: bool,
+    /// Don't use the transposition table.
+    #[structopt(long)]
+    no_tt: bool,
 }
 
 fn main() {
@@ -45,6 +63,20 @@ fn main() {
     }
 
     search_options.depth = args.depth;
+    search_options.see_pruning = args.see_pruning;
+    search_options.delta_pruning = args.delta_pruning;
+    search_options.underpromotion_pruning = args.underpromotion_pruning;
+    search_options.use_tt = !args.no_tt;
+    if args.futility_pruning {
+        search_options.futility_depth = 1;
+        search_options.futility_margin = search::DEFAULT_FUTILITY_MARGIN;
+    }
+
+    if args.reverse_futility_pruning {
+        search_options.reverse_futility_depth = 3;
+        search_options.reverse_futility_margin = search::DEFAULT_REVERSE_FUTILITY_MARGIN;
+    }
+
     let pos = Position::from_fen(args.fen).expect("invalid fen");
     let result = search::search(&pos, &search_options);
     println!("===========================");
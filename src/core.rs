@@ -13,8 +13,10 @@ mod r#move;
 mod squareset;
 mod types;
 
-pub use attacks::{attacks, king_attacks, knight_attacks, pawn_attacks, queen_attacks};
-pub use r#move::Move;
+pub use attacks::{
+    attacks, king_attacks, knight_attacks, pawn_attacks, queen_attacks, squares_between,
+};
+pub use r#move::{chess960, set_chess960, Move};
 pub use squareset::{
     SquareSet, SquareSetIterator, SS_FILES, SS_FILE_A, SS_FILE_B, SS_FILE_C, SS_FILE_D, SS_FILE_E,
     SS_FILE_F, SS_FILE_G, SS_FILE_H, SS_RANKS, SS_RANK_1, SS_RANK_2, SS_RANK_3, SS_RANK_4,
@@ -22,9 +24,10 @@ pub use squareset::{
 };
 pub use types::{
     colors, files, piece_kinds, ranks, squares, AllFiles, AllRanks, AllSquares, CastleStatus,
-    Color, Direction, File, Piece, PieceKind, PieceParseError, Rank, Square, SquareParseError, A1,
-    A2, A3, A4, A5, A6, A7, A8, B1, B2, B3, B4, B5, B6, B7, B8, C1, C2, C3, C4, C5, C6, C7, C8, D1,
-    D2, D3, D4, D5, D6, D7, D8, E1, E2, E3, E4, E5, E6, E7, E8, F1, F2, F3, F4, F5, F6, F7, F8,
-    FILE_A, FILE_B, FILE_C, FILE_D, FILE_E, FILE_F, FILE_G, FILE_H, G1, G2, G3, G4, G5, G6, G7, G8,
-    H1, H2, H3, H4, H5, H6, H7, H8, RANK_1, RANK_2, RANK_3, RANK_4, RANK_5, RANK_6, RANK_7, RANK_8,
+    Color, Direction, File, FileParseError, Piece, PieceKind, PieceParseError, Rank,
+    RankParseError, Square, SquareParseError, A1, A2, A3, A4, A5, A6, A7, A8, B1, B2, B3, B4, B5,
+    B6, B7, B8, C1, C2, C3, C4, C5, C6, C7, C8, D1, D2, D3, D4, D5, D6, D7, D8, E1, E2, E3, E4, E5,
+    E6, E7, E8, F1, F2, F3, F4, F5, F6, F7, F8, FILE_A, FILE_B, FILE_C, FILE_D, FILE_E, FILE_F,
+    FILE_G, FILE_H, G1, G2, G3, G4, G5, G6, G7, G8, H1, H2, H3, H4, H5, H6, H7, H8, RANK_1, RANK_2,
+    RANK_3, RANK_4, RANK_5, RANK_6, RANK_7, RANK_8,
 };
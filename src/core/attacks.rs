@@ -282,3 +282,21 @@ pub fn attacks(kind: PieceKind, color: Color, sq: Square, occupancy: SquareSet)
         PieceKind::King => king_attacks(sq),
     }
 }
+
+/// Returns the squares strictly between two squares that share a rank, file, or diagonal. For squares that aren't
+/// aligned, such as the endpoints of a knight move, the set is empty.
+pub fn squares_between(s1: Square, s2: Square) -> SquareSet {
+    let mut s1_set = SquareSet::empty();
+    s1_set.insert(s1);
+    let mut s2_set = SquareSet::empty();
+    s2_set.insert(s2);
+    let kind = if rook_attacks(s1, SquareSet::empty()).contains(s2) {
+        PieceKind::Rook
+    } else if bishop_attacks(s1, SquareSet::empty()).contains(s2) {
+        PieceKind::Bishop
+    } else {
+        return SquareSet::empty();
+    };
+
+    attacks(kind, Color::White, s1, s2_set) & attacks(kind, Color::White, s2, s1_set)
+}
@@ -9,10 +9,22 @@
 use std::{
     convert::TryFrom,
     fmt::{self, Write},
+    sync::atomic::{AtomicBool, Ordering},
 };
 
 use crate::{core::*, position::Position};
 
+/// Whether or not the GUI has asked for Chess960 castling notation, via the `UCI_Chess960` option.
+static CHESS960: AtomicBool = AtomicBool::new(false);
+
+pub fn set_chess960(enabled: bool) {
+    CHESS960.store(enabled, Ordering::Release);
+}
+
+pub fn chess960() -> bool {
+    CHESS960.load(Ordering::Acquire)
+}
+
 const SOURCE_MASK: u16 = 0xFC00;
 const DESTINATION_MASK: u16 = 0x03F0;
 const PROMO_BIT: u16 = 0x0008;
@@ -163,6 +175,21 @@ impl Move {
         Square(((self.0 & DESTINATION_MASK) >> 4) as u8)
     }
 
+    /// Returns the square of the piece that this move captures, if it is a capture, given the color of the side making
+    /// the move. This is the destination square, except for en-passant, where the captured pawn lies behind the
+    /// destination square.
+    pub fn captured_square(self, side_to_move: Color) -> Option<Square> {
+        if !self.is_capture() {
+            return None;
+        }
+
+        if self.is_en_passant() {
+            Some(self.destination().towards(side_to_move.backward()))
+        } else {
+            Some(self.destination())
+        }
+    }
+
     /// Returns whether or not this move is a quiet move.
     pub fn is_quiet(self) -> bool {
         (self.0 & ATTR_MASK) == 0
@@ -188,6 +215,22 @@ impl Move {
         (self.0 & PROMO_BIT) != 0
     }
 
+    /// Returns whether or not this move is a promotion that also captures a piece.
+    pub fn is_promotion_capture(self) -> bool {
+        self.is_promotion() && self.is_capture()
+    }
+
+    /// Returns whether or not this move is a promotion that doesn't capture anything.
+    pub fn is_quiet_promotion(self) -> bool {
+        self.is_promotion() && !self.is_capture()
+    }
+
+    /// Returns whether or not this move is a capture or a promotion, i.e. whether it changes the material on the
+    /// board.
+    pub fn is_tactical(self) -> bool {
+        self.is_capture() || self.is_promotion()
+    }
+
     /// Returns whether or not this move is a kingside castle.
     pub fn is_kingside_castle(self) -> bool {
         (self.0 & ATTR_MASK) == 2
@@ -208,15 +251,30 @@ impl Move {
         self.0 == 0
     }
 
-    /// Returns an UCI-compatible string representation of this move.
+    /// Returns an UCI-compatible string representation of this move, using Chess960 castling notation if the GUI has
+    /// asked for it.
     pub fn as_uci(self) -> String {
+        self.as_uci_with(chess960())
+    }
+
+    /// Returns an UCI-compatible string representation of this move. In Chess960 notation, castling is written as the
+    /// king capturing its own rook (e.g. `e1h1`) rather than as the king's two-square move (e.g. `e1g1`).
+    pub fn as_uci_with(self, chess960: bool) -> String {
         // Quick out for this weird quirk of UCI: the null move is 0000.
         if self.is_null() {
             return "0000".to_string();
         }
 
         let mut buf = String::new();
-        if !self.is_promotion() {
+        if chess960 && self.is_castle() {
+            let rook_file = if self.is_kingside_castle() {
+                FILE_H
+            } else {
+                FILE_A
+            };
+            let rook = Square::of(self.source().rank(), rook_file);
+            write!(&mut buf, "{}{}", self.source(), rook).unwrap();
+        } else if !self.is_promotion() {
             write!(&mut buf, "{}{}", self.source(), self.destination()).unwrap();
         } else {
             write!(
@@ -232,8 +290,51 @@ impl Move {
         buf
     }
 
-    /// Parses the UCI representation of a move into a Move.
+    /// Parses the coordinates of a UCI move without reference to a position: the source square, the destination square,
+    /// and the promotion piece, if there is one. The null move `0000` parses to the squares of [`Move::null`].
+    ///
+    /// The kind of move (capture, castle, and so on) can't be known without a position; use [`Move::from_uci`] for
+    /// that.
+    pub fn from_uci_squares(move_str: &str) -> Option<(Square, Square, Option<PieceKind>)> {
+        if move_str == "0000" {
+            let null = Move::null();
+            return Some((null.source(), null.destination(), None));
+        }
+
+        let move_chrs: Vec<_> = move_str.chars().collect();
+        if move_chrs.len() != 4 && move_chrs.len() != 5 {
+            return None;
+        }
+
+        let source_file = File::try_from(move_chrs[0]).ok()?;
+        let source_rank = Rank::try_from(move_chrs[1]).ok()?;
+        let dest_file = File::try_from(move_chrs[2]).ok()?;
+        let dest_rank = Rank::try_from(move_chrs[3]).ok()?;
+        let promotion = match move_chrs.get(4) {
+            None => None,
+            Some('n') => Some(PieceKind::Knight),
+            Some('b') => Some(PieceKind::Bishop),
+            Some('r') => Some(PieceKind::Rook),
+            Some('q') => Some(PieceKind::Queen),
+            Some(_) => return None,
+        };
+
+        Some((
+            Square::of(source_rank, source_file),
+            Square::of(dest_rank, dest_file),
+            promotion,
+        ))
+    }
+
+    /// Parses the UCI representation of a move into a Move, using Chess960 castling notation if the GUI has asked for
+    /// it.
     pub fn from_uci(pos: &Position, move_str: &str) -> Option<Move> {
+        Move::from_uci_with(pos, move_str, chess960())
+    }
+
+    /// Parses the UCI representation of a move into a Move. In Chess960 notation, castling is written as the king
+    /// capturing its own rook; see [`Move::as_uci_with`].
+    pub fn from_uci_with(pos: &Position, move_str: &str, chess960: bool) -> Option<Move> {
         // UCI encodes a move as the source square, followed by the destination
         // square, and optionally followed by the promotion piece if necessary.
         let move_chrs: Vec<_> = move_str.chars().collect();
@@ -279,10 +380,10 @@ impl Move {
         //   1.3. Is the destination square on a promotion rank? =? Promotion
         //   1.4. else => Quiet
         // 2. Is the moving piece a king?
-        //   2.1. Is the target the square to the right of the kingside rook? =>
-        //   KingsideCastle
-        //   2.2. Is the target the square to the right of the queenside rook? =>
-        //   QueensideCastle
+        //   2.1. Is the target the square to the right of the kingside rook (or,
+        //   in Chess960 notation, the kingside rook itself)? => KingsideCastle
+        //   2.2. Is the target the square to the right of the queenside rook (or,
+        //   in Chess960 notation, the queenside rook itself)? => QueensideCastle
         //   2.3. Is there piece on the target square? => Capture
         //   2.4. else => Quiet
         // 3. Is there a piece on the target square? => Capture
@@ -358,15 +459,25 @@ impl Move {
                     Color::Black => (G8, C8, E8),
                 };
 
+            // In Chess960 notation, the king "captures" its own rook.
+            let (kingside_target, queenside_target) = if chess960 {
+                (
+                    Square::of(king_start.rank(), FILE_H),
+                    Square::of(king_start.rank(), FILE_A),
+                )
+            } else {
+                (kingside_rook_adjacent, queenside_rook_adjacent)
+            };
+
             if king_start == source {
                 // 2.1. Is the target of the square to the left of the kingside rook?
-                if kingside_rook_adjacent == dest {
-                    return Some(Move::kingside_castle(source, dest));
+                if kingside_target == dest {
+                    return Some(Move::kingside_castle(source, kingside_rook_adjacent));
                 }
 
                 // 2.2. Is the target the square to the right of the queenside rook?
-                if queenside_rook_adjacent == dest {
-                    return Some(Move::queenside_castle(source, dest));
+                if queenside_target == dest {
+                    return Some(Move::queenside_castle(source, queenside_rook_adjacent));
                 }
             }
 
@@ -406,6 +517,25 @@ mod tests {
     use super::Move;
     use crate::{core::*, position::Position};
 
+    #[test]
+    fn uci_squares() {
+        assert_eq!(Some((E2, E4, None)), Move::from_uci_squares("e2e4"));
+        assert_eq!(
+            Some((E7, E8, Some(PieceKind::Queen))),
+            Move::from_uci_squares("e7e8q")
+        );
+
+        let null = Move::null();
+        assert_eq!(
+            Some((null.source(), null.destination(), None)),
+            Move::from_uci_squares("0000")
+        );
+
+        assert_eq!(None, Move::from_uci_squares("e2"));
+        assert_eq!(None, Move::from_uci_squares("e7e8k"));
+        assert_eq!(None, Move::from_uci_squares("i2e4"));
+    }
+
     #[test]
     fn quiet() {
         let quiet = Move::quiet(A4, A5);
@@ -509,6 +639,56 @@ mod tests {
         assert!(!mv.is_capture());
     }
 
+    #[test]
+    fn tactical_predicates() {
+        let quiet = Move::quiet(A1, A2);
+        let capture = Move::capture(A1, A2);
+        let en_passant = Move::en_passant(E5, D6);
+        let promotion = Move::promotion(A7, A8, PieceKind::Queen);
+        let promotion_capture = Move::promotion_capture(A7, B8, PieceKind::Knight);
+        let castle = Move::kingside_castle(E1, G1);
+
+        assert!(promotion_capture.is_promotion_capture());
+        assert!(!promotion.is_promotion_capture());
+        assert!(!capture.is_promotion_capture());
+
+        assert!(promotion.is_quiet_promotion());
+        assert!(!promotion_capture.is_quiet_promotion());
+        assert!(!quiet.is_quiet_promotion());
+
+        for mov in [capture, en_passant, promotion, promotion_capture] {
+            assert!(mov.is_tactical(), "{} should be tactical", mov);
+        }
+        for mov in [quiet, castle, Move::double_pawn_push(E2, E4)] {
+            assert!(!mov.is_tactical(), "{} shouldn't be tactical", mov);
+        }
+    }
+
+    #[test]
+    fn captured_square() {
+        assert_eq!(
+            Some(D5),
+            Move::en_passant(E5, D6).captured_square(Color::White)
+        );
+        assert_eq!(
+            Some(E4),
+            Move::en_passant(D4, E3).captured_square(Color::Black)
+        );
+        assert_eq!(
+            Some(A2),
+            Move::capture(A1, A2).captured_square(Color::White)
+        );
+        assert_eq!(
+            Some(B8),
+            Move::promotion_capture(A7, B8, PieceKind::Queen).captured_square(Color::White)
+        );
+        assert_eq!(None, Move::quiet(A1, A2).captured_square(Color::White));
+        assert_eq!(
+            None,
+            Move::kingside_castle(E1, G1).captured_square(Color::White)
+        );
+    }
+
     #[test]
     fn uci_null() {
         let mv = Move::null();
@@ -533,6 +713,36 @@ mod tests {
         assert_eq!("e1g1", mv.as_uci());
     }
 
+    #[test]
+    fn uci_chess960_castles() {
+        assert_eq!("e1h1", Move::kingside_castle(E1, G1).as_uci_with(true));
+        assert_eq!("e8a8", Move::queenside_castle(E8, C8).as_uci_with(true));
+        assert_eq!("e1f1", Move::quiet(E1, F1).as_uci_with(true));
+    }
+
+    #[test]
+    fn uci_chess960_parse_castles() {
+        let pos = Position::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        assert_eq!(
+            Move::kingside_castle(E1, G1),
+            Move::from_uci_with(&pos, "e1h1", true).unwrap()
+        );
+        assert_eq!(
+            Move::queenside_castle(E1, C1),
+            Move::from_uci_with(&pos, "e1a1", true).unwrap()
+        );
+
+        // The standard notation is just a king move in Chess960.
+        assert_eq!(
+            Move::quiet(E1, G1),
+            Move::from_uci_with(&pos, "e1g1", true).unwrap()
+        );
+        assert_eq!(
+            Move::kingside_castle(E1, G1),
+            Move::from_uci_with(&pos, "e1g1", false).unwrap()
+        );
+    }
+
     #[test]
     fn uci_nullmove() {
         let pos = Position::from_start_position();
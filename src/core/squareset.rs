@@ -63,6 +63,11 @@ impl SquareSet {
         SquareSet(self.0 ^ other.0)
     }
 
+    /// Returns the squares that are in this set but not in the other.
+    pub const fn difference(self, other: SquareSet) -> SquareSet {
+        SquareSet(self.0 & !other.0)
+    }
+
     pub fn rank(self, rank: Rank) -> SquareSet {
         let rank_set = match rank {
             core::RANK_1 => SS_RANK_1,
@@ -95,7 +100,9 @@ impl SquareSet {
         self.and(file_set)
     }
 
-    /// Shifts all squares in the SquareSet one square in the given direction.
+    /// Shifts all squares in the SquareSet one square in the given direction. Squares that would be shifted off of the
+    /// board are dropped, including those that would otherwise wrap around from one side of the board to the other
+    /// (e.g. from the h-file onto the a-file of the next rank).
     pub const fn shift(self, direction: Direction) -> SquareSet {
         match direction {
             Direction::North => SquareSet(self.0 << 8),
@@ -112,6 +119,31 @@ impl SquareSet {
     pub fn bits(self) -> u64 {
         self.0
     }
+
+    /// Returns the lowest-indexed square in this set, if the set is not empty.
+    pub const fn lsb(self) -> Option<Square> {
+        if self.0 == 0 {
+            None
+        } else {
+            Some(Square(self.0.trailing_zeros() as u8))
+        }
+    }
+
+    /// Returns the highest-indexed square in this set, if the set is not empty.
+    pub const fn msb(self) -> Option<Square> {
+        if self.0 == 0 {
+            None
+        } else {
+            Some(Square(63 - self.0.leading_zeros() as u8))
+        }
+    }
+
+    /// Removes and returns the lowest-indexed square in this set, if the set is not empty.
+    pub fn pop_lsb(&mut self) -> Option<Square> {
+        let square = self.lsb()?;
+        self.0 &= self.0 - 1;
+        Some(square)
+    }
 }
 
 impl ops::BitOr for SquareSet {
@@ -146,6 +178,38 @@ impl ops::BitXor for SquareSet {
     }
 }
 
+impl ops::Sub for SquareSet {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        self.difference(rhs)
+    }
+}
+
+impl ops::BitOrAssign for SquareSet {
+    fn bitor_assign(&mut self, rhs: Self) {
+        *self = self.or(rhs);
+    }
+}
+
+impl ops::BitAndAssign for SquareSet {
+    fn bitand_assign(&mut self, rhs: Self) {
+        *self = self.and(rhs);
+    }
+}
+
+impl ops::BitXorAssign for SquareSet {
+    fn bitxor_assign(&mut self, rhs: Self) {
+        *self = self.xor(rhs);
+    }
+}
+
+impl ops::SubAssign for SquareSet {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = self.difference(rhs);
+    }
+}
+
 impl IntoIterator for SquareSet {
     type Item = Square;
     type IntoIter = SquareSetIterator;
@@ -216,13 +280,10 @@ impl Iterator for SquareSetIterator {
     type Item = Square;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.0 == 0 {
-            None
-        } else {
-            let next = self.0.trailing_zeros() as u8;
-            self.0 &= self.0 - 1;
-            Some(Square(next))
-        }
+        let mut set = SquareSet(self.0);
+        let next = set.pop_lsb();
+        self.0 = set.0;
+        next
     }
 }
 
@@ -260,6 +321,39 @@ mod tests {
         assert_eq!(squares, vec![A3, A4, A5]);
     }
 
+    #[test]
+    fn pop_lsb_drains_in_order() {
+        let mut set = SquareSet::empty();
+        set.insert(H8);
+        set.insert(C2);
+        set.insert(A1);
+        set.insert(E4);
+        let mut squares = vec![];
+        while let Some(square) = set.pop_lsb() {
+            squares.push(square);
+        }
+
+        assert_eq!(squares, vec![A1, C2, E4, H8]);
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn lsb_msb() {
+        let mut set = SquareSet::empty();
+        assert_eq!(set.lsb(), None);
+        assert_eq!(set.msb(), None);
+
+        set.insert(B3);
+        set.insert(G6);
+        let bits = set.bits();
+        let lowest = (0..64u8).find(|i| bits & (1 << i) != 0).unwrap();
+        let highest = (0..64u8).rev().find(|i| bits & (1 << i) != 0).unwrap();
+        assert_eq!(set.lsb(), Some(Square::try_from(lowest).unwrap()));
+        assert_eq!(set.msb(), Some(Square::try_from(highest).unwrap()));
+        assert_eq!(set.lsb(), Some(B3));
+        assert_eq!(set.msb(), Some(G6));
+    }
+
     #[test]
     fn rank() {
         let set = SquareSet::all();
@@ -287,4 +381,50 @@ mod tests {
         let result = set.shift(Direction::NorthEast);
         assert!(result.is_empty());
     }
+
+    #[test]
+    fn diagonal_shifts_do_not_wrap() {
+        let file_a = SquareSet::all().file(FILE_A);
+        assert_eq!(
+            SquareSet::all().file(FILE_B) - SS_RANK_1,
+            file_a.shift(Direction::NorthEast)
+        );
+        assert!(file_a.shift(Direction::NorthWest).is_empty());
+        assert!(file_a.shift(Direction::SouthWest).is_empty());
+
+        let file_h = SquareSet::all().file(FILE_H);
+        assert!(file_h.shift(Direction::NorthEast).is_empty());
+        assert!(file_h.shift(Direction::SouthEast).is_empty());
+        assert_eq!(
+            SquareSet::all().file(FILE_G) - SS_RANK_8,
+            file_h.shift(Direction::SouthWest)
+        );
+    }
+
+    #[test]
+    fn set_difference() {
+        let rank_1 = SquareSet::all().rank(RANK_1);
+        let file_a = SquareSet::all().file(FILE_A);
+        let difference = rank_1 - file_a;
+        assert_eq!(7, difference.len());
+        assert!(!difference.contains(A1));
+        assert!(difference.contains(B1));
+        assert_eq!(rank_1 & !file_a, difference);
+
+        let mut set = rank_1;
+        set -= file_a;
+        assert_eq!(difference, set);
+        set |= file_a;
+        assert_eq!(rank_1 | file_a, set);
+    }
+
+    #[test]
+    fn display_draws_a_board() {
+        let drawing = SS_RANK_1.to_string();
+        let rows: Vec<_> = drawing.lines().collect();
+        assert_eq!(" .  .  .  .  .  .  .  . | 8", rows[0]);
+        assert_eq!(" .  .  .  .  .  .  .  . | 2", rows[6]);
+        assert_eq!(" 1  1  1  1  1  1  1  1 | 1", rows[7]);
+        assert_eq!(" a  b  c  d  e  f  g  h ", rows[9]);
+    }
 }
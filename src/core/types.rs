@@ -6,11 +6,13 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use std::{convert::TryFrom, fmt};
+use std::{convert::TryFrom, fmt, iter};
 
 use bitflags::bitflags;
 use thiserror::Error;
 
+use crate::core::SquareSet;
+
 #[derive(Debug, Error)]
 pub enum SquareParseError {
     #[error("square index out of range: {0}")]
@@ -64,14 +66,75 @@ impl Square {
     }
 
     /// Returns the closest square in the given direction. Invalid if the requested direction goes off of the
-    /// board.
+    /// board, including around the side of it; debug builds panic if it does. Use `checked_towards` if the square might
+    /// be on the edge of the board.
     pub const fn towards(self, dir: Direction) -> Square {
+        debug_assert!(
+            self.checked_towards(dir).is_some(),
+            "towards stepped off the board"
+        );
         self.plus(dir.as_vector())
     }
 
+    /// Returns the closest square in the given direction, or `None` if the requested direction goes off of the board,
+    /// either past the first or last rank or around the side of the board.
+    pub const fn checked_towards(self, dir: Direction) -> Option<Square> {
+        let (file_delta, rank_delta) = dir.as_deltas();
+        let file = self.file().0 as i32 + file_delta;
+        let rank = self.rank().0 as i32 + rank_delta;
+        if file < 0 || file > 7 || rank < 0 || rank > 7 {
+            return None;
+        }
+
+        Some(self.plus(dir.as_vector()))
+    }
+
     pub const fn as_u8(self) -> u8 {
         self.0
     }
+
+    /// Returns the color of this square on the chessboard: `White` for light squares and `Black` for dark squares.
+    pub const fn color(self) -> Color {
+        if (self.rank().0 + self.file().0) % 2 == 0 {
+            Color::Black
+        } else {
+            Color::White
+        }
+    }
+
+    /// Returns the number of king moves it takes to get from this square to another square.
+    pub const fn distance(self, other: Square) -> u8 {
+        let rank_distance = self.rank().0.abs_diff(other.rank().0);
+        let file_distance = self.file().0.abs_diff(other.file().0);
+        if rank_distance > file_distance {
+            rank_distance
+        } else {
+            file_distance
+        }
+    }
+
+    /// Returns the square in the same rank on the opposite side of the board, mirroring the files: a1 becomes h1, and
+    /// d4 becomes e4.
+    pub const fn flip_horizontal(self) -> Square {
+        Square(self.0 ^ 7)
+    }
+
+    /// Returns the square that this square ends up on when the board is turned around: a1 becomes h8, and e1 becomes
+    /// d8.
+    pub const fn rotate_180(self) -> Square {
+        Square(self.0 ^ 63)
+    }
+
+    /// Returns the squares from this square (exclusive) to the edge of the board in the given direction, closest
+    /// first. The ray is empty if this square is already on the edge in that direction.
+    pub fn ray_squares(self, dir: Direction) -> impl Iterator<Item = Square> {
+        let mut current = SquareSet::empty();
+        current.insert(self);
+        iter::from_fn(move || {
+            current = current.shift(dir);
+            current.lsb()
+        })
+    }
 }
 
 impl TryFrom<u8> for Square {
@@ -160,6 +223,31 @@ pub const H8: Square = Square(63);
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct Rank(u8);
 
+impl Rank {
+    pub const fn as_u8(self) -> u8 {
+        self.0
+    }
+
+    /// Returns this rank as seen from the given color's side of the board, so that the rank a color's pieces start on
+    /// is always `RANK_1`. For White, this is the rank itself; for Black, it's the rank mirrored vertically.
+    pub const fn relative_to(self, color: Color) -> Rank {
+        match color {
+            Color::White => self,
+            Color::Black => Rank(7 - self.0),
+        }
+    }
+
+    /// Returns the rank one step forward from this one, from the perspective of the given color, or `None` if this is
+    /// the last rank for that color.
+    pub const fn up(self, color: Color) -> Option<Rank> {
+        match color {
+            Color::White if self.0 < 7 => Some(Rank(self.0 + 1)),
+            Color::Black if self.0 > 0 => Some(Rank(self.0 - 1)),
+            _ => None,
+        }
+    }
+}
+
 impl TryFrom<u8> for Rank {
     type Error = RankParseError;
 
@@ -226,6 +314,20 @@ impl File {
     pub const fn as_u8(self) -> u8 {
         self.0
     }
+
+    /// Returns the squares on the files immediately to either side of this one.
+    pub fn adjacent(self) -> SquareSet {
+        let mut files = SquareSet::empty();
+        if self.0 > 0 {
+            files |= SquareSet::all().file(File(self.0 - 1));
+        }
+
+        if self.0 < 7 {
+            files |= SquareSet::all().file(File(self.0 + 1));
+        }
+
+        files
+    }
 }
 
 impl TryFrom<u8> for File {
@@ -301,6 +403,37 @@ impl Color {
             Color::Black => Color::White,
         }
     }
+
+    /// Returns the direction that this color's pawns move in.
+    pub const fn forward(self) -> Direction {
+        match self {
+            Color::White => Direction::North,
+            Color::Black => Direction::South,
+        }
+    }
+
+    /// Returns the direction towards this color's side of the board.
+    pub const fn backward(self) -> Direction {
+        match self {
+            Color::White => Direction::South,
+            Color::Black => Direction::North,
+        }
+    }
+
+    /// Returns the rank that this color's pawns promote on.
+    pub const fn promotion_rank(self) -> Rank {
+        RANK_8.relative_to(self)
+    }
+
+    /// Returns the rank that this color's pawns start on, and so the only rank they can push two squares from.
+    pub const fn double_push_rank(self) -> Rank {
+        RANK_2.relative_to(self)
+    }
+
+    /// Returns the rank of the en-passant square when this color is the one capturing en-passant.
+    pub const fn en_passant_rank(self) -> Rank {
+        RANK_6.relative_to(self)
+    }
 }
 
 #[repr(u8)]
@@ -479,6 +612,21 @@ pub enum Direction {
 }
 
 impl Direction {
+    /// Returns an iterator over all eight directions, clockwise from North.
+    pub fn all() -> ::std::array::IntoIter<Direction, 8> {
+        [
+            Direction::North,
+            Direction::NorthEast,
+            Direction::East,
+            Direction::SouthEast,
+            Direction::South,
+            Direction::SouthWest,
+            Direction::West,
+            Direction::NorthWest,
+        ]
+        .into_iter()
+    }
+
     pub const fn as_vector(self) -> i32 {
         match self {
             Direction::North => 8,
@@ -492,6 +640,33 @@ impl Direction {
         }
     }
 
+    /// Returns the direction to step in to get from one square to another, if the two squares are different and share a
+    /// rank, file, or diagonal.
+    pub fn between(a: Square, b: Square) -> Option<Direction> {
+        let file_delta = b.file().0 as i32 - a.file().0 as i32;
+        let rank_delta = b.rank().0 as i32 - a.rank().0 as i32;
+        let aligned = file_delta == 0 || rank_delta == 0 || file_delta.abs() == rank_delta.abs();
+        if a == b || !aligned {
+            return None;
+        }
+
+        Direction::all().find(|dir| dir.as_deltas() == (file_delta.signum(), rank_delta.signum()))
+    }
+
+    /// Returns the change in file and rank, respectively, of a single step in this direction.
+    pub const fn as_deltas(self) -> (i32, i32) {
+        match self {
+            Direction::North => (0, 1),
+            Direction::NorthEast => (1, 1),
+            Direction::East => (1, 0),
+            Direction::SouthEast => (1, -1),
+            Direction::South => (0, -1),
+            Direction::SouthWest => (-1, -1),
+            Direction::West => (-1, 0),
+            Direction::NorthWest => (-1, 1),
+        }
+    }
+
     pub const fn reverse(self) -> Direction {
         match self {
             Direction::North => Direction::South,
@@ -518,6 +693,54 @@ bitflags! {
     }
 }
 
+impl CastleStatus {
+    /// Returns both castling rights for the given color.
+    pub const fn for_color(color: Color) -> CastleStatus {
+        match color {
+            Color::White => CastleStatus::WHITE,
+            Color::Black => CastleStatus::BLACK,
+        }
+    }
+
+    /// Returns the kingside castling right for the given color.
+    pub const fn kingside(color: Color) -> CastleStatus {
+        match color {
+            Color::White => CastleStatus::WHITE_KINGSIDE,
+            Color::Black => CastleStatus::BLACK_KINGSIDE,
+        }
+    }
+
+    /// Returns the queenside castling right for the given color.
+    pub const fn queenside(color: Color) -> CastleStatus {
+        match color {
+            Color::White => CastleStatus::WHITE_QUEENSIDE,
+            Color::Black => CastleStatus::BLACK_QUEENSIDE,
+        }
+    }
+}
+
+/// Formats castling rights as they appear in FEN, e.g. `KQkq`, or `-` if neither side can castle.
+impl fmt::Display for CastleStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "-");
+        }
+
+        for (right, c) in [
+            (CastleStatus::WHITE_KINGSIDE, 'K'),
+            (CastleStatus::WHITE_QUEENSIDE, 'Q'),
+            (CastleStatus::BLACK_KINGSIDE, 'k'),
+            (CastleStatus::BLACK_QUEENSIDE, 'q'),
+        ] {
+            if self.contains(right) {
+                write!(f, "{}", c)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
 macro_rules! type_iterator {
     ($name:ident, $type:ident, $max:expr) => {
         pub struct $name(u8, u8);
@@ -572,8 +795,8 @@ pub fn files() -> AllFiles {
     AllFiles::default()
 }
 
-pub fn piece_kinds() -> ::std::vec::IntoIter<PieceKind> {
-    vec![
+pub fn piece_kinds() -> ::std::array::IntoIter<PieceKind, 6> {
+    [
         PieceKind::Pawn,
         PieceKind::Bishop,
         PieceKind::Knight,
@@ -584,6 +807,195 @@ pub fn piece_kinds() -> ::std::vec::IntoIter<PieceKind> {
     .into_iter()
 }
 
-pub fn colors() -> ::std::vec::IntoIter<Color> {
-    vec![Color::White, Color::Black].into_iter()
+pub fn colors() -> ::std::array::IntoIter<Color, 2> {
+    [Color::White, Color::Black].into_iter()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::*;
+
+    #[test]
+    fn ray_north_from_a1() {
+        let ray: Vec<_> = A1.ray_squares(Direction::North).collect();
+        assert_eq!(ray, vec![A2, A3, A4, A5, A6, A7, A8]);
+    }
+
+    #[test]
+    fn ray_off_the_edge_is_empty() {
+        assert_eq!(0, A8.ray_squares(Direction::North).count());
+        assert_eq!(0, H4.ray_squares(Direction::East).count());
+        assert_eq!(0, A4.ray_squares(Direction::NorthWest).count());
+    }
+
+    #[test]
+    fn ray_diagonal_stops_at_edge() {
+        let ray: Vec<_> = F1.ray_squares(Direction::NorthEast).collect();
+        assert_eq!(ray, vec![G2, H3]);
+    }
+
+    #[test]
+    fn direction_between() {
+        assert_eq!(Some(Direction::North), Direction::between(E1, E8));
+        assert_eq!(Some(Direction::West), Direction::between(H4, A4));
+        assert_eq!(Some(Direction::NorthEast), Direction::between(A1, H8));
+        assert_eq!(Some(Direction::SouthWest), Direction::between(H8, A1));
+        assert_eq!(Some(Direction::NorthWest), Direction::between(F2, B6));
+        assert_eq!(Some(Direction::SouthEast), Direction::between(C3, D2));
+        assert_eq!(None, Direction::between(E4, E4));
+        assert_eq!(None, Direction::between(G1, F3));
+        assert_eq!(None, Direction::between(A1, H7));
+    }
+
+    #[test]
+    fn squares_between_aligned_squares() {
+        let mut between = SquareSet::empty();
+        for sq in [B2, C3, D4, E5, F6, G7] {
+            between.insert(sq);
+        }
+
+        assert_eq!(between, squares_between(A1, H8));
+        assert_eq!(between, squares_between(H8, A1));
+        assert!(squares_between(E1, E2).is_empty());
+        for sq in squares_between(E1, E8) {
+            assert_eq!(Some(Direction::North), Direction::between(E1, sq));
+        }
+
+        assert_eq!(6, squares_between(E1, E8).len());
+    }
+
+    #[test]
+    fn squares_between_unaligned_squares() {
+        assert!(squares_between(G1, F3).is_empty());
+        assert!(squares_between(A1, H7).is_empty());
+        assert!(squares_between(D4, D4).is_empty());
+    }
+
+    #[test]
+    fn square_color() {
+        assert_eq!(Color::Black, A1.color());
+        assert_eq!(Color::White, H1.color());
+        assert_eq!(Color::White, D1.color());
+        assert_eq!(Color::Black, E1.color());
+        assert_eq!(Color::Black, H8.color());
+    }
+
+    #[test]
+    fn rank_relative_to() {
+        assert_eq!(RANK_2, RANK_2.relative_to(Color::White));
+        assert_eq!(RANK_7, RANK_2.relative_to(Color::Black));
+        assert_eq!(RANK_1, RANK_8.relative_to(Color::Black));
+    }
+
+    #[test]
+    fn rank_up() {
+        assert_eq!(Some(RANK_3), RANK_2.up(Color::White));
+        assert_eq!(Some(RANK_1), RANK_2.up(Color::Black));
+        assert_eq!(None, RANK_8.up(Color::White));
+        assert_eq!(None, RANK_1.up(Color::Black));
+    }
+
+    #[test]
+    fn file_adjacent() {
+        assert_eq!(SS_FILE_B, FILE_A.adjacent());
+        assert_eq!(SS_FILE_G, FILE_H.adjacent());
+        assert_eq!(SS_FILE_C | SS_FILE_E, FILE_D.adjacent());
+    }
+
+    #[test]
+    fn checked_towards() {
+        assert_eq!(None, A1.checked_towards(Direction::West));
+        assert_eq!(None, A1.checked_towards(Direction::South));
+        assert_eq!(Some(A2), A1.checked_towards(Direction::North));
+        assert_eq!(Some(B2), A1.checked_towards(Direction::NorthEast));
+        assert_eq!(None, H4.checked_towards(Direction::East));
+        assert_eq!(None, H4.checked_towards(Direction::NorthEast));
+        assert_eq!(None, A5.checked_towards(Direction::SouthWest));
+        assert_eq!(None, E8.checked_towards(Direction::NorthWest));
+        for sq in squares() {
+            for dir in Direction::all() {
+                if let Some(next) = sq.checked_towards(dir) {
+                    assert_eq!(1, sq.distance(next));
+                    assert_eq!(sq.towards(dir), next);
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic]
+    fn towards_around_the_side_of_the_board() {
+        let _ = H4.towards(Direction::East);
+    }
+
+    #[test]
+    fn square_distance() {
+        assert_eq!(0, E4.distance(E4));
+        assert_eq!(7, A1.distance(H8));
+        assert_eq!(7, A1.distance(A8));
+        assert_eq!(2, E4.distance(F6));
+        assert_eq!(E4.distance(F6), F6.distance(E4));
+    }
+
+    #[test]
+    fn color_directions() {
+        assert_eq!(Direction::North, Color::White.forward());
+        assert_eq!(Direction::South, Color::Black.forward());
+        for color in colors() {
+            assert_eq!(color.forward().reverse(), color.backward());
+        }
+    }
+
+    #[test]
+    fn color_ranks() {
+        assert_eq!(RANK_8, Color::White.promotion_rank());
+        assert_eq!(RANK_1, Color::Black.promotion_rank());
+        assert_eq!(RANK_2, Color::White.double_push_rank());
+        assert_eq!(RANK_7, Color::Black.double_push_rank());
+        assert_eq!(RANK_6, Color::White.en_passant_rank());
+        assert_eq!(RANK_3, Color::Black.en_passant_rank());
+    }
+
+    #[test]
+    fn castle_status_display() {
+        assert_eq!("KQ", CastleStatus::WHITE.to_string());
+        assert_eq!("KQkq", CastleStatus::all().to_string());
+        assert_eq!(
+            "Kq",
+            (CastleStatus::WHITE_KINGSIDE | CastleStatus::BLACK_QUEENSIDE).to_string()
+        );
+        assert_eq!("-", CastleStatus::NONE.to_string());
+    }
+
+    #[test]
+    fn castle_status_per_color() {
+        assert_eq!(CastleStatus::WHITE, CastleStatus::for_color(Color::White));
+        assert_eq!(CastleStatus::BLACK, CastleStatus::for_color(Color::Black));
+        assert_eq!(
+            CastleStatus::WHITE_KINGSIDE,
+            CastleStatus::kingside(Color::White)
+        );
+        assert_eq!(
+            CastleStatus::BLACK_KINGSIDE,
+            CastleStatus::kingside(Color::Black)
+        );
+        assert_eq!(
+            CastleStatus::WHITE_QUEENSIDE,
+            CastleStatus::queenside(Color::White)
+        );
+        assert_eq!(
+            CastleStatus::BLACK_QUEENSIDE,
+            CastleStatus::queenside(Color::Black)
+        );
+    }
+
+    #[test]
+    fn all_directions() {
+        let directions: Vec<_> = Direction::all().collect();
+        assert_eq!(8, directions.len());
+        for dir in Direction::all() {
+            assert!(directions.contains(&dir.reverse()));
+        }
+    }
 }
@@ -7,8 +7,15 @@
 // except according to those terms.
 
 mod analysis;
+pub mod cache;
 mod eval;
+mod params;
+pub mod tuning;
 mod value;
 
-pub use eval::evaluate;
+pub use eval::{
+    evaluate, evaluate_detailed, evaluate_stm, evaluate_stm_with_bounds, evaluate_with_bounds,
+    evaluate_with_params, EvalBreakdown, EvalTerm,
+};
+pub use params::{EvalParams, EvalParamsError};
 pub use value::{UnpackedValue, Value};
@@ -9,10 +9,7 @@
 use std::cell::OnceCell;
 
 use crate::{
-    core::{
-        SquareSet, SS_FILES, SS_FILE_A, SS_FILE_B, SS_FILE_C, SS_FILE_D, SS_FILE_E, SS_FILE_F,
-        SS_FILE_G, SS_FILE_H, SS_RANKS, *,
-    },
+    core::{SquareSet, SS_FILES, SS_RANKS, *},
     movegen,
     position::Position,
 };
@@ -51,8 +48,11 @@ pub struct Analysis<'a> {
     doubled_pawns: OnceAnalysis<SquareSet>,
     isolated_pawns: OnceAnalysis<SquareSet>,
     backward_pawns: OnceAnalysis<SquareSet>,
+    passed_pawns: OnceAnalysis<SquareSet>,
+    pawn_attack_span: OnceAnalysis<SquareSet>,
     moves: OnceAnalysis<Vec<Move>>,
     attacked_by: OnceAnalysis<[OnceCell<SquareSet>; 6]>,
+    attack_count: OnceAnalysis<[u8; 64]>,
 }
 
 impl<'a> Analysis<'a> {
@@ -62,8 +62,11 @@ impl<'a> Analysis<'a> {
             doubled_pawns: OnceAnalysis::new(),
             isolated_pawns: OnceAnalysis::new(),
             backward_pawns: OnceAnalysis::new(),
+            passed_pawns: OnceAnalysis::new(),
+            pawn_attack_span: OnceAnalysis::new(),
             moves: OnceAnalysis::new(),
             attacked_by: OnceAnalysis::new(),
+            attack_count: OnceAnalysis::new(),
         }
     }
 
@@ -85,15 +88,41 @@ impl<'a> Analysis<'a> {
             .clone()
     }
 
+    pub fn passed_pawns(&self, color: Color) -> SquareSet {
+        *self
+            .passed_pawns
+            .get_or_init(color, || passed_pawns(self.pos, color))
+    }
+
+    /// Returns the squares that the given color's pawns attack, or could attack by advancing. Squares outside of this set
+    /// can never be attacked by those pawns.
+    pub fn pawn_attack_span(&self, color: Color) -> SquareSet {
+        *self
+            .pawn_attack_span
+            .get_or_init(color, || pawn_attack_span(self.pos, color))
+    }
+
+    /// Returns the passed pawns that have a fellow passed pawn on an adjacent file.
+    pub fn connected_passed_pawns(&self, color: Color) -> SquareSet {
+        let passed_pawns = self.passed_pawns(color);
+        let mut answer = SquareSet::empty();
+        for pawn in passed_pawns {
+            if !(passed_pawns & pawn.file().adjacent()).is_empty() {
+                answer.insert(pawn);
+            }
+        }
+
+        answer
+    }
+
     pub fn moves(&self, color: Color) -> &[Move] {
         self.moves.get_or_init(color, || {
             // Our move generator only operates on the current side to move. If we need to analyze the
             // other side, make a null move and analyze that instead.
-            let pos = if self.pos.side_to_move() != color {
-                self.pos.clone_and_make_move(Move::null())
-            } else {
-                self.pos.clone()
-            };
+            let mut pos = self.pos.clone();
+            if pos.side_to_move() != color {
+                let _ = pos.make_null_move();
+            }
 
             assert!(pos.side_to_move() == color);
             let mut moves = Vec::new();
@@ -107,6 +136,32 @@ impl<'a> Analysis<'a> {
         self.moves(color).len()
     }
 
+    /// Returns the number of legal moves for the given color that don't land on a square attacked by an enemy pawn.
+    /// Moves onto those squares usually just lose the piece for a pawn, so this is a better measure of how much freedom
+    /// a side has than `mobility`.
+    pub fn safe_mobility(&self, color: Color) -> usize {
+        let pawn_attacks = self.attacked_by_kind(color.toggle(), PieceKind::Pawn);
+        self.moves(color)
+            .iter()
+            .filter(|mov| !pawn_attacks.contains(mov.destination()))
+            .count()
+    }
+
+    /// Returns the squares that the piece on the given square attacks that aren't occupied by a friendly piece and
+    /// aren't attacked by an enemy pawn. Unlike `safe_mobility`, this doesn't check that moves to those squares are
+    /// legal. The set is empty if the square is empty.
+    pub fn safe_squares(&self, square: Square) -> SquareSet {
+        let piece = match self.pos.piece_at(square) {
+            Some(piece) => piece,
+            None => return SquareSet::empty(),
+        };
+
+        let occupancy = self.pos.pieces(Color::White) | self.pos.pieces(Color::Black);
+        attacks(piece.kind, piece.color, square, occupancy)
+            - self.pos.pieces(piece.color)
+            - self.attacked_by_kind(piece.color.toggle(), PieceKind::Pawn)
+    }
+
     pub fn attacked_by_kind(&self, color: Color, kind: PieceKind) -> SquareSet {
         let tables = self.attacked_by.get_or_init(color, || {
             [
@@ -123,9 +178,9 @@ impl<'a> Analysis<'a> {
         table_ref
             .get_or_init(|| {
                 let mut result = SquareSet::empty();
-                let occ = self.pos.pieces(Color::White) & self.pos.pieces(Color::Black);
+                let occ = self.pos.pieces(Color::White) | self.pos.pieces(Color::Black);
                 for piece in self.pos.pieces_of_kind(color, kind) {
-                    result = result | attacks(kind, color, piece, occ);
+                    result |= attacks(kind, color, piece, occ);
                 }
 
                 result
@@ -136,12 +191,29 @@ impl<'a> Analysis<'a> {
     pub fn attacked_by(&self, color: Color) -> SquareSet {
         let mut result = SquareSet::empty();
         for kind in piece_kinds() {
-            result = result | self.attacked_by_kind(color, kind);
+            result |= self.attacked_by_kind(color, kind);
         }
 
         result
     }
 
+    /// Returns the number of pieces of the given color that attack each square, indexed by square.
+    pub fn attack_count(&self, color: Color) -> &[u8; 64] {
+        self.attack_count.get_or_init(color, || {
+            let mut counts = [0; 64];
+            let occ = self.pos.pieces(Color::White) | self.pos.pieces(Color::Black);
+            for kind in piece_kinds() {
+                for piece in self.pos.pieces_of_kind(color, kind) {
+                    for target in attacks(kind, color, piece, occ) {
+                        counts[target.as_u8() as usize] += 1;
+                    }
+                }
+            }
+
+            counts
+        })
+    }
+
     pub fn piece_at_square_is(&self, square: Square, color: Color, kind: PieceKind) -> bool {
         if let Some(piece) = self.pos.piece_at(square) {
             return piece.color == color && piece.kind == kind;
@@ -150,7 +222,7 @@ impl<'a> Analysis<'a> {
         false
     }
 
-    pub fn position(&self) -> &Position {
+    pub fn position(&self) -> &'a Position {
         self.pos
     }
 }
@@ -199,7 +271,7 @@ fn backward_pawns(pos: &Position, color: Color) -> SquareSet {
     let pawns = pos.pawns(color);
     let mut answer = SquareSet::empty();
     for file in files() {
-        let adj_files = adjacent_files(file);
+        let adj_files = file.adjacent();
         let current_file = SquareSet::all().file(file);
         let pawns_on_current_file = pawns.and(current_file);
         let pawns_on_adjacent_files = pawns.and(adj_files);
@@ -226,11 +298,49 @@ fn backward_pawns(pos: &Position, color: Color) -> SquareSet {
     answer
 }
 
+/// Returns the set of passed pawns left by the given color: pawns with no enemy pawns in front of them, either on their
+/// own file or on an adjacent file.
+fn passed_pawns(pos: &Position, color: Color) -> SquareSet {
+    let up = color.forward();
+    let enemy_pawns = pos.pawns(color.toggle());
+    let mut answer = SquareSet::empty();
+    for pawn in pos.pawns(color) {
+        let mut ahead = SquareSet::empty();
+        let mut next = SquareSet::empty();
+        next.insert(pawn);
+        next = next.shift(up);
+        while !next.is_empty() {
+            ahead |= next;
+            next = next.shift(up);
+        }
+
+        let front_span = ahead | ahead.shift(Direction::East) | ahead.shift(Direction::West);
+        if (front_span & enemy_pawns).is_empty() {
+            answer.insert(pawn);
+        }
+    }
+
+    answer
+}
+
+fn pawn_attack_span(pos: &Position, color: Color) -> SquareSet {
+    let up = color.forward();
+    let mut span = SquareSet::empty();
+    let mut pawns = pos.pawns(color);
+    while !pawns.is_empty() {
+        span =
+            span | pawns.shift(up).shift(Direction::East) | pawns.shift(up).shift(Direction::West);
+        pawns = pawns.shift(up);
+    }
+
+    span
+}
+
 fn isolated_pawns(pos: &Position, color: Color) -> SquareSet {
     let pawns = pos.pawns(color);
     let mut answer = SquareSet::empty();
     for file in files() {
-        let adj_files = adjacent_files(file);
+        let adj_files = file.adjacent();
         let current_file = SquareSet::all().file(file);
         let pawns_on_current_file = pawns.and(current_file);
         let pawns_on_adjacent_file = pawns.and(adj_files);
@@ -246,25 +356,26 @@ fn isolated_pawns(pos: &Position, color: Color) -> SquareSet {
     answer
 }
 
-fn adjacent_files(file: File) -> SquareSet {
-    match file {
-        FILE_A => SS_FILE_B,
-        FILE_B => SS_FILE_A.or(SS_FILE_C),
-        FILE_C => SS_FILE_B.or(SS_FILE_D),
-        FILE_D => SS_FILE_C.or(SS_FILE_E),
-        FILE_E => SS_FILE_D.or(SS_FILE_F),
-        FILE_F => SS_FILE_E.or(SS_FILE_G),
-        FILE_G => SS_FILE_F.or(SS_FILE_H),
-        FILE_H => SS_FILE_G,
-        _ => unreachable!(),
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::Analysis;
     use crate::{core::*, position::Position};
 
+    #[test]
+    fn pawn_attack_span() {
+        let pos = Position::from_fen("4k3/8/8/8/8/8/3P4/4K3 w - - 0 1").unwrap();
+        let analysis = Analysis::new(&pos);
+        let span = analysis.pawn_attack_span(Color::White);
+        for square in [C3, E3, C6, E6, C8, E8] {
+            assert!(span.contains(square));
+        }
+
+        assert!(!span.contains(D3));
+        assert!(!span.contains(C2));
+        assert_eq!(12, span.len());
+        assert!(analysis.pawn_attack_span(Color::Black).is_empty());
+    }
+
     #[test]
     fn doubled_pawn_smoke() {
         let pos = Position::from_fen("8/6P1/2P5/4P3/2P2P2/PP1P2P1/P7/8 w - - 0 1").unwrap();
@@ -305,6 +416,20 @@ mod tests {
         assert!(backward_pawns.contains(D7));
     }
 
+    #[test]
+    fn passed_pawn_smoke() {
+        let pos = Position::from_fen("4k3/8/1p6/8/P2P3P/8/6p1/4K3 w - - 0 1").unwrap();
+        let analysis = Analysis::new(&pos);
+        let passed_pawns = analysis.passed_pawns(Color::White);
+        assert_eq!(2, passed_pawns.len());
+        assert!(passed_pawns.contains(D4));
+        assert!(passed_pawns.contains(H4));
+
+        let passed_pawns = analysis.passed_pawns(Color::Black);
+        assert_eq!(1, passed_pawns.len());
+        assert!(passed_pawns.contains(G2));
+    }
+
     #[test]
     fn mobility_smoke() {
         let pos = Position::from_fen("8/8/4r3/8/8/4B3/4K3/8 w - - 0 1").unwrap();
@@ -316,6 +441,45 @@ mod tests {
         assert_eq!(12, analysis.mobility(Color::Black));
     }
 
+    #[test]
+    fn safe_mobility_excludes_pawn_attacked_squares() {
+        // The knight's only moves, to b3 and c2, are both attacked by Black's pawns. The king's moves are safe.
+        let pos = Position::from_fen("7k/8/8/8/p7/3p4/8/N6K w - - 0 1").unwrap();
+        let analysis = Analysis::new(&pos);
+        assert_eq!(5, analysis.mobility(Color::White));
+        assert_eq!(3, analysis.safe_mobility(Color::White));
+        assert!(analysis.safe_squares(A1).is_empty());
+        assert_eq!(3, analysis.safe_squares(H1).len());
+        assert!(analysis.safe_squares(E4).is_empty());
+    }
+
+    #[test]
+    fn attack_count_smoke() {
+        let pos = Position::from_fen("4k3/8/8/8/8/P7/8/R6R w - - 0 1").unwrap();
+        let analysis = Analysis::new(&pos);
+        let counts = analysis.attack_count(Color::White);
+
+        // Both rooks see d1.
+        assert_eq!(2, counts[D1.as_u8() as usize]);
+
+        // The a1 rook's attack stops at its own pawn on a3.
+        assert_eq!(1, counts[A2.as_u8() as usize]);
+        assert_eq!(1, counts[A3.as_u8() as usize]);
+        assert_eq!(0, counts[A5.as_u8() as usize]);
+
+        // ... and the pawn itself attacks b4.
+        assert_eq!(1, counts[B4.as_u8() as usize]);
+    }
+
+    #[test]
+    fn attacked_by_kind_stops_at_blockers() {
+        let pos = Position::from_fen("4k3/8/8/8/8/p7/8/R3K3 w - - 0 1").unwrap();
+        let analysis = Analysis::new(&pos);
+        let rook_attacks = analysis.attacked_by_kind(Color::White, PieceKind::Rook);
+        assert!(rook_attacks.contains(A3));
+        assert!(!rook_attacks.contains(A4));
+    }
+
     #[test]
     fn isolated_pawn_smoke() {
         let pos = Position::from_fen("8/8/8/8/8/3P1P2/6P1/8 w - - 0 1").unwrap();
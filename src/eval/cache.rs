@@ -0,0 +1,148 @@
+// Copyright 2022 Sean Gillespie.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A cache of static evaluations, keyed by Zobrist hash.
+//!
+//! The search evaluates the same positions over and over, since transpositions reach them by different move orders
+//! and every iteration of iterative deepening revisits the leaves of the last one. Evaluation only depends on the
+//! position, so its result can be remembered and reused.
+//!
+//! The cache is direct-mapped: each position can only go in the slot selected by the low bits of its hash, and a new
+//! evaluation always replaces whatever was there. Each slot is a single atomic word holding the high bits of the hash,
+//! to tell apart positions that share a slot, and the evaluation itself, so threads can share the cache without any
+//! locking.
+
+#[cfg(test)]
+use std::cell::Cell;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    LazyLock, RwLock,
+};
+
+use crate::eval::Value;
+
+/// Default size of the cache, in megabytes.
+pub const DEFAULT_EVAL_CACHE_MB: usize = 4;
+
+/// Bits of a slot that hold the evaluation. The rest hold the verification key.
+const VALUE_BITS: u32 = 16;
+
+struct Cache {
+    slots: Vec<AtomicU64>,
+}
+
+impl Cache {
+    /// Creates a cache that uses at most the given number of megabytes, and at least one slot.
+    fn with_megabytes(megabytes: usize) -> Cache {
+        let bytes = megabytes.saturating_mul(1024 * 1024);
+        let count = (bytes / std::mem::size_of::<AtomicU64>()).max(1);
+        // Round down to a power of two, so that a slot can be selected by masking the hash.
+        let count = 1 << (usize::BITS - 1 - count.leading_zeros());
+        Cache::with_slots(count)
+    }
+
+    fn with_slots(count: usize) -> Cache {
+        debug_assert!(count.is_power_of_two());
+        Cache {
+            slots: (0..count).map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+
+    fn slot(&self, key: u64) -> &AtomicU64 {
+        &self.slots[key as usize & (self.slots.len() - 1)]
+    }
+
+    fn probe(&self, key: u64) -> Option<Value> {
+        let slot = self.slot(key).load(Ordering::Relaxed);
+        if slot >> VALUE_BITS == key >> VALUE_BITS && slot != 0 {
+            Some(Value::new(slot as u16 as i16))
+        } else {
+            None
+        }
+    }
+
+    fn store(&self, key: u64, value: Value) {
+        let slot = (key >> VALUE_BITS << VALUE_BITS) | value.to_bits() as u16 as u64;
+        self.slot(key).store(slot, Ordering::Relaxed);
+    }
+
+    fn clear(&self) {
+        for slot in &self.slots {
+            slot.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+static CACHE: LazyLock<RwLock<Cache>> =
+    LazyLock::new(|| RwLock::new(Cache::with_megabytes(DEFAULT_EVAL_CACHE_MB)));
+
+/// Returns the cached evaluation of the position with the given hash, if there is one.
+pub fn probe(key: u64) -> Option<Value> {
+    let value = CACHE.read().expect("eval cache poisoned").probe(key);
+    #[cfg(test)]
+    if value.is_some() {
+        HITS.with(|hits| hits.set(hits.get() + 1));
+    }
+
+    value
+}
+
+/// Remembers the evaluation of the position with the given hash.
+pub fn store(key: u64, value: Value) {
+    CACHE.read().expect("eval cache poisoned").store(key, value);
+}
+
+/// Forgets every cached evaluation.
+pub fn clear() {
+    CACHE.read().expect("eval cache poisoned").clear();
+}
+
+/// Replaces the cache with an empty one that uses at most the given number of megabytes.
+pub fn resize(megabytes: usize) {
+    *CACHE.write().expect("eval cache poisoned") = Cache::with_megabytes(megabytes);
+}
+
+// Tests run concurrently and share the cache, so hits are counted per thread.
+#[cfg(test)]
+thread_local! {
+    static HITS: Cell<u64> = Cell::new(0);
+}
+
+/// Returns the number of cache hits on the current thread.
+#[cfg(test)]
+pub fn hits() -> u64 {
+    HITS.with(|hits| hits.get())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Cache;
+    use crate::eval::Value;
+
+    #[test]
+    fn size_is_a_power_of_two() {
+        assert_eq!(1 << 17, Cache::with_megabytes(1).slots.len());
+        assert_eq!(1 << 18, Cache::with_megabytes(3).slots.len());
+        assert_eq!(1, Cache::with_megabytes(0).slots.len());
+    }
+
+    #[test]
+    fn collision_is_not_a_hit() {
+        let cache = Cache::with_slots(4);
+        cache.store(0x0001_0000_0000_0002, Value::new(-35));
+        assert_eq!(None, cache.probe(0x0002_0000_0000_0002));
+        assert_eq!(Some(Value::new(-35)), cache.probe(0x0001_0000_0000_0002));
+    }
+
+    #[test]
+    fn mate_values_round_trip() {
+        let cache = Cache::with_slots(4);
+        cache.store(0x0001_0000_0000_0001, Value::mated_in(0));
+        assert_eq!(Some(Value::mated_in(0)), cache.probe(0x0001_0000_0000_0001));
+    }
+}
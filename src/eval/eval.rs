@@ -5,32 +5,31 @@
 // <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
+use std::fmt;
+
 use crate::{
     core::*,
-    eval::{analysis::Analysis, Value},
+    eval::{analysis::Analysis, cache, EvalParams, Value},
     position::Position,
 };
 
-const QUEEN_WEIGHT: i16 = 900;
-const ROOK_WEIGHT: i16 = 500;
-const BISHOP_WEIGHT: i16 = 300;
-const KNIGHT_WEIGHT: i16 = 300;
-const PAWN_WEIGHT: i16 = 100;
-const MOBILITY_WEIGHT: i16 = 4;
-const SPACE_WEIGHT: i16 = 13;
-const THREATS_WEIGHT: i16 = 50;
-const TEMPO_WEIGHT: i16 = 15;
+/// Game phase of the starting position, computed from non-pawn material (see `game_phase`).
+const MAX_PHASE: i16 = 24;
+
+/// The most, in centipawns, that all of the evaluation terms other than material are assumed to move the evaluation
+/// away from the material balance. See `evaluate_with_bounds`.
+const LAZY_EVAL_MARGIN: i16 = 1000;
 
-// Pawn piece modifiers
-const ISOLATED_PAWN_MODIFIER: i16 = 17;
-const BACKWARD_PAWN_MODIFIER: i16 = 10;
-const DOUBLED_PAWN_MODIFIER: i16 = 10;
+/// With only opposite-colored bishops left, an evaluation is scaled down to half with this many pawns on the board, and
+/// closer to zero with fewer.
+const OPPOSITE_BISHOPS_PAWNS: i16 = 8;
 
-// Queen modifiers
-const QUEEN_EARLY_DEVELOPMENT_MODIFIER: i16 = 40;
+/// A lone king with this many legal moves or fewer is close enough to stalemate to be worth worrying about.
+const STALEMATE_RISK_MOBILITY: usize = 2;
 
 pub struct Evaluator<'a> {
     analysis: Analysis<'a>,
+    params: &'a EvalParams,
     mobility: [i16; 2],
     material: [i16; 2],
     pawn_modifiers: [i16; 2],
@@ -38,14 +37,17 @@ pub struct Evaluator<'a> {
     threats: [i16; 2],
     tempo: [i16; 2],
     positional_considerations: [i16; 2],
+    king_safety: [i16; 2],
+    mop_up: [i16; 2],
     #[cfg(feature = "trace-eval")]
     remarks: Vec<(Square, &'static str)>,
 }
 
 impl<'a> Evaluator<'a> {
-    fn new(pos: &'a Position) -> Evaluator<'a> {
+    fn new(pos: &'a Position, params: &'a EvalParams) -> Evaluator<'a> {
         Evaluator {
             analysis: Analysis::new(pos),
+            params,
             mobility: [0; 2],
             material: [0; 2],
             pawn_modifiers: [0; 2],
@@ -53,6 +55,8 @@ impl<'a> Evaluator<'a> {
             threats: [0; 2],
             tempo: [0; 2],
             positional_considerations: [0; 2],
+            king_safety: [0; 2],
+            mop_up: [0; 2],
             #[cfg(feature = "trace-eval")]
             remarks: vec![],
         }
@@ -65,7 +69,7 @@ impl<'a> Evaluator<'a> {
             if self.analysis.position().is_check(Color::White) {
                 return Value::mated_in(0);
             } else {
-                return Value::new(0);
+                return Value::DRAW;
             }
         }
         let black_mobility = self.analysis.mobility(Color::Black);
@@ -73,14 +77,11 @@ impl<'a> Evaluator<'a> {
             if self.analysis.position().is_check(Color::Black) {
                 return Value::mate_in(0);
             } else {
-                return Value::new(0);
+                return Value::DRAW;
             }
         }
 
-        // Arbitrary term reducing mobility by 4 to try and penalize low-mobility positions.
-        self.mobility[Color::White as usize] = (white_mobility as i16 - 4) * MOBILITY_WEIGHT;
-        self.mobility[Color::Black as usize] = (black_mobility as i16 - 4) * MOBILITY_WEIGHT;
-
+        self.material();
         for side in colors() {
             for kind in piece_kinds() {
                 for square in self.analysis.position().pieces_of_kind(side, kind) {
@@ -90,15 +91,17 @@ impl<'a> Evaluator<'a> {
                         PieceKind::Bishop => self.evaluate_bishop(side, square),
                         PieceKind::Rook => self.evaluate_rook(side, square),
                         PieceKind::Queen => self.evaluate_queen(side, square),
-                        PieceKind::King => {}
+                        PieceKind::King => self.evaluate_king(side, square),
                     }
                 }
             }
         }
 
-        self.tempo[self.analysis.position().side_to_move() as usize] = TEMPO_WEIGHT;
+        self.tempo[self.analysis.position().side_to_move() as usize] = self.params.tempo_weight;
+        self.safe_mobility();
         self.space();
         self.threats();
+        self.mop_up();
         let centipawns = self.final_adjustment(
             sum_terms(self.material)
                 + sum_terms(self.mobility)
@@ -106,31 +109,87 @@ impl<'a> Evaluator<'a> {
                 + sum_terms(self.space)
                 + sum_terms(self.tempo)
                 + sum_terms(self.threats)
-                + sum_terms(self.positional_considerations),
+                + sum_terms(self.positional_considerations)
+                + sum_terms(self.king_safety)
+                + sum_terms(self.mop_up),
         );
         self.dump_evaluation(centipawns);
         Value::new(centipawns)
     }
 
-    fn evaluate_knight(&mut self, side: Color, _square: Square) {
-        self.material[side as usize] += KNIGHT_WEIGHT;
+    fn evaluate_knight(&mut self, side: Color, square: Square) {
+        let mobility = self.evaluate_mobility(side, PieceKind::Knight, square);
+        self.evaluate_outpost(side, PieceKind::Knight, square);
+        self.evaluate_trapped(side, square, mobility);
     }
 
-    fn evaluate_bishop(&mut self, side: Color, _square: Square) {
-        self.material[side as usize] += BISHOP_WEIGHT;
+    fn evaluate_bishop(&mut self, side: Color, square: Square) {
+        let mobility = self.evaluate_mobility(side, PieceKind::Bishop, square);
+        self.evaluate_outpost(side, PieceKind::Bishop, square);
+        self.evaluate_trapped(side, square, mobility);
     }
 
-    fn evaluate_rook(&mut self, side: Color, _square: Square) {
-        self.material[side as usize] += ROOK_WEIGHT;
+    /// Evaluates whether a minor piece is on an outpost: a square defended by a friendly pawn that no enemy pawn can
+    /// ever attack, so the piece can only be driven off by trading it. Outposts are worth the most in the opponent's
+    /// half of the board, where the piece presses on the enemy position; elsewhere they're worth half as much.
+    fn evaluate_outpost(&mut self, side: Color, kind: PieceKind, square: Square) {
+        if !self
+            .analysis
+            .attacked_by_kind(side, PieceKind::Pawn)
+            .contains(square)
+            || self
+                .analysis
+                .pawn_attack_span(side.toggle())
+                .contains(square)
+        {
+            return;
+        }
+
+        let modifier = match kind {
+            PieceKind::Knight => self.params.knight_outpost_modifier,
+            PieceKind::Bishop => self.params.bishop_outpost_modifier,
+            _ => return,
+        };
+
+        if their_half(side).contains(square) {
+            self.positional_considerations[side as usize] += modifier;
+            self.remark(square, "piece is on an outpost");
+        } else {
+            self.positional_considerations[side as usize] += modifier / 2;
+            self.remark(square, "piece is on an outpost in its own half");
+        }
+    }
+
+    fn evaluate_rook(&mut self, side: Color, square: Square) {
+        self.evaluate_mobility(side, PieceKind::Rook, square);
+
+        // A rook behind a passed pawn supports its advance, and keeps supporting it the further the pawn goes.
+        let pos = self.analysis.position();
+        let occupancy = pos.pieces(Color::White) | pos.pieces(Color::Black);
+        let file_attacks = attacks(PieceKind::Rook, side, square, occupancy)
+            & SquareSet::all().file(square.file());
+        for pawn in file_attacks & self.analysis.passed_pawns(side) {
+            // The rook and the pawn share a file, so the lower square is the one closer to White's side of the board.
+            let behind = match side {
+                Color::White => square.as_u8() < pawn.as_u8(),
+                Color::Black => square.as_u8() > pawn.as_u8(),
+            };
+
+            if behind {
+                self.positional_considerations[side as usize] +=
+                    self.params.rook_behind_passed_pawn_modifier;
+                self.remark(square, "rook is behind a passed pawn");
+            }
+        }
     }
 
     fn evaluate_queen(&mut self, side: Color, square: Square) {
-        self.material[side as usize] += QUEEN_WEIGHT;
+        self.evaluate_mobility(side, PieceKind::Queen, square);
         let mut penalize_queen_development_before =
             |square: Square, color: Color, kind: PieceKind| {
                 if self.analysis.piece_at_square_is(square, color, kind) {
                     self.positional_considerations[color as usize] -=
-                        QUEEN_EARLY_DEVELOPMENT_MODIFIER;
+                        self.params.queen_early_development_modifier;
                     self.remark(
                         square,
                         "penalizing early queen development before this square",
@@ -153,22 +212,163 @@ impl<'a> Evaluator<'a> {
         }
     }
 
+    /// Computes the material term for each side. This only depends on how many pieces of each kind are on the board,
+    /// so it doesn't need to visit every piece.
+    fn material(&mut self) {
+        for side in colors() {
+            self.material[side as usize] = material(self.analysis.position(), side, self.params);
+        }
+    }
+
+    /// Evaluates the safe mobility of each side as a whole: the number of legal moves that don't land on a square
+    /// attacked by an enemy pawn. This is skipped entirely unless it has a weight.
+    fn safe_mobility(&mut self) {
+        if self.params.safe_mobility_weight == 0 {
+            return;
+        }
+
+        for side in colors() {
+            self.mobility[side as usize] +=
+                self.analysis.safe_mobility(side) as i16 * self.params.safe_mobility_weight;
+        }
+    }
+
+    /// Evaluates the mobility of a single piece: the number of squares it can move to that aren't occupied by a
+    /// friendly piece and aren't attacked by an enemy pawn. Each kind of piece has its own weight, since a single
+    /// extra square means much more to a knight than it does to a queen. Returns the number of safe squares.
+    fn evaluate_mobility(&mut self, side: Color, kind: PieceKind, square: Square) -> u32 {
+        let safe_squares = self.analysis.safe_squares(square);
+        let (weight, baseline) = match kind {
+            PieceKind::Knight => (
+                self.params.knight_mobility_weight,
+                self.params.knight_mobility_baseline,
+            ),
+            PieceKind::Bishop => (
+                self.params.bishop_mobility_weight,
+                self.params.bishop_mobility_baseline,
+            ),
+            PieceKind::Rook => (
+                self.params.rook_mobility_weight,
+                self.params.rook_mobility_baseline,
+            ),
+            PieceKind::Queen => (
+                self.params.queen_mobility_weight,
+                self.params.queen_mobility_baseline,
+            ),
+            PieceKind::Pawn | PieceKind::King => return 0,
+        };
+
+        self.mobility[side as usize] += (safe_squares.len() as i16 - baseline) * weight;
+        safe_squares.len()
+    }
+
+    /// Evaluates whether a minor piece is trapped: deep in the opponent's half of the board with so few safe squares
+    /// that it will be hard to extract, like a knight in the corner or a bishop shut in behind enemy pawns. `mobility`
+    /// is the piece's safe mobility, as computed by `evaluate_mobility`.
+    fn evaluate_trapped(&mut self, side: Color, square: Square, mobility: u32) {
+        if mobility < self.params.trapped_piece_mobility_threshold as u32
+            && their_half(side).contains(square)
+        {
+            self.positional_considerations[side as usize] -= self.params.trapped_piece_modifier;
+            self.remark(square, "piece is trapped");
+        }
+    }
+
+    /// Evaluates the safety of a king, based on the pawns sheltering it, the files open around it, and the enemy pieces
+    /// attacking the squares around it. King safety matters less as pieces come off the board, so the penalty is scaled
+    /// by the game phase and fades out in the endgame.
+    fn evaluate_king(&mut self, side: Color, square: Square) {
+        let pos = self.analysis.position();
+        let up = side.forward();
+        let mut king = SquareSet::empty();
+        king.insert(square);
+        let mut penalty = 0;
+        for shelter_square in king | king.shift(Direction::East) | king.shift(Direction::West) {
+            // Shelter pawns are the ones at most two squares in front of the king, on its file or an adjacent one.
+            let mut shelter = SquareSet::empty();
+            shelter.insert(shelter_square);
+            let shelter = shelter.shift(up) | shelter.shift(up).shift(up);
+            if (pos.pawns(side) & shelter).is_empty() {
+                penalty += self.params.king_missing_shelter_pawn_modifier;
+                self.remark(
+                    shelter_square,
+                    "king is missing a shelter pawn on this file",
+                );
+            }
+
+            let file = SquareSet::all().file(shelter_square.file());
+            if (pos.pawns(side) & file).is_empty() {
+                if (pos.pawns(side.toggle()) & file).is_empty() {
+                    penalty += self.params.king_open_file_modifier;
+                    self.remark(shelter_square, "king is next to an open file");
+                } else {
+                    penalty += self.params.king_half_open_file_modifier;
+                    self.remark(shelter_square, "king is next to a half-open file");
+                }
+            }
+        }
+
+        // The king zone is the king's square and every square adjacent to it. The more enemy pieces that attack the
+        // zone, and the heavier they are, the more dangerous the attack.
+        let zone = king | king_attacks(square);
+        let occupancy = pos.pieces(Color::White) | pos.pieces(Color::Black);
+        let mut attackers = 0;
+        let mut attack_weight = 0;
+        for kind in [
+            PieceKind::Knight,
+            PieceKind::Bishop,
+            PieceKind::Rook,
+            PieceKind::Queen,
+        ] {
+            for attacker in pos.pieces_of_kind(side.toggle(), kind) {
+                if !(attacks(kind, side.toggle(), attacker, occupancy) & zone).is_empty() {
+                    attackers += 1;
+                    attack_weight += king_attacker_weight(kind);
+                }
+            }
+        }
+
+        if attackers > 0 {
+            self.remark(square, "king zone is under attack");
+        }
+
+        penalty += attackers * attack_weight * self.params.king_zone_attack_modifier;
+        let scaled = penalty as i32 * game_phase(pos) as i32 / MAX_PHASE as i32;
+        self.king_safety[side as usize] -= scaled as i16;
+    }
+
     fn evaluate_pawn(&mut self, side: Color, square: Square) {
-        self.material[side as usize] += PAWN_WEIGHT;
         if self.analysis.isolated_pawns(side).contains(square) {
-            self.pawn_modifiers[side as usize] -= ISOLATED_PAWN_MODIFIER;
+            self.pawn_modifiers[side as usize] -= self.params.isolated_pawn_modifier;
             self.remark(square, "pawn is isolated");
         }
 
         if self.analysis.doubled_pawns(side).contains(square) {
-            self.pawn_modifiers[side as usize] -= DOUBLED_PAWN_MODIFIER;
+            self.pawn_modifiers[side as usize] -= self.params.doubled_pawn_modifier;
             self.remark(square, "pawn is doubled");
         }
 
         if self.analysis.backward_pawns(side).contains(square) {
-            self.pawn_modifiers[side as usize] -= BACKWARD_PAWN_MODIFIER;
+            self.pawn_modifiers[side as usize] -= self.params.backward_pawn_modifier;
             self.remark(square, "pawn is backward");
         }
+
+        if self.analysis.passed_pawns(side).contains(square) {
+            if self.analysis.connected_passed_pawns(side).contains(square) {
+                self.pawn_modifiers[side as usize] += self.params.connected_passed_pawn_modifier;
+                self.remark(square, "passed pawn is connected");
+            }
+
+            let up = side.forward();
+            let pos = self.analysis.position();
+            let blockaded = square
+                .checked_towards(up)
+                .map_or(false, |front| pos.pieces(side.toggle()).contains(front));
+            if blockaded {
+                self.pawn_modifiers[side as usize] -= self.params.blockaded_passed_pawn_modifier;
+                self.remark(square, "passed pawn is blockaded");
+            }
+        }
     }
 
     /// Computes the space coefficient for each side. "Space" represents the space that is controlled by a given player
@@ -185,10 +385,7 @@ impl<'a> Evaluator<'a> {
                 Color::Black => SS_RANK_7 | SS_RANK_6 | SS_RANK_5,
             };
             let space_squares = center_files & our_side_of_the_board;
-            let down = match side {
-                Color::White => Direction::South,
-                Color::Black => Direction::North,
-            };
+            let down = side.backward();
             let pos = self.analysis.position();
 
             // Our pawns lead the way into the unknown and claim space; a space is only claimed, though, if it is actually
@@ -199,12 +396,13 @@ impl<'a> Evaluator<'a> {
                     .analysis
                     .attacked_by_kind(side.toggle(), PieceKind::Pawn);
             let mut space_behind_pawns = pos.pawns(side);
-            space_behind_pawns = space_behind_pawns | pos.pawns(side).shift(down);
-            space_behind_pawns = space_behind_pawns | pos.pawns(side).shift(down).shift(down);
+            space_behind_pawns |= pos.pawns(side).shift(down);
+            space_behind_pawns |= pos.pawns(side).shift(down).shift(down);
             let totally_safe_spaces =
                 safe_squares & space_behind_pawns & !self.analysis.attacked_by(side.toggle());
-            self.space[side as usize] =
-                (safe_squares.len() as i16 + totally_safe_spaces.len() as i16) * SPACE_WEIGHT;
+            self.space[side as usize] = (safe_squares.len() as i16
+                + totally_safe_spaces.len() as i16)
+                * self.params.space_weight;
         }
     }
 
@@ -214,14 +412,50 @@ impl<'a> Evaluator<'a> {
     fn threats(&mut self) {
         let pos = self.analysis.position();
         for side in colors() {
-            // Opponent's pieces that are defended are "attacked" by their fellow pieces.
-            let defended_pieces =
-                pos.pieces(side.toggle()) & self.analysis.attacked_by(side.toggle());
-
-            // Weak pieces are attacked by us and not defended adequately.
-            let weak_pieces =
-                pos.pieces(side.toggle()) & !defended_pieces & self.analysis.attacked_by(side);
-            self.threats[side as usize] = weak_pieces.len() as i16 * THREATS_WEIGHT;
+            // Weak pieces are attacked by us and not defended adequately, which is to say that they are attacked by
+            // more of our pieces than they are defended by their fellow pieces.
+            let attackers = self.analysis.attack_count(side);
+            let defenders = self.analysis.attack_count(side.toggle());
+            let weak_pieces = pos
+                .pieces(side.toggle())
+                .into_iter()
+                .filter(|sq| {
+                    let index = sq.as_u8() as usize;
+                    attackers[index] > defenders[index]
+                })
+                .count();
+            self.threats[side as usize] = weak_pieces as i16 * self.params.threats_weight;
+        }
+    }
+
+    /// Mop-up term for evaluation. Once one side is down to a bare king, the only way for the other side to make
+    /// progress is to checkmate it, which requires driving the lone king to the edge of the board and bringing the
+    /// attacking king close enough to help. Without this term the engine has no reason to prefer any one of the many
+    /// won positions over another and can shuffle forever.
+    fn mop_up(&mut self) {
+        let pos = self.analysis.position();
+        for side in colors() {
+            if pos.pieces(side.toggle()).len() != 1 || pos.pieces(side).len() == 1 {
+                continue;
+            }
+
+            let our_king = pos.king(side).unwrap();
+            let their_king = pos.king(side.toggle()).unwrap();
+            let center_distance = center_distance(their_king) as i16;
+            let king_distance = our_king.distance(their_king) as i16;
+            self.mop_up[side as usize] = center_distance
+                * self.params.lone_king_center_distance_weight
+                + (7 - king_distance) * self.params.king_proximity_weight;
+            self.remark(their_king, "lone king is being driven to the edge");
+
+            // Boxing the lone king in is how the mate happens, but it's also how stalemates happen. Search will find
+            // the stalemate if it is within the horizon; this is a nudge away from the ones lurking just past it.
+            let their_mobility = self.analysis.mobility(side.toggle());
+            if their_mobility <= STALEMATE_RISK_MOBILITY && !pos.is_check(side.toggle()) {
+                self.mop_up[side as usize] -= (STALEMATE_RISK_MOBILITY + 1 - their_mobility) as i16
+                    * self.params.stalemate_risk_modifier;
+                self.remark(their_king, "lone king is at risk of being stalemated");
+            }
         }
     }
 
@@ -267,9 +501,39 @@ impl<'a> Evaluator<'a> {
             }
         }
 
+        // Endgames where each side has only a bishop, and the bishops are on opposite colors, are very drawish even
+        // with a pawn or two more for one side: the defending bishop can blockade the pawns on squares the attacking
+        // bishop can never contest. The fewer pawns there are, the closer to a draw it is.
+        if self.opposite_colored_bishops_only() {
+            let pawns = (pos.pawns(Color::White) | pos.pawns(Color::Black)).len() as i16;
+            self.remark(A4, "opposite-colored bishops are drawish");
+            return input_cp * pawns.min(OPPOSITE_BISHOPS_PAWNS) / (2 * OPPOSITE_BISHOPS_PAWNS);
+        }
+
         return input_cp;
     }
 
+    /// Returns whether the only pieces on the board other than kings and pawns are one bishop for each side, with the
+    /// two bishops on squares of opposite colors.
+    fn opposite_colored_bishops_only(&self) -> bool {
+        let pos = self.analysis.position();
+        let white_bishops = pos.bishops(Color::White);
+        let black_bishops = pos.bishops(Color::Black);
+        if white_bishops.len() != 1 || black_bishops.len() != 1 {
+            return false;
+        }
+
+        for side in colors() {
+            if !(pos.knights(side) | pos.rooks(side) | pos.queens(side)).is_empty() {
+                return false;
+            }
+        }
+
+        let white_bishop = white_bishops.into_iter().next().unwrap();
+        let black_bishop = black_bishops.into_iter().next().unwrap();
+        white_bishop.color() != black_bishop.color()
+    }
+
     #[cfg(feature = "trace-eval")]
     fn remark(&mut self, square: Square, remark: &'static str) {
         self.remarks.push((square, remark));
@@ -278,57 +542,28 @@ impl<'a> Evaluator<'a> {
     #[cfg(not(feature = "trace-eval"))]
     fn remark(&mut self, _: Square, _: &'static str) {}
 
+    /// Collects the per-side value of every evaluation term, given the final value of the position.
+    fn breakdown(&self, total: Value) -> EvalBreakdown {
+        EvalBreakdown {
+            material: EvalTerm::new(self.material),
+            mobility: EvalTerm::new(self.mobility),
+            pawn_modifiers: EvalTerm::new(self.pawn_modifiers),
+            space: EvalTerm::new(self.space),
+            threats: EvalTerm::new(self.threats),
+            tempo: EvalTerm::new(self.tempo),
+            positional_considerations: EvalTerm::new(self.positional_considerations),
+            king_safety: EvalTerm::new(self.king_safety),
+            mop_up: EvalTerm::new(self.mop_up),
+            total,
+        }
+    }
+
     #[cfg(feature = "trace-eval")]
     fn dump_evaluation(&self, cp: i16) {
         println!("========================================");
         println!("FEN: {}", self.analysis.position().as_fen());
         println!("========================================");
-        println!("Term           | White | Black | Total |");
-        println!("----------------------------------------");
-        println!(
-            "Material       | {:^5} | {:^5} | {:^5} |",
-            self.material[Color::White as usize],
-            self.material[Color::Black as usize],
-            sum_terms(self.material)
-        );
-        println!(
-            "Mobility       | {:^5} | {:^5} | {:^5} |",
-            self.mobility[Color::White as usize],
-            self.mobility[Color::Black as usize],
-            sum_terms(self.mobility)
-        );
-        println!(
-            "Pawn Modifiers | {:^5} | {:^5} | {:^5} |",
-            self.pawn_modifiers[Color::White as usize],
-            self.pawn_modifiers[Color::Black as usize],
-            sum_terms(self.pawn_modifiers)
-        );
-        println!(
-            "Space          | {:^5} | {:^5} | {:^5} |",
-            self.space[Color::White as usize],
-            self.space[Color::Black as usize],
-            sum_terms(self.space)
-        );
-        println!(
-            "Threats        | {:^5} | {:^5} | {:^5} |",
-            self.threats[Color::White as usize],
-            self.threats[Color::Black as usize],
-            sum_terms(self.threats)
-        );
-        println!(
-            "Tempo          | {:^5} | {:^5} | {:^5} |",
-            self.tempo[Color::White as usize],
-            self.tempo[Color::Black as usize],
-            sum_terms(self.tempo)
-        );
-        println!(
-            "Positional     | {:^5} | {:^5} | {:^5} |",
-            self.positional_considerations[Color::White as usize],
-            self.positional_considerations[Color::Black as usize],
-            sum_terms(self.positional_considerations)
-        );
-        println!("----------------------------------------");
-        println!("Final Score: {}", cp);
+        print!("{}", self.breakdown(Value::new(cp)));
         println!("----------------------------------------");
         println!("Remarks");
         println!("----------------------------------------");
@@ -341,12 +576,207 @@ impl<'a> Evaluator<'a> {
     fn dump_evaluation(&self, _: i16) {}
 }
 
+/// The value of a single evaluation term for each side, in centipawns.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct EvalTerm {
+    pub white: i16,
+    pub black: i16,
+}
+
+impl EvalTerm {
+    fn new(terms: [i16; 2]) -> EvalTerm {
+        EvalTerm {
+            white: terms[Color::White as usize],
+            black: terms[Color::Black as usize],
+        }
+    }
+
+    /// The net value of this term, from White's perspective.
+    pub fn total(self) -> i16 {
+        self.white - self.black
+    }
+}
+
+/// A breakdown of an evaluation into its individual terms.
+///
+/// The total is the final value of the position, which isn't necessarily the sum of the terms: checkmates, stalemates,
+/// and draws by insufficient material override them.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct EvalBreakdown {
+    pub material: EvalTerm,
+    pub mobility: EvalTerm,
+    pub pawn_modifiers: EvalTerm,
+    pub space: EvalTerm,
+    pub threats: EvalTerm,
+    pub tempo: EvalTerm,
+    pub positional_considerations: EvalTerm,
+    pub king_safety: EvalTerm,
+    pub mop_up: EvalTerm,
+    pub total: Value,
+}
+
+impl fmt::Display for EvalBreakdown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Term           | White | Black | Total |")?;
+        writeln!(f, "----------------------------------------")?;
+        for (name, term) in [
+            ("Material", self.material),
+            ("Mobility", self.mobility),
+            ("Pawn Modifiers", self.pawn_modifiers),
+            ("Space", self.space),
+            ("Threats", self.threats),
+            ("Tempo", self.tempo),
+            ("Positional", self.positional_considerations),
+            ("King Safety", self.king_safety),
+            ("Mop-up", self.mop_up),
+        ] {
+            writeln!(
+                f,
+                "{:<14} | {:^5} | {:^5} | {:^5} |",
+                name,
+                term.white,
+                term.black,
+                term.total()
+            )?;
+        }
+        writeln!(f, "----------------------------------------")?;
+        writeln!(f, "Final Score: {}", self.total)
+    }
+}
+
 fn sum_terms(terms: [i16; 2]) -> i16 {
     terms[Color::White as usize] - terms[Color::Black as usize]
 }
 
+/// Returns the half of the board nearest to the opponent of the given side.
+fn their_half(side: Color) -> SquareSet {
+    match side {
+        Color::White => SS_RANK_5 | SS_RANK_6 | SS_RANK_7 | SS_RANK_8,
+        Color::Black => SS_RANK_4 | SS_RANK_3 | SS_RANK_2 | SS_RANK_1,
+    }
+}
+
+/// Returns the material term for one side: the weight of each of its pieces, not counting the king.
+fn material(pos: &Position, side: Color, params: &EvalParams) -> i16 {
+    pos.pawns(side).len() as i16 * params.pawn_weight
+        + pos.knights(side).len() as i16 * params.knight_weight
+        + pos.bishops(side).len() as i16 * params.bishop_weight
+        + pos.rooks(side).len() as i16 * params.rook_weight
+        + pos.queens(side).len() as i16 * params.queen_weight
+}
+
+/// Relative weight of a piece attacking the enemy king zone.
+fn king_attacker_weight(kind: PieceKind) -> i16 {
+    match kind {
+        PieceKind::Knight | PieceKind::Bishop => 2,
+        PieceKind::Rook => 3,
+        PieceKind::Queen => 5,
+        PieceKind::Pawn | PieceKind::King => 0,
+    }
+}
+
+/// Returns the Manhattan distance from a square to the nearest of the four center squares, ranging from zero in the
+/// center to six in a corner.
+fn center_distance(square: Square) -> u8 {
+    let file = square.file().as_u8();
+    let rank = square.rank().as_u8();
+    let file_distance = if file < 4 { 3 - file } else { file - 4 };
+    let rank_distance = if rank < 4 { 3 - rank } else { rank - 4 };
+    file_distance + rank_distance
+}
+
+/// Computes the phase of the game from the non-pawn material left on the board, ranging from `MAX_PHASE` in the
+/// opening down to zero in a pawn endgame.
+fn game_phase(pos: &Position) -> i16 {
+    let mut phase = 0;
+    for side in colors() {
+        phase += (pos.knights(side).len() + pos.bishops(side).len()) as i16;
+        phase += 2 * pos.rooks(side).len() as i16;
+        phase += 4 * pos.queens(side).len() as i16;
+    }
+
+    phase.min(MAX_PHASE)
+}
+
+/// Evaluates a position using the default evaluation weights. Evaluations are remembered in the evaluation cache (see
+/// [`cache`](crate::eval::cache)), so evaluating a position that was evaluated recently is cheap.
 pub fn evaluate(pos: &Position) -> Value {
-    Evaluator::new(pos).evaluate()
+    let key = pos.zobrist_hash();
+    if let Some(value) = cache::probe(key) {
+        return value;
+    }
+
+    let value = evaluate_with_params(pos, &EvalParams::DEFAULT);
+    cache::store(key, value);
+    value
+}
+
+/// Evaluates a position using the default evaluation weights, unless its material balance alone puts it far enough
+/// outside of the window `[lower, upper]` that no other evaluation term could bring it back in (a "lazy"
+/// evaluation). Like `evaluate`, the bounds and the result are relative to White.
+///
+/// A lazy evaluation is a bound rather than an exact value: if the material balance is far above `upper`, the result
+/// is at least `upper` and no more than the full evaluation, and if it's far below `lower`, the result is at most
+/// `lower` and no less than the full evaluation. Either way, it's on the same side of the window as the full
+/// evaluation. Positions within reach of the window are evaluated in full.
+///
+/// Lazy evaluation doesn't notice checkmate or stalemate, so it shouldn't be used for positions where they're likely.
+/// Stalemate is likely when a side has nothing but pawns and its king to move, which is also when the evaluation looks
+/// for lone kings at risk of being stalemated, so those positions are always evaluated in full.
+pub fn evaluate_with_bounds(pos: &Position, lower: Value, upper: Value) -> Value {
+    if let Some(value) = cache::probe(pos.zobrist_hash()) {
+        return value;
+    }
+
+    if colors().any(|side| pos.non_pawn_material(side) == 0) {
+        return evaluate(pos);
+    }
+
+    // The position keeps its material balance up to date as moves are made, and it agrees with the default material
+    // weights, so there's no need to count pieces here.
+    let balance = pos.material_balance();
+    if Value::new(balance - LAZY_EVAL_MARGIN) >= upper {
+        return Value::new(balance - LAZY_EVAL_MARGIN);
+    }
+
+    if Value::new(balance + LAZY_EVAL_MARGIN) <= lower {
+        return Value::new(balance + LAZY_EVAL_MARGIN);
+    }
+
+    evaluate(pos)
+}
+
+/// Evaluates a position using the default evaluation weights, as `evaluate` does, but from the perspective of the side
+/// to move: positive values are good for the side to move, and a side to move that has been checkmated is
+/// `Value::mated_in(0)`. This is the evaluation that the search wants.
+pub fn evaluate_stm(pos: &Position) -> Value {
+    let value = evaluate(pos);
+    match pos.side_to_move() {
+        Color::White => value,
+        Color::Black => -value,
+    }
+}
+
+/// Evaluates a position lazily, as `evaluate_with_bounds` does, but with the bounds and the result relative to the side
+/// to move, as `evaluate_stm`.
+pub fn evaluate_stm_with_bounds(pos: &Position, lower: Value, upper: Value) -> Value {
+    match pos.side_to_move() {
+        Color::White => evaluate_with_bounds(pos, lower, upper),
+        Color::Black => -evaluate_with_bounds(pos, -upper, -lower),
+    }
+}
+
+/// Evaluates a position using the given evaluation weights.
+pub fn evaluate_with_params(pos: &Position, params: &EvalParams) -> Value {
+    Evaluator::new(pos, params).evaluate()
+}
+
+/// Evaluates a position using the default evaluation weights, returning the value of each evaluation term alongside
+/// the final value of the position.
+pub fn evaluate_detailed(pos: &Position) -> EvalBreakdown {
+    let mut evaluator = Evaluator::new(pos, &EvalParams::DEFAULT);
+    let value = evaluator.evaluate();
+    evaluator.breakdown(value)
 }
 
 #[cfg(test)]
@@ -354,6 +784,61 @@ mod tests {
     use super::*;
     use crate::{eval::Value, position::Position};
 
+    #[test]
+    fn repeated_evaluation_hits_the_cache() {
+        let pos = Position::from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        )
+        .unwrap();
+        let first = evaluate(&pos);
+        let hits = cache::hits();
+        let second = evaluate(&pos);
+        assert_eq!(hits + 1, cache::hits());
+        assert_eq!(first, second);
+        assert_eq!(evaluate_with_params(&pos, &EvalParams::DEFAULT), second);
+    }
+
+    #[test]
+    fn lazy_evaluation_within_bounds_is_exact() {
+        let pos = Position::from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        )
+        .unwrap();
+        assert_eq!(
+            evaluate(&pos),
+            evaluate_with_bounds(&pos, Value::new(-50), Value::new(50))
+        );
+    }
+
+    #[test]
+    fn lazy_evaluation_outside_bounds_is_a_bound() {
+        // White is up two queens. There's no need to evaluate the position to know that it's well above a window
+        // around zero and well below a window around ten queens.
+        let pos = Position::from_fen("3qk3/8/8/8/8/8/8/QQQQK3 w - - 0 1").unwrap();
+        let full = evaluate_with_params(&pos, &EvalParams::DEFAULT);
+
+        let above = evaluate_with_bounds(&pos, Value::new(-50), Value::new(50));
+        assert!(Value::new(50) <= above && above <= full);
+
+        let below = evaluate_with_bounds(&pos, Value::new(9000), Value::new(9100));
+        assert!(full <= below && below <= Value::new(9000));
+    }
+
+    #[test]
+    fn lazy_evaluation_notices_stalemate() {
+        // Black is stalemated, though White is up two queens. A window around zero is far below the material balance,
+        // but the position is a draw and so not above it at all.
+        let pos = Position::from_fen("k7/2Q5/1K6/8/8/8/7Q/8 b - - 0 1").unwrap();
+        assert_eq!(
+            Value::DRAW,
+            evaluate_with_bounds(&pos, Value::new(-50), Value::new(50))
+        );
+        assert_eq!(
+            Value::DRAW,
+            evaluate_stm_with_bounds(&pos, Value::new(-50), Value::new(50))
+        );
+    }
+
     #[test]
     fn white_mate_evaluation() {
         let pos = Position::from_fen("8/8/8/8/8/3k4/3q4/3K4 w - - 0 1").unwrap();
@@ -366,6 +851,42 @@ mod tests {
         assert_eq!(Value::mate_in(0), evaluate(&pos));
     }
 
+    #[test]
+    fn safe_mobility_weight() {
+        let pos =
+            Position::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3")
+                .unwrap();
+        let analysis = Analysis::new(&pos);
+        let difference = analysis.safe_mobility(Color::White) as i16
+            - analysis.safe_mobility(Color::Black) as i16;
+        assert_ne!(0, difference);
+
+        let params = EvalParams {
+            safe_mobility_weight: 2,
+            ..EvalParams::DEFAULT
+        };
+        assert_eq!(
+            evaluate_with_params(&pos, &EvalParams::DEFAULT) + 2 * difference,
+            evaluate_with_params(&pos, &params)
+        );
+    }
+
+    #[test]
+    fn side_to_move_evaluation() {
+        let black_mated = Position::from_fen("4k3/4Q3/4K3/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(Value::mated_in(0), evaluate_stm(&black_mated));
+
+        let white_mated = Position::from_fen("8/8/8/8/8/3k4/3q4/3K4 w - - 0 1").unwrap();
+        assert_eq!(Value::mated_in(0), evaluate_stm(&white_mated));
+
+        let pos = Position::from_fen("3qk3/8/8/8/8/8/8/QQ2K3 b - - 0 1").unwrap();
+        assert_eq!(-evaluate(&pos), evaluate_stm(&pos));
+        assert_eq!(
+            -evaluate_with_bounds(&pos, Value::new(-50), Value::new(50)),
+            evaluate_stm_with_bounds(&pos, Value::new(-50), Value::new(50))
+        );
+    }
+
     #[test]
     fn drawn_by_insufficient_material_1() {
         let pos = Position::from_fen("3k4/8/8/8/2N5/8/8/3K4 w - - 0 1").unwrap();
@@ -384,6 +905,211 @@ mod tests {
         assert_eq!(Value::new(0), evaluate(&pos));
     }
 
+    #[test]
+    fn intact_pawn_shield_is_safer() {
+        let intact =
+            Position::from_fen("r2q1rk1/ppp2ppp/8/8/8/8/PPP2PPP/R2Q1RK1 w - - 0 1").unwrap();
+        let advanced =
+            Position::from_fen("r2q1rk1/ppp2ppp/8/8/5PPP/8/PPP5/R2Q1RK1 w - - 0 1").unwrap();
+
+        let mut intact_eval = Evaluator::new(&intact, &EvalParams::DEFAULT);
+        intact_eval.evaluate();
+        let mut advanced_eval = Evaluator::new(&advanced, &EvalParams::DEFAULT);
+        advanced_eval.evaluate();
+        assert!(
+            intact_eval.king_safety[Color::White as usize]
+                > advanced_eval.king_safety[Color::White as usize]
+        );
+    }
+
+    #[test]
+    fn centralized_knight_is_more_mobile() {
+        let central = Position::from_fen("4k3/8/8/8/4N3/8/8/4K3 w - - 0 1").unwrap();
+        let cornered = Position::from_fen("4k3/8/8/8/8/8/8/N3K3 w - - 0 1").unwrap();
+
+        let mut central_eval = Evaluator::new(&central, &EvalParams::DEFAULT);
+        central_eval.evaluate();
+        let mut cornered_eval = Evaluator::new(&cornered, &EvalParams::DEFAULT);
+        cornered_eval.evaluate();
+        assert!(
+            central_eval.mobility[Color::White as usize]
+                > cornered_eval.mobility[Color::White as usize]
+        );
+    }
+
+    #[test]
+    fn connected_passers_beat_isolated_passers() {
+        let connected = Position::from_fen("4k3/8/2PP4/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let isolated = Position::from_fen("4k3/8/P6P/8/8/8/8/4K3 w - - 0 1").unwrap();
+
+        let mut connected_eval = Evaluator::new(&connected, &EvalParams::DEFAULT);
+        connected_eval.evaluate();
+        let mut isolated_eval = Evaluator::new(&isolated, &EvalParams::DEFAULT);
+        isolated_eval.evaluate();
+        assert!(
+            connected_eval.pawn_modifiers[Color::White as usize]
+                > isolated_eval.pawn_modifiers[Color::White as usize]
+        );
+    }
+
+    #[test]
+    fn blockaded_passer_is_worse() {
+        let blockaded = Position::from_fen("4k3/8/8/3n4/3P4/8/8/4K3 w - - 0 1").unwrap();
+        let free = Position::from_fen("4k3/8/8/n7/3P4/8/8/4K3 w - - 0 1").unwrap();
+
+        let mut blockaded_eval = Evaluator::new(&blockaded, &EvalParams::DEFAULT);
+        blockaded_eval.evaluate();
+        let mut free_eval = Evaluator::new(&free, &EvalParams::DEFAULT);
+        free_eval.evaluate();
+        assert!(
+            blockaded_eval.pawn_modifiers[Color::White as usize]
+                < free_eval.pawn_modifiers[Color::White as usize]
+        );
+    }
+
+    #[test]
+    fn rook_behind_passer() {
+        let behind = Position::from_fen("4k3/8/8/3P4/8/8/8/3RK3 w - - 0 1").unwrap();
+        let beside = Position::from_fen("4k3/8/8/3P4/8/8/8/2R1K3 w - - 0 1").unwrap();
+
+        let mut behind_eval = Evaluator::new(&behind, &EvalParams::DEFAULT);
+        behind_eval.evaluate();
+        let mut beside_eval = Evaluator::new(&beside, &EvalParams::DEFAULT);
+        beside_eval.evaluate();
+        assert!(
+            behind_eval.positional_considerations[Color::White as usize]
+                > beside_eval.positional_considerations[Color::White as usize]
+        );
+    }
+
+    #[test]
+    fn knight_on_outpost() {
+        // The knight on e5 is defended by the pawn on d4, and there are no black pawns on the d or f files to chase it
+        // away. On e4, it's defended but black's f-pawn can come to f5 and attack it.
+        let outpost = Position::from_fen("4k3/p5p1/8/4N3/3P4/8/8/4K3 w - - 0 1").unwrap();
+        let exposed = Position::from_fen("4k3/p4pp1/8/8/4N3/3P4/8/4K3 w - - 0 1").unwrap();
+
+        let mut outpost_eval = Evaluator::new(&outpost, &EvalParams::DEFAULT);
+        outpost_eval.evaluate();
+        let mut exposed_eval = Evaluator::new(&exposed, &EvalParams::DEFAULT);
+        exposed_eval.evaluate();
+        assert!(
+            outpost_eval.positional_considerations[Color::White as usize]
+                > exposed_eval.positional_considerations[Color::White as usize]
+        );
+    }
+
+    #[test]
+    fn trapped_bishop() {
+        // The bishop on h7 can only go back to g8; g6 is defended by the pawn on f7.
+        let trapped = Position::from_fen("4k3/5p1B/6p1/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let free = Position::from_fen("4k3/5p2/6p1/8/8/3B4/8/4K3 w - - 0 1").unwrap();
+
+        let mut trapped_eval = Evaluator::new(&trapped, &EvalParams::DEFAULT);
+        trapped_eval.evaluate();
+        let mut free_eval = Evaluator::new(&free, &EvalParams::DEFAULT);
+        free_eval.evaluate();
+        assert!(
+            trapped_eval.positional_considerations[Color::White as usize]
+                < free_eval.positional_considerations[Color::White as usize]
+        );
+    }
+
+    #[test]
+    fn opposite_colored_bishops_are_drawish() {
+        // White is a pawn up in both positions, but with the bishops on opposite colors, Black's bishop can hold e4
+        // forever.
+        let opposite = Position::from_fen("8/4k3/8/4b3/8/4P3/2B5/4K3 w - - 0 1").unwrap();
+        let same = Position::from_fen("8/4k3/8/3b4/8/4P3/2B5/4K3 w - - 0 1").unwrap();
+        let opposite_value = evaluate_with_params(&opposite, &EvalParams::DEFAULT);
+        let same_value = evaluate_with_params(&same, &EvalParams::DEFAULT);
+        assert!(same_value > Value::new(50), "{}", same_value);
+        assert!(
+            Value::DRAW <= opposite_value && opposite_value < Value::new(20),
+            "{}",
+            opposite_value
+        );
+    }
+
+    #[test]
+    fn king_safety_fades_in_endgame() {
+        let pos = Position::from_fen("6k1/8/8/8/8/8/8/6K1 w - - 0 1").unwrap();
+        let mut evaluator = Evaluator::new(&pos, &EvalParams::DEFAULT);
+        evaluator.evaluate();
+        assert_eq!(0, evaluator.king_safety[Color::White as usize]);
+    }
+
+    #[test]
+    fn doubled_pawn_weight_from_params_file() {
+        let pos = Position::from_fen("4k3/8/8/8/8/4P3/4P3/4K3 w - - 0 1").unwrap();
+        let doubled = Analysis::new(&pos).doubled_pawns(Color::White).len() as i16;
+        assert!(doubled > 0);
+
+        let path = std::env::temp_dir().join(format!("a4-params-test-{}.json", std::process::id()));
+        std::fs::write(&path, r#"{ "doubled_pawn_modifier": 30 }"#).unwrap();
+        let params = EvalParams::from_json(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let default_value = evaluate(&pos);
+        let tuned_value = evaluate_with_params(&pos, &params);
+        assert_eq!(default_value - 20 * doubled, tuned_value);
+    }
+
+    #[test]
+    fn lone_king_is_driven_to_the_corner() {
+        // The same KQvK position, before and after White's king closes in on a black king that has been pushed into
+        // the corner.
+        let centralized = Position::from_fen("8/8/8/4k3/8/8/8/K5Q1 b - - 0 1").unwrap();
+        let cornered = Position::from_fen("7k/8/5K2/8/8/8/8/6Q1 b - - 0 1").unwrap();
+        assert!(evaluate(&cornered) > evaluate(&centralized));
+
+        let mut centralized_eval = Evaluator::new(&centralized, &EvalParams::DEFAULT);
+        centralized_eval.evaluate();
+        let mut cornered_eval = Evaluator::new(&cornered, &EvalParams::DEFAULT);
+        cornered_eval.evaluate();
+        assert!(
+            cornered_eval.mop_up[Color::White as usize]
+                > centralized_eval.mop_up[Color::White as usize]
+        );
+        assert_eq!(0, cornered_eval.mop_up[Color::Black as usize]);
+    }
+
+    #[test]
+    fn stalemate_risk() {
+        // White to move in "k7/8/1K6/3Q4/8/8/8/8 w": Qc7 stalemates, while Qd7 leaves Black a single move.
+        let stalemate = Position::from_fen("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1").unwrap();
+        let progress = Position::from_fen("k7/3Q4/1K6/8/8/8/8/8 b - - 0 1").unwrap();
+        assert!(evaluate(&stalemate) < evaluate(&progress));
+
+        // Black's king can only go to b8, so the progressing move still carries a penalty for hemming it in so tightly.
+        let params = &EvalParams::DEFAULT;
+        let mut progress_eval = Evaluator::new(&progress, params);
+        progress_eval.evaluate();
+        let unpenalized = center_distance(A8) as i16 * params.lone_king_center_distance_weight
+            + (7 - B6.distance(A8) as i16) * params.king_proximity_weight;
+        assert_eq!(
+            unpenalized - 2 * params.stalemate_risk_modifier,
+            progress_eval.mop_up[Color::White as usize]
+        );
+    }
+
+    #[test]
+    fn breakdown_matches_evaluation() {
+        let pos = Position::from_fen("r2q1rk1/ppp2ppp/8/8/5PPP/8/PPP5/R2Q1RK1 w - - 0 1").unwrap();
+        let breakdown = evaluate_detailed(&pos);
+        assert_eq!(evaluate(&pos), breakdown.total);
+        let sum = breakdown.material.total()
+            + breakdown.mobility.total()
+            + breakdown.pawn_modifiers.total()
+            + breakdown.space.total()
+            + breakdown.threats.total()
+            + breakdown.tempo.total()
+            + breakdown.positional_considerations.total()
+            + breakdown.king_safety.total()
+            + breakdown.mop_up.total();
+        assert_eq!(Value::new(sum), breakdown.total);
+    }
+
     #[test]
     fn drawn_by_insufficient_material_4() {
         let pos = Position::from_fen("3k4/8/8/8/8/8/8/3K4 w - - 0 1").unwrap();
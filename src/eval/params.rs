@@ -0,0 +1,158 @@
+// Copyright 2022 Sean Gillespie.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::{fs, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Possible errors that can arise when loading evaluation parameters.
+#[derive(Debug, Error)]
+pub enum EvalParamsError {
+    #[error("failed to read eval params: {0}")]
+    Io(#[from] io::Error),
+    #[error("failed to parse eval params: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// The weights used by the evaluator, all in centipawns. These are runtime values so that they can be tuned without
+/// rebuilding the engine; the defaults are the engine's hand-tuned weights.
+///
+/// Parameter files are JSON objects keyed by field name. Any weight that a file doesn't mention keeps its default.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EvalParams {
+    pub queen_weight: i16,
+    pub rook_weight: i16,
+    pub bishop_weight: i16,
+    pub knight_weight: i16,
+    pub pawn_weight: i16,
+    pub space_weight: i16,
+    pub threats_weight: i16,
+    pub tempo_weight: i16,
+
+    // Mobility weights, per safe square that a piece can move to. Mobility is measured relative to a baseline number of
+    // squares for each kind of piece, so that a piece with typical mobility doesn't contribute anything.
+    pub knight_mobility_weight: i16,
+    pub bishop_mobility_weight: i16,
+    pub rook_mobility_weight: i16,
+    pub queen_mobility_weight: i16,
+    pub knight_mobility_baseline: i16,
+    pub bishop_mobility_baseline: i16,
+    pub rook_mobility_baseline: i16,
+    pub queen_mobility_baseline: i16,
+    /// Weight per legal move that doesn't land on a square attacked by an enemy pawn, counted for the whole side rather
+    /// than per piece. This is an alternative to the per-piece weights, which count attacked squares rather than legal
+    /// moves, and is off by default.
+    pub safe_mobility_weight: i16,
+
+    // Pawn piece modifiers
+    pub isolated_pawn_modifier: i16,
+    pub backward_pawn_modifier: i16,
+    pub doubled_pawn_modifier: i16,
+    pub connected_passed_pawn_modifier: i16,
+    pub blockaded_passed_pawn_modifier: i16,
+
+    // Minor piece modifiers
+    pub knight_outpost_modifier: i16,
+    pub bishop_outpost_modifier: i16,
+    pub trapped_piece_modifier: i16,
+    /// Minor pieces in the opponent's half with fewer safe squares than this are considered trapped.
+    pub trapped_piece_mobility_threshold: i16,
+
+    // Rook modifiers
+    pub rook_behind_passed_pawn_modifier: i16,
+
+    // Queen modifiers
+    pub queen_early_development_modifier: i16,
+
+    // King safety modifiers
+    pub king_missing_shelter_pawn_modifier: i16,
+    pub king_open_file_modifier: i16,
+    pub king_half_open_file_modifier: i16,
+    pub king_zone_attack_modifier: i16,
+
+    // Mop-up weights, used when one side has nothing left but its king
+    pub lone_king_center_distance_weight: i16,
+    pub king_proximity_weight: i16,
+    pub stalemate_risk_modifier: i16,
+}
+
+impl EvalParams {
+    /// The default evaluation weights.
+    pub const DEFAULT: EvalParams = EvalParams {
+        queen_weight: 900,
+        rook_weight: 500,
+        bishop_weight: 300,
+        knight_weight: 300,
+        pawn_weight: 100,
+        space_weight: 13,
+        threats_weight: 50,
+        tempo_weight: 15,
+        knight_mobility_weight: 4,
+        bishop_mobility_weight: 5,
+        rook_mobility_weight: 3,
+        queen_mobility_weight: 1,
+        knight_mobility_baseline: 4,
+        bishop_mobility_baseline: 6,
+        rook_mobility_baseline: 7,
+        queen_mobility_baseline: 13,
+        safe_mobility_weight: 0,
+        isolated_pawn_modifier: 17,
+        backward_pawn_modifier: 10,
+        doubled_pawn_modifier: 10,
+        connected_passed_pawn_modifier: 15,
+        blockaded_passed_pawn_modifier: 20,
+        knight_outpost_modifier: 25,
+        bishop_outpost_modifier: 15,
+        trapped_piece_modifier: 50,
+        trapped_piece_mobility_threshold: 2,
+        rook_behind_passed_pawn_modifier: 20,
+        queen_early_development_modifier: 40,
+        king_missing_shelter_pawn_modifier: 20,
+        king_open_file_modifier: 25,
+        king_half_open_file_modifier: 12,
+        king_zone_attack_modifier: 4,
+        lone_king_center_distance_weight: 10,
+        king_proximity_weight: 4,
+        stalemate_risk_modifier: 15,
+    };
+
+    /// Reads evaluation parameters from the JSON file at the given path.
+    pub fn from_json(path: impl AsRef<Path>) -> Result<EvalParams, EvalParamsError> {
+        let contents = fs::read_to_string(path)?;
+        EvalParams::from_json_str(&contents)
+    }
+
+    /// Reads evaluation parameters from an in-memory JSON string.
+    pub fn from_json_str(json: &str) -> Result<EvalParams, EvalParamsError> {
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        EvalParams::DEFAULT
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EvalParams;
+
+    #[test]
+    fn missing_weights_keep_defaults() {
+        let params = EvalParams::from_json_str(r#"{ "pawn_weight": 90 }"#).unwrap();
+        assert_eq!(90, params.pawn_weight);
+        assert_eq!(EvalParams::DEFAULT.queen_weight, params.queen_weight);
+    }
+
+    #[test]
+    fn unparseable_params() {
+        assert!(EvalParams::from_json_str("{ \"pawn_weight\": \"lots\" }").is_err());
+    }
+}
@@ -0,0 +1,87 @@
+// Copyright 2022 Sean Gillespie.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Support for tuning evaluation weights with the Texel method: given a set of positions labeled with the result of
+//! the game they came from, find the weights that best predict those results.
+
+use crate::{
+    eval::{evaluate_with_params, EvalParams, UnpackedValue},
+    position::Position,
+};
+
+/// Computes the mean squared error between the predicted and the actual results of a set of positions, given a set of
+/// evaluation weights. Results are from White's perspective: 1.0 for a White win, 0.5 for a draw, and 0.0 for a Black
+/// win.
+///
+/// The static evaluation of each position is mapped to a predicted result with the logistic function
+/// `1 / (1 + 10^(-k * eval / 400))`, where `k` is a scaling constant that should be fit to the data set before tuning.
+pub fn evaluation_error(positions: &[(Position, f64)], params: &EvalParams, k: f64) -> f64 {
+    if positions.is_empty() {
+        return 0.0;
+    }
+
+    let total_error: f64 = positions
+        .iter()
+        .map(|(pos, result)| {
+            let predicted = match evaluate_with_params(pos, params).unpack() {
+                UnpackedValue::MateIn(_) => 1.0,
+                UnpackedValue::MatedIn(_) => 0.0,
+                UnpackedValue::Value(cp) => sigmoid(cp as f64, k),
+            };
+
+            (result - predicted).powi(2)
+        })
+        .sum();
+
+    total_error / positions.len() as f64
+}
+
+fn sigmoid(centipawns: f64, k: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * centipawns / 400.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::evaluation_error;
+    use crate::{eval::EvalParams, position::Position};
+
+    fn labeled(fen: &str, result: f64) -> (Position, f64) {
+        (Position::from_fen(fen).unwrap(), result)
+    }
+
+    #[test]
+    fn sane_material_weights_reduce_error() {
+        let positions = [
+            // White is up a rook and won.
+            labeled("4k3/pppp4/8/8/8/8/PPPP4/R3K3 w - - 0 1", 1.0),
+            // Black is up a queen and won.
+            labeled("3qk3/pppp4/8/8/8/8/PPPP4/4K3 w - - 0 1", 0.0),
+            // Material is level and the game was drawn.
+            labeled("4k3/pppp4/8/8/8/8/PPPP4/4K3 w - - 0 1", 0.5),
+        ];
+
+        let worthless_material = EvalParams {
+            queen_weight: 0,
+            rook_weight: 0,
+            bishop_weight: 0,
+            knight_weight: 0,
+            pawn_weight: 0,
+            ..EvalParams::default()
+        };
+
+        let k = 1.0;
+        let sane_error = evaluation_error(&positions, &EvalParams::default(), k);
+        let worthless_error = evaluation_error(&positions, &worthless_material, k);
+        assert!(sane_error < worthless_error);
+    }
+
+    #[test]
+    fn no_positions_no_error() {
+        assert_eq!(0.0, evaluation_error(&[], &EvalParams::default(), 1.0));
+    }
+}
@@ -11,6 +11,10 @@ const VALUE_MATED: i16 = i16::MIN / 2 + 1;
 const VALUE_MATE: i16 = i16::MAX / 2;
 const MATE_DISTANCE_MAX: i16 = 50;
 
+/// Centipawn scores at least this large are beyond anything the evaluator produces from material and position alone,
+/// and only come from knowing how the game ends (e.g. from the endgame tablebases).
+const DECISIVE_THRESHOLD: i16 = 10000;
+
 /// A Value is the static value given to a position by evaluation of the game board. It is a single number, in
 /// centipawns, that represents the engine's assessment of a particular position. The number is positive if the engine
 /// is winning and negative if the engine is losing.
@@ -38,6 +42,16 @@ pub enum UnpackedValue {
 }
 
 impl Value {
+    /// The value of a drawn position.
+    pub const DRAW: Value = Value(0);
+
+    /// The value of delivering checkmate on the board. This is `Value::mate_in(0)`.
+    pub const MATE: Value = Value(VALUE_MATE + MATE_DISTANCE_MAX);
+
+    /// A value at least as large as any other value. Nothing is better than checkmate, so this is the same as
+    /// `Value::MATE`; `-Value::INFINITE..Value::INFINITE` is a full search window.
+    pub const INFINITE: Value = Value::MATE;
+
     pub fn mate_in(ply: i16) -> Value {
         debug_assert!(ply < MATE_DISTANCE_MAX);
         Value(VALUE_MATE + MATE_DISTANCE_MAX - ply)
@@ -52,6 +66,11 @@ impl Value {
         Value(evaluation)
     }
 
+    /// Returns the representation of this value, which `Value::new` turns back into the same value.
+    pub(crate) fn to_bits(self) -> i16 {
+        self.0
+    }
+
     pub fn step(self) -> Value {
         match self.unpack() {
             UnpackedValue::MateIn(value) => Value::mate_in((value + 1) as i16),
@@ -88,17 +107,67 @@ impl Value {
         }
     }
 
-    fn add(self, other: Value) -> Value {
-        debug_assert!(self.0 > VALUE_MATED && self.0 < VALUE_MATE);
-        let mut next = self.0 + other.0;
-        if next <= VALUE_MATED || next >= VALUE_MATE {
-            if next <= VALUE_MATED {
-                next = VALUE_MATED + 1;
-            } else {
-                next = VALUE_MATE - 1;
+    /// Parses a value in the format produced by `as_uci`: `cp X` for a centipawn score, or `mate N` or `mate -N` for a
+    /// forced mate for or against the side to move. Returns `None` if the string isn't a score, or if the score is out
+    /// of range.
+    pub fn from_uci(s: &str) -> Option<Value> {
+        let mut parts = s.split_whitespace();
+        let kind = parts.next()?;
+        let amount = parts.next()?;
+        if parts.next().is_some() {
+            return None;
+        }
+
+        match kind {
+            "cp" => {
+                let value = amount.parse::<i16>().ok()?;
+                (VALUE_MATED..=VALUE_MATE)
+                    .contains(&value)
+                    .then_some(Value(value))
+            }
+            "mate" => {
+                let (mated, distance) = match amount.strip_prefix('-') {
+                    Some(distance) => (true, distance),
+                    None => (false, amount),
+                };
+                let distance = distance.parse::<i16>().ok()?;
+                if !(0..MATE_DISTANCE_MAX).contains(&distance) {
+                    return None;
+                }
+
+                Some(if mated {
+                    Value::mated_in(distance)
+                } else {
+                    Value::mate_in(distance)
+                })
             }
+            _ => None,
+        }
+    }
+
+    /// Whether or not this value represents a forced mate, for either side.
+    pub fn is_mate(self) -> bool {
+        !matches!(self.unpack(), UnpackedValue::Value(_))
+    }
+
+    /// Whether or not this value decides the game: a forced mate for either side, or a centipawn score so large that it
+    /// can only come from knowing the outcome, such as a tablebase win.
+    pub fn is_decisive(self) -> bool {
+        self.is_mate() || self.0.abs() >= DECISIVE_THRESHOLD
+    }
+
+    /// Adds two values. Centipawn scores saturate at the boundary of the mate scores, while mate scores absorb
+    /// centipawn adjustments; a forced mate is still a forced mate after adding a margin to it.
+    fn add(self, other: Value) -> Value {
+        if self.is_mate() {
+            return self;
+        }
+
+        if other.is_mate() {
+            return other;
         }
-        Value(next)
+
+        Value((self.0 + other.0).clamp(VALUE_MATED + 1, VALUE_MATE - 1))
     }
 }
 
@@ -185,6 +254,72 @@ mod tests {
         assert_eq!(v.0, VALUE_MATED + 1);
     }
 
+    #[test]
+    fn mate_scores_absorb_margins() {
+        assert_eq!(Value::mate_in(0) + 100, Value::mate_in(0));
+        assert_eq!(Value::mate_in(3) - 100, Value::mate_in(3));
+        assert_eq!(Value::mated_in(0) - 100, Value::mated_in(0));
+        assert_eq!(Value::new(100) + Value::mated_in(2), Value::mated_in(2));
+        assert_eq!(Value::new(100) - Value::mate_in(2), Value::mated_in(2));
+    }
+
+    #[test]
+    fn named_values() {
+        assert_eq!(Value::DRAW, Value::new(0));
+        assert_eq!(Value::MATE, Value::mate_in(0));
+        assert_eq!(-Value::INFINITE, Value::mated_in(0));
+        assert!(Value::INFINITE >= Value::mate_in(1));
+    }
+
+    #[test]
+    fn decisive_values() {
+        assert!(Value::MATE.is_decisive());
+        assert!(Value::mated_in(5).is_decisive());
+        assert!(Value::new(12000).is_decisive());
+        assert!(Value::new(-12000).is_decisive());
+        assert!(!Value::new(50).is_decisive());
+        assert!(!Value::DRAW.is_decisive());
+    }
+
+    #[test]
+    fn double_negation() {
+        for v in [
+            Value::new(0),
+            Value::new(-250),
+            Value::new(VALUE_MATE - 1),
+            Value::mate_in(0),
+            Value::mated_in(7),
+        ] {
+            assert_eq!(-(-v), v);
+        }
+    }
+
+    #[test]
+    fn uci_round_trip() {
+        for v in [
+            Value::new(0),
+            Value::new(-250),
+            Value::new(37),
+            Value::mate_in(0),
+            Value::mate_in(3),
+            Value::mated_in(0),
+            Value::mated_in(7),
+        ] {
+            assert_eq!(Some(v), Value::from_uci(&v.as_uci()));
+        }
+    }
+
+    #[test]
+    fn from_uci_rejects_garbage() {
+        assert_eq!(None, Value::from_uci(""));
+        assert_eq!(None, Value::from_uci("cp"));
+        assert_eq!(None, Value::from_uci("cp abc"));
+        assert_eq!(None, Value::from_uci("cp 20000"));
+        assert_eq!(None, Value::from_uci("mate 200"));
+        assert_eq!(None, Value::from_uci("lowerbound 30"));
+        assert_eq!(None, Value::from_uci("cp 30 extra"));
+    }
+
     #[test]
     fn mated_in_4_is_better_than_mated_in_3() {
         assert!(Value::mated_in(4) > Value::mated_in(3))
@@ -0,0 +1,168 @@
+// Copyright 2022 Sean Gillespie.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Navigable game state, for GUIs and analysis tools that step back and forth through a game.
+//!
+//! A [`Game`] remembers every position that was reached, so undoing a move is just a matter of stepping back to the
+//! previous one. Moves that have been undone are kept on a redo stack until a new move is played that doesn't follow
+//! them.
+
+use crate::{
+    core::Move,
+    position::{MoveError, Position},
+};
+
+/// A game, starting from some position, with undo and redo.
+#[derive(Clone, Debug)]
+pub struct Game {
+    /// Every position reached in the game, starting with the initial position. The last entry is the current position.
+    positions: Vec<Position>,
+    /// Moves that have been undone, with the most recently undone move last.
+    redo: Vec<Move>,
+}
+
+impl Game {
+    /// Creates a new game starting at the given position.
+    pub fn new(pos: Position) -> Game {
+        Game {
+            positions: vec![pos],
+            redo: vec![],
+        }
+    }
+
+    /// Creates a new game starting at the starting position.
+    pub fn from_start_position() -> Game {
+        Game::new(Position::from_start_position())
+    }
+
+    /// The current position of the game.
+    pub fn current(&self) -> &Position {
+        self.positions.last().expect("game has no positions")
+    }
+
+    /// The moves that have been played to reach the current position.
+    pub fn moves(&self) -> &[Move] {
+        let start = self.positions[0].history().len();
+        &self.current().history()[start..]
+    }
+
+    /// Plays a move from the current position, if it is legal. If the move is the next one on the redo stack, the rest
+    /// of the redo stack is kept; otherwise, it is cleared.
+    pub fn push(&mut self, mov: Move) -> Result<(), MoveError> {
+        let mut next = self.current().clone();
+        next.try_make_move(mov)?;
+        self.positions.push(next);
+        if self.redo.last() == Some(&mov) {
+            self.redo.pop();
+        } else {
+            self.redo.clear();
+        }
+        Ok(())
+    }
+
+    /// Takes back the most recent move, returning it, or `None` if there are no moves to take back.
+    pub fn undo(&mut self) -> Option<Move> {
+        if self.positions.len() == 1 {
+            return None;
+        }
+
+        let pos = self.positions.pop().expect("game has no positions");
+        let mov = *pos.history().last().expect("position has no history");
+        self.redo.push(mov);
+        Some(mov)
+    }
+
+    /// Replays the most recently undone move, returning it, or `None` if there are no moves to replay.
+    pub fn redo(&mut self) -> Option<Move> {
+        let mov = self.redo.pop()?;
+        let mut next = self.current().clone();
+        next.make_move(mov);
+        self.positions.push(next);
+        Some(mov)
+    }
+
+    /// Returns whether there is a move to take back.
+    pub fn can_undo(&self) -> bool {
+        self.positions.len() > 1
+    }
+
+    /// Returns whether there is a move to replay.
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Game;
+    use crate::{
+        core::Move,
+        position::{MoveError, Position},
+    };
+
+    fn uci(game: &Game, s: &str) -> Move {
+        Move::from_uci(game.current(), s).unwrap()
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let mut game = Game::from_start_position();
+        assert_eq!(None, game.undo());
+        assert_eq!(None, game.redo());
+
+        let e4 = uci(&game, "e2e4");
+        game.push(e4).unwrap();
+        let e5 = uci(&game, "e7e5");
+        game.push(e5).unwrap();
+        let after_e5 = game.current().clone();
+        assert_eq!(&[e4, e5], game.moves());
+
+        assert_eq!(Some(e5), game.undo());
+        assert_eq!(Some(e4), game.undo());
+        assert_eq!(None, game.undo());
+        assert!(game.moves().is_empty());
+        assert!(game
+            .current()
+            .is_same_position(&Position::from_start_position()));
+
+        assert_eq!(Some(e4), game.redo());
+        assert_eq!(Some(e5), game.redo());
+        assert_eq!(None, game.redo());
+        assert!(game.current().is_same_position(&after_e5));
+    }
+
+    #[test]
+    fn new_move_clears_redo() {
+        let mut game = Game::from_start_position();
+        let e4 = uci(&game, "e2e4");
+        game.push(e4).unwrap();
+        game.push(uci(&game, "e7e5")).unwrap();
+        game.undo();
+        game.undo();
+        assert!(game.can_redo());
+
+        // Replaying the undone move keeps the rest of the redo stack.
+        game.push(e4).unwrap();
+        assert!(game.can_redo());
+
+        game.push(uci(&game, "c7c5")).unwrap();
+        assert!(!game.can_redo());
+        assert_eq!(None, game.redo());
+    }
+
+    #[test]
+    fn illegal_move_is_rejected() {
+        let mut game = Game::from_start_position();
+        game.push(uci(&game, "e2e4")).unwrap();
+        game.undo();
+        let e5 = Move::from_uci(&Position::from_start_position(), "e7e5").unwrap();
+        assert!(matches!(game.push(e5), Err(MoveError::NotSideToMove(_))));
+        assert!(game.moves().is_empty());
+        assert!(game.can_redo());
+    }
+}
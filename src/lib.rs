@@ -38,6 +38,16 @@ macro_rules! uci_output {
     };
 }
 
+/// Helper macro for writing `info string` diagnostics to standard out. Diagnostics are only written if the GUI has
+/// turned on debug mode with the UCI `debug on` command.
+macro_rules! info_string {
+    ($($arg:tt)*) => {
+        if crate::log::uci_debug() {
+            crate::log::info_string(format_args!($($arg)*));
+        }
+    };
+}
+
 macro_rules! log {
     ($level:expr, $format:literal) => {
         crate::log::log($level, format_args!($format));
@@ -88,14 +98,42 @@ macro_rules! always {
     };
 }
 
+pub mod bench;
+pub mod book;
 pub mod core;
 pub mod debug;
 pub mod eval;
+pub mod history;
 mod log;
 pub mod movegen;
 pub mod position;
 pub mod search;
+pub mod selfplay;
 mod table;
+pub mod tablebase;
 mod threads;
 pub mod uci;
-mod zobrist;
+pub mod zobrist;
+
+#[cfg(test)]
+mod tests {
+    use crate::{book, core, eval, position};
+
+    /// Library errors should be usable with `?` in any error-handling scheme, including ones that send errors across
+    /// threads, like `anyhow`.
+    fn assert_error<E: std::error::Error + Send + Sync + 'static>() {}
+
+    #[test]
+    fn public_errors_are_thread_safe() {
+        assert_error::<book::BookError>();
+        assert_error::<core::SquareParseError>();
+        assert_error::<core::RankParseError>();
+        assert_error::<core::FileParseError>();
+        assert_error::<core::PieceParseError>();
+        assert_error::<eval::EvalParamsError>();
+        assert_error::<position::FenParseError>();
+        assert_error::<position::MoveError>();
+        assert_error::<position::PieceError>();
+        assert_error::<position::PositionError>();
+    }
+}
@@ -21,7 +21,7 @@ use chrono::Local;
 use crate::threads;
 
 #[repr(u8)]
-#[derive(PartialEq, PartialOrd, Eq, Debug)]
+#[derive(Copy, Clone, PartialEq, PartialOrd, Eq, Debug)]
 pub enum LogLevel {
     Debug = 3,
     Info = 2,
@@ -58,13 +58,47 @@ pub fn disable() {
     LOGGER.enabled.store(false, Ordering::Release);
 }
 
+/// Whether or not the GUI has put us in debug mode, via the UCI `debug` command.
+static UCI_DEBUG: AtomicBool = AtomicBool::new(false);
+
+pub fn set_uci_debug(enabled: bool) {
+    UCI_DEBUG.store(enabled, Ordering::Release);
+}
+
+pub fn uci_debug() -> bool {
+    UCI_DEBUG.load(Ordering::Acquire)
+}
+
+#[cfg(test)]
+thread_local! {
+    static CAPTURED_INFO_STRINGS: std::cell::RefCell<Vec<String>> = std::cell::RefCell::new(vec![]);
+}
+
+/// Writes an `info string` diagnostic to the GUI. Use the `info_string!` macro instead of calling this directly.
+pub fn info_string(args: Arguments<'_>) {
+    #[cfg(test)]
+    CAPTURED_INFO_STRINGS.with(|captured| captured.borrow_mut().push(args.to_string()));
+    uci_output!("info string {}", args);
+}
+
+/// Returns every `info string` diagnostic written by the current thread since the last call.
+#[cfg(test)]
+pub fn take_info_strings() -> Vec<String> {
+    CAPTURED_INFO_STRINGS.with(|captured| captured.take())
+}
+
+/// Sets the most verbose level of message that will be logged. Messages at `Always` are logged at every level.
 pub fn set_level(level: LogLevel) {
     LOGGER.level.store(level as u8, Ordering::Release);
 }
 
+/// Returns whether or not messages at the given level will be logged.
+pub fn is_enabled(level: LogLevel) -> bool {
+    LOGGER.enabled.load(Ordering::Acquire) && LOGGER.level.load(Ordering::Acquire) >= level as u8
+}
+
 pub fn log(level: LogLevel, args: Arguments<'_>) {
-    if LOGGER.enabled.load(Ordering::Acquire) && LOGGER.level.load(Ordering::Acquire) >= level as u8
-    {
+    if is_enabled(level) {
         let id = threads::get_worker_id()
             .map(|t| t.to_string())
             .unwrap_or_else(|| "?".to_owned());
@@ -74,3 +108,30 @@ pub fn log(level: LogLevel, args: Arguments<'_>) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::LogLevel;
+
+    #[test]
+    fn level_filters_messages() {
+        let path = std::env::temp_dir().join(format!("a4-log-test-{}.log", std::process::id()));
+        super::set_file(path.to_str().unwrap()).unwrap();
+        super::set_level(LogLevel::Warn);
+        super::enable();
+        debug!("suppressed debug message");
+        info!("suppressed info message");
+        warn!("logged warn message");
+        always!("logged always message");
+        super::disable();
+        super::set_level(LogLevel::Always);
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        assert!(!contents.contains("suppressed"));
+        assert!(contents.contains("logged warn message"));
+        assert!(contents.contains("logged always message"));
+    }
+}
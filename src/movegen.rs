@@ -5,33 +5,45 @@
 // <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
+use smallvec::{Array, SmallVec};
+
 use crate::{core::*, position::Position};
 
-pub fn generate_pawn_moves(us: Color, pos: &Position, moves: &mut Vec<Move>) {
+/// Every piece that a pawn can promote to.
+const ALL_PROMOTIONS: [PieceKind; 4] = [
+    PieceKind::Bishop,
+    PieceKind::Knight,
+    PieceKind::Rook,
+    PieceKind::Queen,
+];
+
+/// The pieces that a pawn can usefully promote to. A queen can do anything that a rook or bishop can, so promoting to
+/// one of those only matters in the rare positions where promoting to a queen would stalemate the opponent.
+const USEFUL_PROMOTIONS: [PieceKind; 2] = [PieceKind::Knight, PieceKind::Queen];
+
+pub fn generate_pawn_moves<B: MoveSink>(us: Color, pos: &Position, moves: &mut B) {
+    generate_pawn_moves_with(us, pos, moves, &ALL_PROMOTIONS);
+}
+
+fn generate_pawn_moves_with<B: MoveSink>(
+    us: Color,
+    pos: &Position,
+    moves: &mut B,
+    promotions: &[PieceKind],
+) {
     let them = us.toggle();
     let their_pieces = pos.pieces(them);
     let our_pieces = pos.pieces(us);
     let all_pieces = their_pieces.or(our_pieces);
     let empty_squares = !all_pieces;
-    let (up, down, up_left, up_right, promo_rank, start_rank) = if us == Color::White {
-        (
-            Direction::North,
-            Direction::South,
-            Direction::NorthWest,
-            Direction::NorthEast,
-            SS_RANK_8,
-            SS_RANK_2,
-        )
-    } else {
-        (
-            Direction::South,
-            Direction::North,
-            Direction::SouthWest,
-            Direction::SouthEast,
-            SS_RANK_1,
-            SS_RANK_7,
-        )
+    let up = us.forward();
+    let down = us.backward();
+    let (up_left, up_right) = match us {
+        Color::White => (Direction::NorthWest, Direction::NorthEast),
+        Color::Black => (Direction::SouthWest, Direction::SouthEast),
     };
+    let promo_rank = SquareSet::all().rank(us.promotion_rank());
+    let start_rank = SquareSet::all().rank(us.double_push_rank());
     let rank_below_promo = promo_rank.shift(down);
     let our_pawns = pos.pawns(us);
     // Single and double pawn pushes, not counting promotions.
@@ -62,72 +74,29 @@ pub fn generate_pawn_moves(us: Color, pos: &Position, moves: &mut Vec<Move>) {
         let up_right_promo = pawns_near_promo.shift(up_right).and(their_pieces);
         let up_promo = pawns_near_promo.shift(up).and(empty_squares);
         for target in up_left_promo {
-            moves.push(Move::promotion_capture(
-                target.towards(up_left.reverse()),
-                target,
-                PieceKind::Bishop,
-            ));
-            moves.push(Move::promotion_capture(
-                target.towards(up_left.reverse()),
-                target,
-                PieceKind::Knight,
-            ));
-            moves.push(Move::promotion_capture(
-                target.towards(up_left.reverse()),
-                target,
-                PieceKind::Rook,
-            ));
-            moves.push(Move::promotion_capture(
-                target.towards(up_left.reverse()),
-                target,
-                PieceKind::Queen,
-            ));
+            for &kind in promotions {
+                moves.push(Move::promotion_capture(
+                    target.towards(up_left.reverse()),
+                    target,
+                    kind,
+                ));
+            }
         }
 
         for target in up_right_promo {
-            moves.push(Move::promotion_capture(
-                target.towards(up_right.reverse()),
-                target,
-                PieceKind::Bishop,
-            ));
-            moves.push(Move::promotion_capture(
-                target.towards(up_right.reverse()),
-                target,
-                PieceKind::Knight,
-            ));
-            moves.push(Move::promotion_capture(
-                target.towards(up_right.reverse()),
-                target,
-                PieceKind::Rook,
-            ));
-            moves.push(Move::promotion_capture(
-                target.towards(up_right.reverse()),
-                target,
-                PieceKind::Queen,
-            ));
+            for &kind in promotions {
+                moves.push(Move::promotion_capture(
+                    target.towards(up_right.reverse()),
+                    target,
+                    kind,
+                ));
+            }
         }
 
         for target in up_promo {
-            moves.push(Move::promotion(
-                target.towards(up.reverse()),
-                target,
-                PieceKind::Bishop,
-            ));
-            moves.push(Move::promotion(
-                target.towards(up.reverse()),
-                target,
-                PieceKind::Knight,
-            ));
-            moves.push(Move::promotion(
-                target.towards(up.reverse()),
-                target,
-                PieceKind::Rook,
-            ));
-            moves.push(Move::promotion(
-                target.towards(up.reverse()),
-                target,
-                PieceKind::Queen,
-            ));
+            for &kind in promotions {
+                moves.push(Move::promotion(target.towards(up.reverse()), target, kind));
+            }
         }
     }
 
@@ -151,7 +120,12 @@ pub fn generate_pawn_moves(us: Color, pos: &Position, moves: &mut Vec<Move>) {
     }
 }
 
-pub fn generate_moves_for_kind(us: Color, pos: &Position, kind: PieceKind, moves: &mut Vec<Move>) {
+pub fn generate_moves_for_kind<B: MoveSink>(
+    us: Color,
+    pos: &Position,
+    kind: PieceKind,
+    moves: &mut B,
+) {
     debug_assert!(
         kind != PieceKind::King && kind != PieceKind::Pawn,
         "kings and pawns have their own movegen routines"
@@ -170,7 +144,7 @@ pub fn generate_moves_for_kind(us: Color, pos: &Position, kind: PieceKind, moves
     }
 }
 
-pub fn generate_king_moves(us: Color, pos: &Position, moves: &mut Vec<Move>) {
+pub fn generate_king_moves<B: MoveSink>(us: Color, pos: &Position, moves: &mut B) {
     let enemy_pieces = pos.pieces(us.toggle());
     let allied_pieces = pos.pieces(us);
     let pieces = enemy_pieces.or(allied_pieces);
@@ -198,16 +172,18 @@ pub fn generate_king_moves(us: Color, pos: &Position, moves: &mut Vec<Move>) {
 
         if let Some(piece) = pos.piece_at(starting_rook) {
             if piece.kind == PieceKind::Rook && piece.color == us {
-                let one = king.towards(Direction::East);
-                let two = one.towards(Direction::East);
-                if !pieces.contains(one) && !pieces.contains(two) {
-                    // The king moves across both squares one and two and it is illegal
-                    // to castle through check. We can only proceed if no enemy piece is
-                    // attacking the squares the king travels upon.
-                    if pos.squares_attacking(us.toggle(), one).is_empty()
-                        && pos.squares_attacking(us.toggle(), two).is_empty()
-                    {
-                        moves.push(Move::kingside_castle(king, two));
+                let path = king
+                    .checked_towards(Direction::East)
+                    .and_then(|one| Some((one, one.checked_towards(Direction::East)?)));
+                if let Some((one, two)) = path {
+                    if !pieces.contains(one) && !pieces.contains(two) {
+                        // The king moves across both squares one and two and it is illegal
+                        // to castle through check. We can only proceed if no enemy piece is
+                        // attacking the squares the king travels upon.
+                        if !pos.is_attacked(one, us.toggle()) && !pos.is_attacked(two, us.toggle())
+                        {
+                            moves.push(Move::kingside_castle(king, two));
+                        }
                     }
                 }
             }
@@ -219,16 +195,18 @@ pub fn generate_king_moves(us: Color, pos: &Position, moves: &mut Vec<Move>) {
 
         if let Some(piece) = pos.piece_at(starting_rook) {
             if piece.kind == PieceKind::Rook && piece.color == us {
-                let one = king.towards(Direction::West);
-                let two = one.towards(Direction::West);
-                let three = two.towards(Direction::West);
-                if !pieces.contains(one) && !pieces.contains(two) && !pieces.contains(three) {
-                    // Square three can be checked, but it can't be occupied. The rook
-                    // travels across square three, but the king does not.
-                    if pos.squares_attacking(us.toggle(), one).is_empty()
-                        && pos.squares_attacking(us.toggle(), two).is_empty()
-                    {
-                        moves.push(Move::queenside_castle(king, two));
+                let path = king.checked_towards(Direction::West).and_then(|one| {
+                    let two = one.checked_towards(Direction::West)?;
+                    Some((one, two, two.checked_towards(Direction::West)?))
+                });
+                if let Some((one, two, three)) = path {
+                    if !pieces.contains(one) && !pieces.contains(two) && !pieces.contains(three) {
+                        // Square three can be checked, but it can't be occupied. The rook
+                        // travels across square three, but the king does not.
+                        if !pos.is_attacked(one, us.toggle()) && !pos.is_attacked(two, us.toggle())
+                        {
+                            moves.push(Move::queenside_castle(king, two));
+                        }
                     }
                 }
             }
@@ -236,8 +214,84 @@ pub fn generate_king_moves(us: Color, pos: &Position, moves: &mut Vec<Move>) {
     }
 }
 
+/// A collection that move generation can add moves to. This is used instead of `Extend`, whose one-item-at-a-time path
+/// is much slower than a plain push for `SmallVec`.
+pub trait MoveSink {
+    fn push(&mut self, mov: Move);
+}
+
+impl MoveSink for Vec<Move> {
+    fn push(&mut self, mov: Move) {
+        Vec::push(self, mov)
+    }
+}
+
+impl<A: Array<Item = Move>> MoveSink for SmallVec<A> {
+    fn push(&mut self, mov: Move) {
+        SmallVec::push(self, mov)
+    }
+}
+
+/// A list of moves that is stored inline for the common case. Positions with more than 64 pseudolegal moves are rare,
+/// so move generation into a `MoveList` almost never allocates.
+pub type MoveList = SmallVec<[Move; 64]>;
+
+/// Generates all pseudolegal moves for the given side into the given vector.
 pub fn generate_moves(us: Color, pos: &Position, moves: &mut Vec<Move>) {
+    let start = moves.len();
+    generate_moves_into(us, pos, moves);
+    debug_validate_moves(us, pos, &moves[start..]);
+}
+
+/// Generates all pseudolegal moves for the given side into the given move list. This is instantiated within a4, so
+/// callers outside of it get the same inlining that the search does; prefer it to `generate_moves_into`.
+pub fn generate_move_list(us: Color, pos: &Position, moves: &mut MoveList) {
+    let start = moves.len();
+    generate_moves_into(us, pos, moves);
+    debug_validate_moves(us, pos, &moves[start..]);
+}
+
+/// Generates pseudolegal moves for the given side into the given move list, like `generate_move_list`, except that
+/// pawns only promote to queens and knights. This is for searches, where underpromotions to rooks and bishops are
+/// almost never worth the nodes that they cost; anything that needs every move, like perft or legality checking, should
+/// use the full generator.
+pub fn generate_moves_pruned(us: Color, pos: &Position, moves: &mut MoveList) {
+    let start = moves.len();
+    generate_pawn_moves_with(us, pos, moves, &USEFUL_PROMOTIONS);
+    generate_piece_moves(us, pos, moves);
+    debug_validate_moves(us, pos, &moves[start..]);
+}
+
+/// Generates all pseudolegal moves for the given side into any collection of moves, such as a `MoveList`.
+pub fn generate_moves_into<B: MoveSink>(us: Color, pos: &Position, moves: &mut B) {
     generate_pawn_moves(us, pos, moves);
+    generate_piece_moves(us, pos, moves);
+}
+
+/// In debug builds, checks that the given moves, which were just generated for the given side, are ones that
+/// `Position::is_pseudolegal` agrees the move generator could produce. This turns a move generator that has started
+/// producing nonsense into an immediate panic, rather than a search that quietly goes wrong somewhere else. Moves
+/// generated for the side that isn't to move can't be checked this way, so they aren't.
+fn debug_validate_moves(us: Color, pos: &Position, moves: &[Move]) {
+    if cfg!(debug_assertions) && us == pos.side_to_move() {
+        validate_moves(pos, moves);
+    }
+}
+
+/// Panics if any of the given moves isn't pseudolegal in the given position.
+fn validate_moves(pos: &Position, moves: &[Move]) {
+    for &mov in moves {
+        assert!(
+            pos.is_pseudolegal(mov),
+            "move generator produced {}, which isn't pseudolegal in {}",
+            mov,
+            pos.as_fen()
+        );
+    }
+}
+
+/// Generates all pseudolegal moves for the given side's pieces other than pawns.
+fn generate_piece_moves<B: MoveSink>(us: Color, pos: &Position, moves: &mut B) {
     generate_moves_for_kind(us, pos, PieceKind::Bishop, moves);
     generate_moves_for_kind(us, pos, PieceKind::Knight, moves);
     generate_moves_for_kind(us, pos, PieceKind::Rook, moves);
@@ -245,11 +299,44 @@ pub fn generate_moves(us: Color, pos: &Position, moves: &mut Vec<Move>) {
     generate_king_moves(us, pos, moves);
 }
 
+/// Generates all pseudolegal moves for the given side, ordered by a cheap heuristic: captures first, most valuable
+/// victim first and then least valuable attacker first (MVV-LVA), followed by every other move in generation order.
+/// This is far cruder than the search's move ordering, but is good enough for tools that just want to look at likely
+/// moves before unlikely ones.
+pub fn generate_ordered(us: Color, pos: &Position) -> Vec<Move> {
+    let mut moves = Vec::new();
+    generate_moves(us, pos, &mut moves);
+    moves.sort_by_cached_key(|&mov| {
+        if !mov.is_capture() {
+            return (1, 0, 0);
+        }
+
+        // There's no piece on the destination square of an en-passant capture, but it can only capture a pawn.
+        let victim = if mov.is_en_passant() {
+            PieceKind::Pawn
+        } else {
+            pos.piece_at(mov.destination())
+                .expect("capture with no victim")
+                .kind
+        };
+        let attacker = pos
+            .piece_at(mov.source())
+            .expect("move with no piece at source")
+            .kind;
+        (0, -victim.value(), attacker.value())
+    });
+
+    moves
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashSet;
 
-    use super::generate_moves;
+    use super::{
+        generate_move_list, generate_moves, generate_moves_pruned, generate_ordered,
+        validate_moves, MoveList,
+    };
     use crate::{core::*, position::Position};
 
     fn assert_moves_generated(fen: &'static str, moves: &[Move]) {
@@ -299,6 +386,69 @@ mod tests {
         }
     }
 
+    #[test]
+    fn ordered_moves_put_captures_first() {
+        // The pawn on d5 can take the queen or the rook, and the knight can take the queen.
+        let pos = Position::from_fen("4k3/8/1pr1q3/2NP4/8/8/8/7K w - - 0 1").unwrap();
+        let moves = generate_ordered(Color::White, &pos);
+        let first_quiet = moves.iter().position(|mov| !mov.is_capture()).unwrap();
+        assert_eq!(
+            &[
+                Move::capture(D5, E6),
+                Move::capture(C5, E6),
+                Move::capture(D5, C6),
+            ][..],
+            &moves[..first_quiet]
+        );
+        assert!(moves[first_quiet..].iter().all(|mov| !mov.is_capture()));
+    }
+
+    #[test]
+    fn move_list_matches_vec() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        ] {
+            let pos = Position::from_fen(fen).unwrap();
+            let mut vec = Vec::new();
+            generate_moves(pos.side_to_move(), &pos, &mut vec);
+            let mut list = MoveList::new();
+            generate_move_list(pos.side_to_move(), &pos, &mut list);
+            assert_eq!(vec.as_slice(), list.as_slice(), "{}", fen);
+            assert!(!list.spilled());
+        }
+    }
+
+    #[test]
+    fn generated_moves_pass_validation() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1",
+        ] {
+            let pos = Position::from_fen(fen).unwrap();
+            let mut moves = Vec::new();
+            generate_moves(pos.side_to_move(), &pos, &mut moves);
+            validate_moves(&pos, &moves);
+
+            let mut pruned = MoveList::new();
+            generate_moves_pruned(pos.side_to_move(), &pos, &mut pruned);
+            validate_moves(&pos, &pruned);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "isn't pseudolegal")]
+    fn validation_catches_bad_moves() {
+        let pos = Position::from_start_position();
+        validate_moves(&pos, &[Move::quiet(E2, E5)]);
+    }
+
     mod pawns {
         use super::*;
 
@@ -398,6 +548,28 @@ mod tests {
             )
         }
 
+        #[test]
+        fn pruned_promotions() {
+            let pos = Position::from_fen("1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+            let mut pruned = MoveList::new();
+            generate_moves_pruned(Color::White, &pos, &mut pruned);
+            let mut full = MoveList::new();
+            generate_move_list(Color::White, &pos, &mut full);
+            for kind in [PieceKind::Knight, PieceKind::Queen] {
+                assert!(pruned.contains(&Move::promotion(A7, A8, kind)));
+                assert!(pruned.contains(&Move::promotion_capture(A7, B8, kind)));
+            }
+
+            for kind in [PieceKind::Rook, PieceKind::Bishop] {
+                assert!(!pruned.contains(&Move::promotion(A7, A8, kind)));
+                assert!(!pruned.contains(&Move::promotion_capture(A7, B8, kind)));
+                assert!(full.contains(&Move::promotion(A7, A8, kind)));
+                assert!(full.contains(&Move::promotion_capture(A7, B8, kind)));
+            }
+
+            assert_eq!(full.len(), pruned.len() + 4);
+        }
+
         #[test]
         fn kiwipete_bug_1() {
             assert_moves_contains(
@@ -417,6 +589,51 @@ mod tests {
                 ],
             );
         }
+
+        #[test]
+        fn white_pawn_on_h_file_does_not_wrap() {
+            // h4xa6 would be a capture "to the northeast" if the board wrapped around.
+            assert_moves_generated("8/8/p7/8/7P/8/8/8 w - - 0 1", &[Move::quiet(H4, H5)]);
+        }
+
+        #[test]
+        fn black_pawn_on_a_file_does_not_wrap() {
+            assert_moves_generated("8/8/8/p7/8/7P/8/8 b - - 0 1", &[Move::quiet(A5, A4)]);
+        }
+
+        #[test]
+        fn white_pawn_on_a_file_captures_without_wrapping() {
+            // a4xh4 would be a capture "to the northwest" if the board wrapped around.
+            assert_moves_generated(
+                "8/8/8/1p6/P6p/8/8/8 w - - 0 1",
+                &[Move::quiet(A4, A5), Move::capture(A4, B5)],
+            );
+            assert_moves_contains("8/8/8/1p6/P6p/8/8/8 w - - 0 1", &[Move::capture(A4, B5)]);
+        }
+
+        #[test]
+        fn black_pawn_on_h_file_captures_without_wrapping() {
+            // h5xa5 would be a capture "to the southeast" if the board wrapped around.
+            assert_moves_generated(
+                "8/8/8/P6p/6P1/8/8/8 b - - 0 1",
+                &[Move::quiet(H5, H4), Move::capture(H5, G4)],
+            );
+            assert_moves_contains("8/8/8/P6p/6P1/8/8/8 b - - 0 1", &[Move::capture(H5, G4)]);
+        }
+
+        #[test]
+        fn promotion_on_edge_file_does_not_wrap() {
+            assert_moves_generated(
+                // a7xh7 would be a capture "to the northwest" if the board wrapped around.
+                "8/P6r/8/8/8/8/8/8 w - - 0 1",
+                &[
+                    Move::promotion(A7, A8, PieceKind::Knight),
+                    Move::promotion(A7, A8, PieceKind::Bishop),
+                    Move::promotion(A7, A8, PieceKind::Rook),
+                    Move::promotion(A7, A8, PieceKind::Queen),
+                ],
+            );
+        }
     }
 
     mod bishops {
@@ -486,6 +703,20 @@ mod tests {
             );
         }
 
+        #[test]
+        fn king_on_h_file_does_not_wrap() {
+            assert_moves_generated(
+                "8/8/8/p7/p6K/p7/8/8 w - - 0 1",
+                &[
+                    Move::quiet(H4, H5),
+                    Move::quiet(H4, G5),
+                    Move::quiet(H4, G4),
+                    Move::quiet(H4, G3),
+                    Move::quiet(H4, H3),
+                ],
+            );
+        }
+
         #[test]
         fn position_4_check_block() {
             assert_moves_contains(
@@ -577,4 +808,135 @@ mod tests {
             )
         }
     }
+
+    /// Perft tests: count the leaf nodes of the legal move tree to a fixed depth and compare against the well-known
+    /// reference counts for each position. This exercises move generation, legality testing, and making moves all at
+    /// once, including every kind of special move.
+    mod perft {
+        use super::*;
+
+        fn legal_moves(pos: &Position) -> Vec<Move> {
+            let mut moves = Vec::new();
+            generate_moves(pos.side_to_move(), pos, &mut moves);
+            moves.retain(|&mov| pos.is_legal_given_pseudolegal(mov));
+            moves
+        }
+
+        fn perft(pos: &Position, depth: u32) -> u64 {
+            if depth == 0 {
+                return 1;
+            }
+
+            let moves = legal_moves(pos);
+            if depth == 1 {
+                return moves.len() as u64;
+            }
+
+            moves
+                .into_iter()
+                .map(|mov| perft(&pos.clone_and_make_move(mov), depth - 1))
+                .sum()
+        }
+
+        fn perft_divide(pos: &Position, depth: u32) -> Vec<(Move, u64)> {
+            legal_moves(pos)
+                .into_iter()
+                .map(|mov| (mov, perft(&pos.clone_and_make_move(mov), depth - 1)))
+                .collect()
+        }
+
+        /// Asserts that the perft counts of the given position match `counts`, which starts at depth 1. On a mismatch,
+        /// dumps the count for every root move so that it can be compared against a reference engine.
+        fn assert_perft(fen: &'static str, counts: &[u64]) {
+            let pos = Position::from_fen(fen).unwrap();
+            for (depth, &expected) in (1..).zip(counts) {
+                let actual = perft(&pos, depth);
+                if actual != expected {
+                    println!("{}", pos);
+                    println!("perft({}) = {}, expected {}", depth, actual, expected);
+                    for (mov, count) in perft_divide(&pos, depth) {
+                        println!("  {}: {}", mov.as_uci(), count);
+                    }
+
+                    panic!("perft mismatch for {} at depth {}", fen, depth);
+                }
+            }
+        }
+
+        // Kiwipete and positions 5 and 6 have millions of nodes at depth 4, which takes too long for an unoptimized test
+        // build. Their depth 4 counts are checked by `deep`, which runs with `cargo test --release -- --ignored`.
+
+        #[test]
+        fn initial() {
+            assert_perft(
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                &[20, 400, 8902, 197281],
+            );
+        }
+
+        #[test]
+        fn kiwipete() {
+            assert_perft(
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                &[48, 2039, 97862],
+            );
+        }
+
+        #[test]
+        fn position_3() {
+            assert_perft(
+                "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+                &[14, 191, 2812, 43238],
+            );
+        }
+
+        #[test]
+        fn position_4() {
+            assert_perft(
+                "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+                &[6, 264, 9467, 422333],
+            );
+        }
+
+        #[test]
+        fn position_4_mirrored() {
+            assert_perft(
+                "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+                &[6, 264, 9467, 422333],
+            );
+        }
+
+        #[test]
+        fn position_5() {
+            assert_perft(
+                "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+                &[44, 1486, 62379],
+            );
+        }
+
+        #[test]
+        fn position_6() {
+            assert_perft(
+                "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+                &[46, 2079, 89890],
+            );
+        }
+
+        #[test]
+        #[ignore]
+        fn deep() {
+            assert_perft(
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                &[48, 2039, 97862, 4085603],
+            );
+            assert_perft(
+                "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+                &[44, 1486, 62379, 2103487],
+            );
+            assert_perft(
+                "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+                &[46, 2079, 89890, 3894594],
+            );
+        }
+    }
 }
@@ -7,9 +7,11 @@
 // except according to those terms.
 
 use std::{
+    cmp::max,
     convert::TryFrom,
     fmt::{self, Write},
     hash::{Hash, Hasher},
+    str::FromStr,
 };
 
 use thiserror::Error;
@@ -41,6 +43,42 @@ pub struct Position {
     zobrist_hash: u64,
     /// The move history of this position.
     history: Vec<Move>,
+    /// The Zobrist hashes of the positions preceding each move in `history`.
+    hash_history: Vec<u64>,
+    /// The material value of each color's pieces, not counting the king. Maintained incrementally as pieces are added
+    /// and removed.
+    material: [i32; 2],
+}
+
+/// Possible reasons that `Position::try_make_move` can reject a move.
+#[derive(Clone, PartialEq, Eq, Debug, Error)]
+pub enum MoveError {
+    #[error("null move")]
+    NullMove,
+    #[error("no piece at source square {0}")]
+    NoPieceAtSource(Square),
+    #[error("piece at {0} doesn't belong to the side to move")]
+    NotSideToMove(Square),
+    #[error("{0} is not a legal move")]
+    Illegal(Move),
+    #[error("{0} leaves the king in check")]
+    LeavesKingInCheck(Move),
+}
+
+/// Possible reasons that `Position::add_piece` or `Position::remove_piece` can fail.
+#[derive(Clone, PartialEq, Eq, Debug, Error)]
+pub enum PieceError {
+    #[error("square {0} is already occupied")]
+    Occupied(Square),
+    #[error("square {0} is empty")]
+    Empty(Square),
+}
+
+/// State needed to undo a null move. See [`Position::make_null_move`].
+#[derive(Copy, Clone, Debug)]
+#[must_use]
+pub struct NullMoveUndo {
+    en_passant_square: Option<Square>,
 }
 
 impl Position {
@@ -65,17 +103,11 @@ impl Position {
     }
 
     pub fn can_castle_kingside(&self, color: Color) -> bool {
-        match color {
-            Color::White => self.castle_status.contains(CastleStatus::WHITE_KINGSIDE),
-            Color::Black => self.castle_status.contains(CastleStatus::BLACK_KINGSIDE),
-        }
+        self.castle_status.contains(CastleStatus::kingside(color))
     }
 
     pub fn can_castle_queenside(&self, color: Color) -> bool {
-        match color {
-            Color::White => self.castle_status.contains(CastleStatus::WHITE_QUEENSIDE),
-            Color::Black => self.castle_status.contains(CastleStatus::BLACK_QUEENSIDE),
-        }
+        self.castle_status.contains(CastleStatus::queenside(color))
     }
 
     pub fn pieces(&self, color: Color) -> SquareSet {
@@ -90,6 +122,32 @@ impl Position {
         self.sets_by_piece[offset + kind as usize]
     }
 
+    /// Returns the material value of the given color's pieces, in pawns (see `PieceKind::value`). Kings aren't counted.
+    pub fn material(&self, color: Color) -> i32 {
+        self.material[color as usize]
+    }
+
+    /// Returns the material value of the given color's pieces other than pawns and the king, in centipawns.
+    pub fn non_pawn_material(&self, color: Color) -> i16 {
+        ((self.material(color) - self.pawns(color).len() as i32) * 100) as i16
+    }
+
+    /// Returns White's material minus Black's material, in centipawns. This is only a count of the pieces on the board,
+    /// not an evaluation of the position.
+    pub fn material_balance(&self) -> i16 {
+        ((self.material(Color::White) - self.material(Color::Black)) * 100) as i16
+    }
+
+    /// Returns the number of pieces on the board, of both colors and including kings and pawns.
+    pub fn piece_count(&self) -> u32 {
+        (self.pieces(Color::White) | self.pieces(Color::Black)).len()
+    }
+
+    /// Returns the number of pieces of the given color and kind on the board.
+    pub fn piece_count_of(&self, color: Color, kind: PieceKind) -> u32 {
+        self.pieces_of_kind(color, kind).len()
+    }
+
     pub fn pawns(&self, color: Color) -> SquareSet {
         self.pieces_of_kind(color, PieceKind::Pawn)
     }
@@ -113,10 +171,11 @@ impl Position {
     pub fn king(&self, color: Color) -> Option<Square> {
         let kings = self.pieces_of_kind(color, PieceKind::King);
         assert!(kings.len() <= 1);
-        // TODO(swgillespie) this is pretty inefficient
-        kings.into_iter().next()
+        kings.lsb()
     }
 
+    /// Returns the moves played to reach this position, oldest first. Null moves made during search are included
+    /// until they are unmade.
     pub fn history(&self) -> &[Move] {
         &self.history
     }
@@ -124,7 +183,7 @@ impl Position {
 
 impl Position {
     pub fn new() -> Position {
-        Position {
+        let mut pos = Position {
             sets_by_piece: [SquareSet::empty(); 12],
             sets_by_color: [SquareSet::empty(); 2],
             halfmove_clock: 0,
@@ -134,26 +193,34 @@ impl Position {
             side_to_move: Color::White,
             zobrist_hash: 0,
             history: vec![],
-        }
+            hash_history: vec![],
+            material: [0; 2],
+        };
+
+        pos.zobrist_hash = zobrist::hash_position(&pos);
+        pos
     }
 
-    pub fn add_piece(&mut self, square: Square, piece: Piece) -> Result<(), ()> {
+    /// Places a piece on an empty square.
+    pub fn add_piece(&mut self, square: Square, piece: Piece) -> Result<(), PieceError> {
         if self.piece_at(square).is_some() {
-            return Err(());
+            return Err(PieceError::Occupied(square));
         }
 
         self.sets_by_color[piece.color as usize].insert(square);
         let offset = if piece.color == Color::White { 0 } else { 6 };
         self.sets_by_piece[piece.kind as usize + offset].insert(square);
+        self.material[piece.color as usize] += material_value(piece.kind);
         zobrist::modify_piece(&mut self.zobrist_hash, square, piece);
         Ok(())
     }
 
-    pub fn remove_piece(&mut self, square: Square) -> Result<(), ()> {
+    /// Removes the piece on a square.
+    pub fn remove_piece(&mut self, square: Square) -> Result<(), PieceError> {
         let existing_piece = if let Some(piece) = self.piece_at(square) {
             piece
         } else {
-            return Err(());
+            return Err(PieceError::Empty(square));
         };
 
         self.sets_by_color[existing_piece.color as usize].remove(square);
@@ -163,10 +230,94 @@ impl Position {
             6
         };
         self.sets_by_piece[existing_piece.kind as usize + offset].remove(square);
+        self.material[existing_piece.color as usize] -= material_value(existing_piece.kind);
         zobrist::modify_piece(&mut self.zobrist_hash, square, existing_piece);
         Ok(())
     }
 
+    /// Replaces whatever is on the given square with the given piece, or leaves the square empty if there is no piece.
+    pub fn set_piece(&mut self, square: Square, piece: Option<Piece>) {
+        let _ = self.remove_piece(square);
+        if let Some(piece) = piece {
+            self.add_piece(square, piece)
+                .expect("square should be empty after removing its piece");
+        }
+    }
+
+    pub fn set_halfmove_clock(&mut self, halfmove_clock: u16) {
+        self.halfmove_clock = halfmove_clock;
+    }
+
+    pub fn set_fullmove_clock(&mut self, fullmove_clock: u16) {
+        self.fullmove_clock = fullmove_clock;
+    }
+
+    /// Resets the clocks to the state at the start of a game: no progress towards the 50-move rule, on the first move.
+    pub fn reset_clocks(&mut self) {
+        self.halfmove_clock = 0;
+        self.fullmove_clock = 1;
+    }
+
+    /// Returns the contents of every square on the board, indexed by square.
+    pub fn to_array(&self) -> [Option<Piece>; 64] {
+        let mut board = [None; 64];
+        for color in core::colors() {
+            for kind in core::piece_kinds() {
+                for square in self.pieces_of_kind(color, kind) {
+                    board[square.as_u8() as usize] = Some(Piece { kind, color });
+                }
+            }
+        }
+
+        board
+    }
+
+    /// Returns this position mirrored left to right, so that pieces on the a-file end up on the h-file. Castle rights
+    /// are cleared, since the kings and rooks no longer stand where castling starts. Apart from castling, chess is
+    /// symmetric from left to right, so the mirrored position is won, drawn, or lost just as this one is, with the same
+    /// side to move; the static evaluation isn't necessarily the same, though. The move history is dropped.
+    pub fn flip_horizontal(&self) -> Position {
+        self.transformed(Square::flip_horizontal, false)
+    }
+
+    /// Returns this position as seen from the other side of the board: the board is turned around, and the pieces and
+    /// the side to move swap colors, so that pawns still advance towards their own promotion rank. White's king on e1
+    /// becomes Black's king on d8. The result is the same game with the colors exchanged and the files mirrored, so the
+    /// side to move is winning, drawing, or losing just as it was. As with `flip_horizontal`, castle rights are
+    /// cleared and the move history is dropped.
+    pub fn rotate_180(&self) -> Position {
+        self.transformed(Square::rotate_180, true)
+    }
+
+    /// Moves every piece to the square given by `map`, optionally swapping piece colors and the side to move.
+    fn transformed(&self, map: fn(Square) -> Square, swap_colors: bool) -> Position {
+        let board = self.to_array();
+        let mut pos = Position::new();
+        for square in core::squares() {
+            let piece = board[square.as_u8() as usize].map(|piece| Piece {
+                color: if swap_colors {
+                    piece.color.toggle()
+                } else {
+                    piece.color
+                },
+                kind: piece.kind,
+            });
+            pos.set_piece(map(square), piece);
+        }
+
+        pos.side_to_move = if swap_colors {
+            self.side_to_move.toggle()
+        } else {
+            self.side_to_move
+        };
+        pos.castle_status = CastleStatus::NONE;
+        pos.en_passant_square = self.en_passant_square.map(map);
+        pos.halfmove_clock = self.halfmove_clock;
+        pos.fullmove_clock = self.fullmove_clock;
+        pos.zobrist_hash = zobrist::hash_position(&pos);
+        pos
+    }
+
     pub fn piece_at(&self, square: Square) -> Option<Piece> {
         let (board_offset, color) = if self.sets_by_color[Color::White as usize].contains(square) {
             (0, Color::White)
@@ -188,76 +339,280 @@ impl Position {
     }
 
     pub fn squares_attacking(&self, to_move: Color, target: Square) -> SquareSet {
-        // TODO(swgillespie) This function and king move generation need to be rewritten for efficiency
-        let mut attacks = SquareSet::empty();
-
-        // Pretend that there's a "super-piece" at the target square and see if it hits anything.
-        // This covers all pieces except for kings and pawns.
         let occupancy = self.pieces(Color::White) | self.pieces(Color::Black);
+        self.attackers_to(target, occupancy) & self.pieces(to_move)
+    }
 
-        // Queen attacks cover bishops, rooks, and queens, so check that first.
-        let sliding_pieces = self.pieces_of_kind(to_move, PieceKind::Queen)
-            | self.pieces_of_kind(to_move, PieceKind::Rook)
-            | self.pieces_of_kind(to_move, PieceKind::Bishop);
-        let sliding_attacks = queen_attacks(target, occupancy).and(sliding_pieces);
-        if !sliding_attacks.is_empty() {
-            // Hit - there's something that might be attacking via a slide. However, since we're
-            // modeling a superpiece, we need to check that the attacking pieces actually can legally
-            // attack this square.
-            for attacker in sliding_attacks {
-                let piece = self
-                    .piece_at(attacker)
-                    .expect("attack table produced piece not on board?");
-                if core::attacks(piece.kind, piece.color, attacker, occupancy).contains(target) {
-                    attacks.insert(attacker);
-                }
+    /// Returns the squares attacked by the piece on the given square, given the current occupancy of the board. The set
+    /// is empty if there is no piece on the square.
+    pub fn attacks_from(&self, square: Square) -> SquareSet {
+        match self.piece_at(square) {
+            Some(piece) => {
+                let occupancy = self.pieces(Color::White) | self.pieces(Color::Black);
+                attacks(piece.kind, piece.color, square, occupancy)
             }
+            None => SquareSet::empty(),
         }
+    }
+
+    /// Returns the squares of all pieces, of both colors, that attack the target square given the occupancy of the
+    /// board. The occupancy doesn't need to match the board; pieces that aren't in it neither attack nor block, which
+    /// lets callers see through pieces to the sliders behind them.
+    pub fn attackers_to(&self, target: Square, occupancy: SquareSet) -> SquareSet {
+        // Attacks are symmetric, so put each kind of piece on the target square and see which pieces of that kind it
+        // hits. Pawns are the exception, since they attack in the opposite direction of the pawns attacking them.
+        let white_pawns = pawn_attacks(target, Color::Black) & self.pawns(Color::White);
+        let black_pawns = pawn_attacks(target, Color::White) & self.pawns(Color::Black);
+        let mut attackers = white_pawns | black_pawns;
+        for kind in [PieceKind::Knight, PieceKind::King] {
+            let pieces =
+                self.pieces_of_kind(Color::White, kind) | self.pieces_of_kind(Color::Black, kind);
+            attackers |= attacks(kind, Color::White, target, occupancy) & pieces;
+        }
+
+        let queens = self.queens(Color::White) | self.queens(Color::Black);
+        let diagonal = self.bishops(Color::White) | self.bishops(Color::Black) | queens;
+        let straight = self.rooks(Color::White) | self.rooks(Color::Black) | queens;
+        attackers = attackers
+            | (attacks(PieceKind::Bishop, Color::White, target, occupancy) & diagonal)
+            | (attacks(PieceKind::Rook, Color::White, target, occupancy) & straight);
+        attackers & occupancy
+    }
 
-        // Knight attacks are straightforward since knight moves are symmetric.
-        let knight_attacks = knight_attacks(target).and(self.knights(to_move));
-        if !knight_attacks.is_empty() {
-            attacks = attacks | knight_attacks;
+    /// Returns whether or not any piece of the given color attacks the target square. This is equivalent to checking
+    /// that `squares_attacking` isn't empty, but stops as soon as it finds an attacker, and checks pawns, knights, and
+    /// kings before the more expensive sliding pieces.
+    pub fn is_attacked(&self, target: Square, by: Color) -> bool {
+        if !(pawn_attacks(target, by.toggle()) & self.pawns(by)).is_empty()
+            || !(knight_attacks(target) & self.knights(by)).is_empty()
+            || !(king_attacks(target) & self.pieces_of_kind(by, PieceKind::King)).is_empty()
+        {
+            return true;
         }
 
-        // For pawns, there are only a few places a pawn could be to legally attack this square. In all cases,
-        // the capturing pawn has to be on the rank immediately above (or below) the square we're looking at.
-        //
-        // A correlary to this is that pieces on the bottom (or top) ranks can't be attacked by pawns.
-        let cant_be_attacked_by_pawns_rank = if to_move == Color::White {
-            RANK_1
+        let occupancy = self.pieces(Color::White) | self.pieces(Color::Black);
+        let queens = self.queens(by);
+        !(attacks(PieceKind::Bishop, by, target, occupancy) & (self.bishops(by) | queens))
+            .is_empty()
+            || !(attacks(PieceKind::Rook, by, target, occupancy) & (self.rooks(by) | queens))
+                .is_empty()
+    }
+
+    pub fn is_check(&self, us: Color) -> bool {
+        if let Some(king) = self.king(us) {
+            self.is_attacked(king, us.toggle())
         } else {
-            RANK_8
+            false
+        }
+    }
+
+    /// Returns whether or not the given move, made by the side to move, puts the other side's king in check. This is
+    /// equivalent to making the move and then calling `is_check`, but doesn't make the move, so it's cheap enough to use
+    /// to decide whether a move is worth making at all. Checks given by castling rooks, by promoted pieces, and by
+    /// sliders uncovered by the move (including by an en-passant capture) are all noticed.
+    pub fn gives_check(&self, mov: Move) -> bool {
+        let us = self.side_to_move;
+        let king = match self.king(us.toggle()) {
+            Some(king) => king,
+            None => return false,
+        };
+        let moving_piece = match self.piece_at(mov.source()) {
+            Some(piece) => piece,
+            None => return false,
         };
 
-        if target.rank() != cant_be_attacked_by_pawns_rank {
-            let pawn_attack_rank = if to_move == Color::White {
-                target.towards(Direction::South).rank()
+        let mut occupancy = self.pieces(Color::White) | self.pieces(Color::Black);
+        if let Some(captured) = mov.captured_square(us) {
+            occupancy.remove(captured);
+        }
+        occupancy.remove(mov.source());
+        occupancy.insert(mov.destination());
+
+        // The piece that could give check from where it lands. When castling, that's the rook, not the king.
+        let (kind, square) = if mov.is_castle() {
+            // As in `make_move`, the rook lands on the far side of the king from where it started.
+            let (rook_source, rook_destination) = if mov.is_kingside_castle() {
+                (
+                    mov.destination().towards(Direction::East),
+                    mov.destination().towards(Direction::West),
+                )
             } else {
-                target.towards(Direction::North).rank()
+                (
+                    mov.destination()
+                        .towards(Direction::West)
+                        .towards(Direction::West),
+                    mov.destination().towards(Direction::East),
+                )
             };
-            for pawn in self.pawns(to_move) & SquareSet::all().rank(pawn_attack_rank) {
-                if pawn_attacks(pawn, to_move).contains(target) {
-                    attacks.insert(pawn);
-                }
-            }
+            occupancy.remove(rook_source);
+            occupancy.insert(rook_destination);
+            (PieceKind::Rook, rook_destination)
+        } else if mov.is_promotion() {
+            (mov.promotion_piece(), mov.destination())
+        } else {
+            (moving_piece.kind, mov.destination())
+        };
+
+        // Pieces that didn't move can only give check by being uncovered, since the other side can't have been in check
+        // before the move. Pieces that did move aren't counted by `attackers_to`, since their old squares aren't in the
+        // occupancy anymore.
+        attacks(kind, us, square, occupancy).contains(king)
+            || !(self.attackers_to(king, occupancy) & self.pieces(us)).is_empty()
+    }
+
+    /// Returns the squares of the pieces giving check to the given color's king. The set is empty if the king isn't in
+    /// check, or if there is no king.
+    pub fn checkers(&self, color: Color) -> SquareSet {
+        match self.king(color) {
+            Some(king) => self.squares_attacking(color.toggle(), king),
+            None => SquareSet::empty(),
         }
+    }
 
-        // There's only one king, so it's cheap to check.
-        if let Some(king) = self.king(to_move) {
-            if king_attacks(king).contains(target) {
-                attacks.insert(king);
+    /// Returns the squares of the given color's pieces that are absolutely pinned to their king: pieces that are the
+    /// only thing standing between their king and an enemy slider that could attack along that line.
+    pub fn pinned_pieces(&self, color: Color) -> SquareSet {
+        let king = match self.king(color) {
+            Some(king) => king,
+            None => return SquareSet::empty(),
+        };
+
+        let them = color.toggle();
+        let occupancy = self.pieces(Color::White) | self.pieces(Color::Black);
+        let queens = self.queens(them);
+        let snipers = (attacks(PieceKind::Bishop, them, king, SquareSet::empty())
+            & (self.bishops(them) | queens))
+            | (attacks(PieceKind::Rook, them, king, SquareSet::empty())
+                & (self.rooks(them) | queens));
+        let mut pinned = SquareSet::empty();
+        for sniper in snipers {
+            let blockers = squares_between(king, sniper) & occupancy;
+            if blockers.len() == 1 {
+                pinned |= blockers & self.pieces(color);
             }
         }
 
-        attacks
+        pinned
     }
 
-    pub fn is_check(&self, us: Color) -> bool {
-        if let Some(king) = self.king(us) {
-            !self.squares_attacking(us.toggle(), king).is_empty()
+    /// Returns the squares that the piece on the given square can move to without exposing its king. For a pinned
+    /// piece, this is the line between its king and the pinning piece, including the pinning piece; for any other
+    /// square, it is every square.
+    pub fn pin_ray(&self, square: Square) -> SquareSet {
+        let color = match self.piece_at(square) {
+            Some(piece) => piece.color,
+            None => return SquareSet::all(),
+        };
+        let king = match self.king(color) {
+            Some(king) if king != square => king,
+            _ => return SquareSet::all(),
+        };
+        let dir = match Direction::between(king, square) {
+            Some(dir) => dir,
+            None => return SquareSet::all(),
+        };
+
+        let occupancy = self.pieces(Color::White) | self.pieces(Color::Black);
+        if !(squares_between(king, square) & occupancy).is_empty() {
+            return SquareSet::all();
+        }
+
+        let pinner = match square.ray_squares(dir).find(|&sq| occupancy.contains(sq)) {
+            Some(pinner) => pinner,
+            None => return SquareSet::all(),
+        };
+        let them = color.toggle();
+        let (file_delta, rank_delta) = dir.as_deltas();
+        let sliders = if file_delta != 0 && rank_delta != 0 {
+            self.bishops(them) | self.queens(them)
         } else {
-            false
+            self.rooks(them) | self.queens(them)
+        };
+        if !sliders.contains(pinner) {
+            return SquareSet::all();
+        }
+
+        let mut ray = squares_between(king, pinner);
+        ray.insert(pinner);
+        ray
+    }
+
+    /// Returns whether the given color has a pawn that could capture en-passant on the given square.
+    fn can_capture_en_passant(&self, ep_square: Square, capturer: Color) -> bool {
+        !(pawn_attacks(ep_square, capturer.toggle()) & self.pawns(capturer)).is_empty()
+    }
+
+    /// Returns whether or not the move generator would produce the given move for this position, without generating
+    /// any moves: the source square must hold a piece of the side to move that can reach the destination, and the kind
+    /// of move must agree with the board (captures capture, en-passant and double pushes are only made by pawns when
+    /// they're possible, and so on). Castles are checked by generating only king moves.
+    ///
+    /// Together with `is_legal_given_pseudolegal`, this is how `is_legal` checks moves that came from somewhere other
+    /// than the move generator, like the transposition table.
+    pub fn is_pseudolegal(&self, mov: Move) -> bool {
+        if mov.is_null() {
+            return false;
+        }
+
+        let us = self.side_to_move;
+        let (source, dest) = (mov.source(), mov.destination());
+        let kind = match self.piece_at(source) {
+            Some(piece) if piece.color == us => piece.kind,
+            _ => return false,
+        };
+
+        if mov.is_castle() {
+            if kind != PieceKind::King {
+                return false;
+            }
+
+            let mut moves = movegen::MoveList::new();
+            movegen::generate_king_moves(us, self, &mut moves);
+            return moves.contains(&mov);
+        }
+
+        // Nothing can move onto one of our own pieces, and captures (other than en-passant) are exactly the moves that
+        // move onto one of theirs.
+        if self.pieces(us).contains(dest) {
+            return false;
+        }
+
+        if mov.is_en_passant() {
+            return kind == PieceKind::Pawn
+                && self.en_passant_square == Some(dest)
+                && pawn_attacks(source, us).contains(dest);
+        }
+
+        if mov.is_capture() != self.pieces(us.toggle()).contains(dest) {
+            return false;
+        }
+
+        let occupancy = self.pieces(Color::White) | self.pieces(Color::Black);
+        if kind != PieceKind::Pawn {
+            return !mov.is_promotion()
+                && !mov.is_double_pawn_push()
+                && attacks(kind, us, source, occupancy).contains(dest);
+        }
+
+        if mov.is_promotion() != (dest.rank() == us.promotion_rank()) {
+            return false;
+        }
+
+        if mov.is_capture() {
+            return pawn_attacks(source, us).contains(dest);
+        }
+
+        let one = match source.checked_towards(us.forward()) {
+            Some(one) => one,
+            None => return false,
+        };
+
+        if mov.is_double_pawn_push() {
+            source.rank() == us.double_push_rank()
+                && !occupancy.contains(one)
+                && one.checked_towards(us.forward()) == Some(dest)
+        } else {
+            one == dest
         }
     }
 
@@ -266,9 +621,17 @@ impl Position {
     /// useful for legality testing moves coming out of the move generator, which is known to
     /// produce only pseudolegal moves.
     pub fn is_legal_given_pseudolegal(&self, mov: Move) -> bool {
-        // The below implementation is naive and simple, but correct. There's lots of room for performance wins here.
-        let mut new_pos = self.clone();
         let side = self.side_to_move();
+        // When not in check, a move by anything other than the king can only be illegal if it exposes the king, which
+        // means moving a pinned piece off of its pin ray. En-passant is the exception, since it removes two pieces
+        // from the capturing rank.
+        let is_king_move = self.king(side) == Some(mov.source());
+        if !is_king_move && !mov.is_en_passant() && !self.is_check(side) {
+            return self.pin_ray(mov.source()).contains(mov.destination());
+        }
+
+        // Everything else is checked by making the move and seeing if the king is attacked.
+        let mut new_pos = self.clone();
         new_pos.make_move(mov);
         !new_pos.is_check(side)
     }
@@ -276,14 +639,313 @@ impl Position {
     /// Legality test for any move. It is generally going to be much faster to use is_legal_given_pseudolegal if you
     /// already know that the machine is pseudolegal.
     pub fn is_legal(&self, mov: Move) -> bool {
+        self.is_pseudolegal(mov) && self.is_legal_given_pseudolegal(mov)
+    }
+
+    /// Returns whether or not this position is quiet: the side to move isn't in check and has no legal promotion or
+    /// capture that wins material according to static exchange evaluation. Static evaluation of a quiet position can
+    /// be trusted, since there's nothing immediately tactical left to resolve.
+    pub fn is_quiet(&self) -> bool {
+        if self.is_check(self.side_to_move) {
+            return false;
+        }
+
         let mut moves = vec![];
         movegen::generate_moves(self.side_to_move, self, &mut moves);
-        // O(n) scan here; could be O(1) if we collect moves into a set
-        if !moves.contains(&mov) {
+        moves.retain(|&m| m.is_tactical());
+        !moves.into_iter().any(|mov| {
+            (mov.is_promotion() || self.see_ge(mov, 1)) && self.is_legal_given_pseudolegal(mov)
+        })
+    }
+}
+
+//
+// Static exchange evaluation.
+//
+
+impl Position {
+    /// Static exchange evaluation of a capture. Returns the material balance, in pawns, of making the capture and then
+    /// playing out the best sequence of recaptures on the destination square. Negative values indicate captures that
+    /// lose material.
+    pub fn see(&self, mov: Move) -> i32 {
+        debug_assert!(mov.is_capture(), "see called on a non-capture");
+
+        let captured_piece_value = mov
+            .captured_square(self.side_to_move)
+            .and_then(|square| self.piece_at(square))
+            .expect("capture with no piece at the captured square")
+            .kind
+            .value();
+
+        // For promo captures, we "gain" material points from turning the pawn into another piece.
+        let promotion_value = if mov.is_promotion() {
+            mov.promotion_piece().value() - 1
+        } else {
+            0
+        };
+
+        let child = self.clone_and_make_move(mov);
+        captured_piece_value + promotion_value - child.static_exchange_evaluation(mov.destination())
+    }
+
+    /// Returns whether the static exchange evaluation of a capture is at least `threshold`, i.e. whether
+    /// `self.see(mov) >= threshold`. Pruning decisions usually only need to know which side of a threshold a capture
+    /// falls on, so this plays out the exchange on a bitboard, without making any moves, and stops as soon as the
+    /// outcome is certain.
+    pub fn see_ge(&self, mov: Move, threshold: i32) -> bool {
+        debug_assert!(mov.is_capture(), "see_ge called on a non-capture");
+
+        let target = mov.destination();
+        let captured_square = mov
+            .captured_square(self.side_to_move)
+            .expect("see_ge called on a non-capture");
+        let captured_piece_value = self
+            .piece_at(captured_square)
+            .expect("capture with no piece at the captured square")
+            .kind
+            .value();
+        let (promotion_value, moved_piece_value) = if mov.is_promotion() {
+            let value = mov.promotion_piece().value();
+            (value - 1, value)
+        } else {
+            let moved_piece = self
+                .piece_at(mov.source())
+                .expect("capture with no piece at the source square");
+            (0, moved_piece.kind.value())
+        };
+
+        // `swap` is how much the side that just captured can afford to lose on the next capture while still meeting
+        // the threshold, negated at every turn so that it's always from the point of view of the side to capture.
+        let mut swap = captured_piece_value + promotion_value - threshold;
+        if swap < 0 {
+            // Even if the opponent doesn't recapture, the capture doesn't win enough.
+            return false;
+        }
+
+        swap = moved_piece_value - swap;
+        if swap <= 0 {
+            // Even if the opponent recaptures, the capture wins enough.
+            return true;
+        }
+
+        let mut occupied = self.pieces(Color::White) | self.pieces(Color::Black);
+        occupied.remove(mov.source());
+        occupied.remove(captured_square);
+        let mut attackers = self.attackers_to(target, occupied);
+        let mut side = self.side_to_move;
+        // Whether the side that made the root capture meets the threshold if the exchange stops here.
+        let mut result = true;
+        loop {
+            side = side.toggle();
+            attackers &= occupied;
+            // Recapture with the least valuable piece, in the same order that `see` does.
+            let attacker = match (attackers & self.pieces(side))
+                .into_iter()
+                .min_by_key(|&sq| self.piece_at(sq).unwrap().kind.value())
+            {
+                Some(attacker) => attacker,
+                None => break,
+            };
+
+            result = !result;
+            swap = self.piece_at(attacker).unwrap().kind.value() - swap;
+            if swap < result as i32 {
+                break;
+            }
+
+            // Moving the attacker might reveal a slider behind it.
+            occupied.remove(attacker);
+            attackers |= self.attackers_to(target, occupied);
+        }
+
+        result
+    }
+
+    /// Computes the material that the side to move can expect to win by initiating a sequence of captures on the
+    /// target square. The side to move can always decline to capture, so this is never negative.
+    pub(crate) fn static_exchange_evaluation(&self, target: Square) -> i32 {
+        let mut value = 0;
+        if let Some(attacker) = self.smallest_attacker(target) {
+            let target_piece = self.piece_at(target).unwrap();
+            let child = self.clone_and_make_move(Move::capture(attacker, target));
+            // The term may be negative, which indicates an unprofitable recapture. We must assume that our opponent won't
+            // do that.
+            value = max(
+                target_piece.kind.value() - child.static_exchange_evaluation(target),
+                0,
+            );
+        }
+
+        value
+    }
+
+    fn smallest_attacker(&self, target: Square) -> Option<Square> {
+        self.squares_attacking(self.side_to_move(), target)
+            .into_iter()
+            .min_by_key(|&sq| self.piece_at(sq).unwrap().kind.value())
+    }
+}
+
+//
+// Repetition detection.
+//
+
+impl Position {
+    /// Returns whether or not the side to move has a reversible move that reaches a position that has occurred
+    /// earlier in the game, i.e. whether the side to move can force a repetition. `ply` is the distance from the root
+    /// of the search; a repetition of a position within the search tree counts as a draw, while repeating a position
+    /// from before the root requires that position to have occurred twice already.
+    ///
+    /// This uses Marcel van Kervinck's cuckoo tables, which identify the single reversible move (if any) that would
+    /// take us from the current position to an earlier one by looking up the difference of the two positions' hashes.
+    pub fn has_upcoming_repetition(&self, ply: u32) -> bool {
+        // Only positions since the last irreversible move (or null move) can repeat.
+        let plies_from_null = self
+            .history
+            .iter()
+            .rev()
+            .position(|mov| mov.is_null())
+            .unwrap_or(self.history.len());
+        let end = (self.halfmove_clock as usize).min(plies_from_null);
+        if end < 3 {
+            return false;
+        }
+
+        let occupancy = self.pieces(Color::White) | self.pieces(Color::Black);
+        for i in (3..=end).step_by(2) {
+            let previous_hash = self.hash_history[self.hash_history.len() - i];
+            let (s1, s2) = match zobrist::reversible_move(self.zobrist_hash ^ previous_hash) {
+                Some(squares) => squares,
+                None => continue,
+            };
+
+            // The move has to be unobstructed in the current position.
+            if !(squares_between(s1, s2) & occupancy).is_empty() {
+                continue;
+            }
+
+            if ply as usize > i {
+                return true;
+            }
+
+            // For positions at or before the root, the move must belong to the side to move (and not be a move that
+            // the opponent could have made to reach the current position), and the position that it repeats must
+            // itself have been repeated.
+            let moving_square = if occupancy.contains(s1) { s1 } else { s2 };
+            let moving_piece = self.piece_at(moving_square).unwrap();
+            if moving_piece.color != self.side_to_move {
+                continue;
+            }
+
+            if self.is_repeated_before(self.hash_history.len() - i, end - i) {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Returns whether or not the position at the given index into the hash history occurred earlier in the game,
+    /// looking back at most `lookback` plies.
+    fn is_repeated_before(&self, index: usize, lookback: usize) -> bool {
+        let hash = self.hash_history[index];
+        (4..=lookback)
+            .step_by(2)
+            .any(|distance| self.hash_history[index - distance] == hash)
+    }
+}
+
+//
+// Game status.
+//
+
+/// The state of a game, as far as the rules of chess are concerned.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum GameStatus {
+    /// The game isn't over.
+    Ongoing,
+    /// The side to move has been checkmated.
+    Checkmate,
+    /// The side to move has no legal moves, but isn't in check.
+    Stalemate,
+    /// A hundred plies have passed without a capture or a pawn move.
+    FiftyMoveRule,
+    /// The current position has occurred three times.
+    ThreefoldRepetition,
+    /// Neither side has enough material left to deliver checkmate.
+    InsufficientMaterial,
+}
+
+impl Position {
+    /// Returns the status of the game. Draws by the fifty-move rule and by repetition are treated as automatic, rather
+    /// than as draws that a player has to claim.
+    pub fn status(&self) -> GameStatus {
+        let mut moves = vec![];
+        movegen::generate_moves(self.side_to_move, self, &mut moves);
+        if !moves
+            .into_iter()
+            .any(|mov| self.is_legal_given_pseudolegal(mov))
+        {
+            return if self.is_check(self.side_to_move) {
+                GameStatus::Checkmate
+            } else {
+                GameStatus::Stalemate
+            };
+        }
+
+        if self.halfmove_clock >= 100 {
+            GameStatus::FiftyMoveRule
+        } else if self.is_threefold_repetition() {
+            GameStatus::ThreefoldRepetition
+        } else if self.has_insufficient_material() {
+            GameStatus::InsufficientMaterial
+        } else {
+            GameStatus::Ongoing
+        }
+    }
+
+    /// Returns whether or not the current position has occurred at least twice before.
+    pub fn is_threefold_repetition(&self) -> bool {
+        let lookback = (self.halfmove_clock as usize).min(self.hash_history.len());
+        let repetitions = (2..=lookback)
+            .step_by(2)
+            .filter(|&distance| {
+                self.hash_history[self.hash_history.len() - distance] == self.zobrist_hash
+            })
+            .count();
+        repetitions >= 2
+    }
+
+    /// Returns whether or not neither side can possibly checkmate the other: there are no pawns, rooks, or queens left,
+    /// and either there is at most one minor piece on the board or all of the minor pieces are bishops on squares of
+    /// the same color.
+    pub fn has_insufficient_material(&self) -> bool {
+        let heavy_pieces_or_pawns = colors()
+            .any(|color| !(self.pawns(color) | self.rooks(color) | self.queens(color)).is_empty());
+        if heavy_pieces_or_pawns {
             return false;
         }
 
-        self.is_legal_given_pseudolegal(mov)
+        let knights = self.knights(Color::White) | self.knights(Color::Black);
+        let bishops = self.bishops(Color::White) | self.bishops(Color::Black);
+        if knights.len() + bishops.len() <= 1 {
+            return true;
+        }
+
+        knights.is_empty() && {
+            let mut bishop_squares = bishops.into_iter().map(Square::color);
+            let first = bishop_squares.next();
+            bishop_squares.all(|color| Some(color) == first)
+        }
+    }
+}
+
+/// The value of a piece for the purposes of `Position::material`. Kings are always on the board, so counting them
+/// would only get in the way of comparing material.
+fn material_value(kind: PieceKind) -> i32 {
+    match kind {
+        PieceKind::King => 0,
+        kind => kind.value(),
     }
 }
 
@@ -292,62 +954,114 @@ impl Position {
 //
 
 impl Position {
-    /// Shorthand for cloning a position and making a move.
+    /// Shorthand for cloning a position and making a move. This is the non-mutating counterpart to
+    /// [`Position::make_move`]; the position it is called on is left untouched.
     pub fn clone_and_make_move(&self, mov: Move) -> Position {
         let mut pos = self.clone();
         pos.make_move(mov);
         pos
     }
 
-    /// Makes a move on the position, updating all internal state to reflect the effects of the move.
-    pub fn make_move(&mut self, mov: Move) {
-        self.history.push(mov);
+    /// Makes a null move on the position, passing the turn to the other side without moving any pieces. Returns the
+    /// state needed to undo the null move with [`Position::unmake_null_move`].
+    ///
+    /// A null move only affects a handful of things:
+    ///  1. EP is not legal next turn.
+    ///  2. The fullmove clock increases if Black makes the null move.
+    pub fn make_null_move(&mut self) -> NullMoveUndo {
+        let undo = NullMoveUndo {
+            en_passant_square: self.en_passant_square,
+        };
+
+        self.history.push(Move::null());
+        self.hash_history.push(self.zobrist_hash);
+        zobrist::modify_en_passant(&mut self.zobrist_hash, self.en_passant_square, None);
+        self.en_passant_square = None;
+        self.side_to_move = self.side_to_move.toggle();
+        zobrist::modify_side_to_move(&mut self.zobrist_hash);
+        if self.side_to_move == Color::White {
+            self.fullmove_clock += 1;
+        }
+
+        undo
+    }
+
+    /// Undoes a null move made by [`Position::make_null_move`], restoring the position to exactly the state that it
+    /// was in beforehand.
+    pub fn unmake_null_move(&mut self, undo: NullMoveUndo) {
+        debug_assert!(
+            self.history.last().map_or(false, |mov| mov.is_null()),
+            "unmake_null_move without a null move"
+        );
 
-        // Quick out for null moves:
-        //  1. EP is not legal next turn.
-        //  2. Halfmove clock always increases.
-        //  3. Fullmove clock increases if Black makes the null move.
+        self.history.pop();
+        self.hash_history.pop();
+        if self.side_to_move == Color::White {
+            self.fullmove_clock -= 1;
+        }
+
+        self.side_to_move = self.side_to_move.toggle();
+        zobrist::modify_side_to_move(&mut self.zobrist_hash);
+        zobrist::modify_en_passant(&mut self.zobrist_hash, None, undo.en_passant_square);
+        self.en_passant_square = undo.en_passant_square;
+    }
+
+    /// Makes a move on the position if it is legal, returning an error describing why it isn't otherwise. The position
+    /// is left unchanged if the move is rejected. Unlike `make_move`, this is safe to use with moves from untrusted
+    /// sources; null moves are rejected, since they aren't legal chess moves.
+    pub fn try_make_move(&mut self, mov: Move) -> Result<(), MoveError> {
         if mov.is_null() {
-            self.en_passant_square = None;
-            self.side_to_move = self.side_to_move.toggle();
-            zobrist::modify_side_to_move(&mut self.zobrist_hash);
-            if self.side_to_move == Color::White {
-                self.fullmove_clock += 1;
+            return Err(MoveError::NullMove);
+        }
+
+        match self.piece_at(mov.source()) {
+            None => return Err(MoveError::NoPieceAtSource(mov.source())),
+            Some(piece) if piece.color != self.side_to_move => {
+                return Err(MoveError::NotSideToMove(mov.source()))
             }
+            Some(_) => {}
+        }
+
+        let mut moves = vec![];
+        movegen::generate_moves(self.side_to_move, self, &mut moves);
+        if !moves.contains(&mov) {
+            return Err(MoveError::Illegal(mov));
+        }
+
+        if !self.is_legal_given_pseudolegal(mov) {
+            return Err(MoveError::LeavesKingInCheck(mov));
+        }
+
+        self.make_move(mov);
+        Ok(())
+    }
+
+    /// Makes a move on the position, updating all internal state to reflect the effects of the move. The move must be
+    /// legal; this panics on some illegal moves and silently corrupts the position on others. Use `try_make_move` for
+    /// moves that haven't already been checked.
+    pub fn make_move(&mut self, mov: Move) {
+        if mov.is_null() {
+            let _ = self.make_null_move();
             return;
         }
 
+        self.history.push(mov);
+        self.hash_history.push(self.zobrist_hash);
+
         let moving_piece = self
             .piece_at(mov.source())
             .expect("invalid move: no piece at source square");
 
         // If this move is a capture, we need to remove the captured piece from the board before we
         // proceed.
-        if mov.is_capture() {
-            // The target square is often the destination square of the move, except in the case of
-            // en-passant where the target square lies on an adjacent file.
-            let target_square = if !mov.is_en_passant() {
-                mov.destination()
-            } else {
-                // En-passant moves are the only case when the piece being captured does
-                // not lie on the same square as the move destination.
-                let ep_dir = if self.side_to_move == Color::White {
-                    Direction::South
-                } else {
-                    Direction::North
-                };
-
-                let ep_square = if let Some(ep) = self.en_passant_square {
-                    ep
-                } else {
-                    panic!(
-                        "invalid move: EP without EP-square ({}) {}",
-                        mov,
-                        self.as_fen()
-                    );
-                };
-                ep_square.towards(ep_dir)
-            };
+        if let Some(target_square) = mov.captured_square(self.side_to_move) {
+            if mov.is_en_passant() && self.en_passant_square != Some(mov.destination()) {
+                panic!(
+                    "invalid move: EP without EP-square ({}) {}",
+                    mov,
+                    self.as_fen()
+                );
+            }
 
             // Remove the piece from the board - it has been captured.
             self.remove_piece(target_square)
@@ -356,14 +1070,9 @@ impl Position {
             // If this piece is a rook on its starting square, invalidate the castle for the other
             // player.
             if target_square == kingside_rook(self.side_to_move.toggle()) {
-                self.castle_status &= !kingside_castle_mask(self.side_to_move.toggle());
-                zobrist::modify_kingside_castle(&mut self.zobrist_hash, self.side_to_move.toggle());
+                self.revoke_castle_rights(CastleStatus::kingside(self.side_to_move.toggle()));
             } else if target_square == queenside_rook(self.side_to_move.toggle()) {
-                self.castle_status &= !queenside_castle_mask(self.side_to_move.toggle());
-                zobrist::modify_queenside_castle(
-                    &mut self.zobrist_hash,
-                    self.side_to_move.toggle(),
-                );
+                self.revoke_castle_rights(CastleStatus::queenside(self.side_to_move.toggle()));
             }
         }
 
@@ -381,10 +1090,15 @@ impl Position {
                 (Direction::East, Direction::West, 2)
             };
 
-            let new_rook_square = mov.destination().towards(post_castle_dir);
+            let new_rook_square = mov
+                .destination()
+                .checked_towards(post_castle_dir)
+                .expect("invalid move: castle rook target off the board");
             let mut rook_square = mov.destination();
             for _ in 0..num_squares {
-                rook_square = rook_square.towards(pre_castle_dir);
+                rook_square = rook_square
+                    .checked_towards(pre_castle_dir)
+                    .expect("invalid move: castle rook off the board");
             }
 
             let rook = self
@@ -419,17 +1133,17 @@ impl Position {
                 Direction::North
             };
 
-            let ep_square = mov.destination().towards(ep_dir);
-            zobrist::modify_en_passant(
-                &mut self.zobrist_hash,
-                self.en_passant_square,
-                Some(ep_square),
-            );
-            self.en_passant_square = Some(ep_square);
+            // Only record the square if an enemy pawn can actually capture there, so that this position hashes
+            // the same as the one where the pawn had already been on its destination square.
+            let ep_square = Some(mov.destination().towards(ep_dir)).filter(|&ep_square| {
+                self.can_capture_en_passant(ep_square, self.side_to_move.toggle())
+            });
+            zobrist::modify_en_passant(&mut self.zobrist_hash, self.en_passant_square, ep_square);
+            self.en_passant_square = ep_square;
         } else {
             // All other moves clear the en-passant square.
-            self.en_passant_square = None;
             zobrist::modify_en_passant(&mut self.zobrist_hash, self.en_passant_square, None);
+            self.en_passant_square = None;
         }
 
         // Re-calculate our castle status. Side to move may have invalidated their castle rights
@@ -437,24 +1151,16 @@ impl Position {
         if moving_piece.kind == PieceKind::Rook {
             // Moving a rook invalidates the castle on that rook's side of the board.
 
-            if self.can_castle_queenside(self.side_to_move)
-                && mov.source() == queenside_rook(self.side_to_move)
-            {
+            if mov.source() == queenside_rook(self.side_to_move) {
                 // Move of the queenside rook. Can't castle queenside anymore.
-                self.castle_status &= !queenside_castle_mask(self.side_to_move);
-                zobrist::modify_queenside_castle(&mut self.zobrist_hash, self.side_to_move);
-            } else if self.can_castle_kingside(self.side_to_move)
-                && mov.source() == kingside_rook(self.side_to_move)
-            {
+                self.revoke_castle_rights(CastleStatus::queenside(self.side_to_move));
+            } else if mov.source() == kingside_rook(self.side_to_move) {
                 // Move of the kingside rook. Can't castle kingside anymore.
-                self.castle_status &= !kingside_castle_mask(self.side_to_move);
-                zobrist::modify_kingside_castle(&mut self.zobrist_hash, self.side_to_move);
+                self.revoke_castle_rights(CastleStatus::kingside(self.side_to_move));
             }
         } else if moving_piece.kind == PieceKind::King {
             // Moving a king invalides the castle on both sides of the board.
-            self.castle_status &= !castle_mask(self.side_to_move);
-            zobrist::modify_queenside_castle(&mut self.zobrist_hash, self.side_to_move);
-            zobrist::modify_kingside_castle(&mut self.zobrist_hash, self.side_to_move);
+            self.revoke_castle_rights(CastleStatus::for_color(self.side_to_move));
         }
 
         self.side_to_move = self.side_to_move.toggle();
@@ -468,6 +1174,31 @@ impl Position {
         if self.side_to_move == Color::White {
             self.fullmove_clock += 1;
         }
+
+        #[cfg(feature = "check-zobrist")]
+        debug_assert_eq!(
+            self.zobrist_hash,
+            zobrist::hash_position(self),
+            "incremental hash out of sync after {}",
+            mov
+        );
+    }
+
+    /// Removes the given castle rights. The Zobrist hash only changes for the rights that were actually held, since
+    /// toggling the key for a right that is already gone would add it back to the hash.
+    fn revoke_castle_rights(&mut self, rights: CastleStatus) {
+        let revoked = self.castle_status & rights;
+        for color in colors() {
+            if revoked.contains(CastleStatus::kingside(color)) {
+                zobrist::modify_kingside_castle(&mut self.zobrist_hash, color);
+            }
+
+            if revoked.contains(CastleStatus::queenside(color)) {
+                zobrist::modify_queenside_castle(&mut self.zobrist_hash, color);
+            }
+        }
+
+        self.castle_status &= !rights;
     }
 }
 
@@ -686,25 +1417,38 @@ impl Position {
         eat(iter, ' ')?;
         pos.castle_status = eat_castle_status(iter)?;
         eat(iter, ' ')?;
-        pos.en_passant_square = eat_en_passant(iter)?;
+        // Many FEN writers record the en-passant square after every double pawn push, whether or not a pawn can capture
+        // there. Keep it only if a capture is possible, so that this position hashes the same as the same position
+        // reached without an en-passant square.
+        pos.en_passant_square = eat_en_passant(iter)?
+            .filter(|&ep_square| pos.can_capture_en_passant(ep_square, pos.side_to_move));
         eat(iter, ' ')?;
         pos.halfmove_clock = eat_halfmove(iter)?;
         eat(iter, ' ')?;
         pos.fullmove_clock = eat_fullmove(iter)?;
+        pos.zobrist_hash = zobrist::hash_position(&pos);
         Ok(pos)
     }
 
     pub fn as_fen(&self) -> String {
         let mut buf = String::new();
+        self.write_fen(&mut buf)
+            .expect("writing to a String can't fail");
+        buf
+    }
+
+    /// Writes the FEN representation of this position to the given writer. Unlike `as_fen`, this doesn't allocate, so
+    /// callers that produce many FEN strings can reuse a single buffer.
+    pub fn write_fen(&self, buf: &mut impl Write) -> fmt::Result {
         for rank in core::ranks().rev() {
             let mut empty_squares = 0;
             for file in core::files() {
                 let square = Square::of(rank, file);
                 if let Some(piece) = self.piece_at(square) {
                     if empty_squares != 0 {
-                        write!(&mut buf, "{}", empty_squares).unwrap();
+                        write!(buf, "{}", empty_squares)?;
                     }
-                    write!(&mut buf, "{}", piece).unwrap();
+                    write!(buf, "{}", piece)?;
                     empty_squares = 0;
                 } else {
                     empty_squares += 1;
@@ -712,56 +1456,190 @@ impl Position {
             }
 
             if empty_squares != 0 {
-                write!(&mut buf, "{}", empty_squares).unwrap();
+                write!(buf, "{}", empty_squares)?;
             }
 
             if rank != core::RANK_1 {
-                buf.push('/');
+                buf.write_char('/')?;
             }
         }
 
-        buf.push(' ');
         match self.side_to_move() {
-            Color::White => buf.push('w'),
-            Color::Black => buf.push('b'),
+            Color::White => buf.write_str(" w ")?,
+            Color::Black => buf.write_str(" b ")?,
+        }
+        write!(buf, "{} ", self.castle_status)?;
+        if let Some(ep_square) = self.en_passant_square() {
+            write!(buf, "{}", ep_square)?;
+        } else {
+            buf.write_char('-')?;
+        }
+        write!(buf, " {} {}", self.halfmove_clock(), self.fullmove_clock())
+    }
+}
+
+//
+// Position validation and construction.
+//
+// FEN parsing only checks that a FEN string is well-formed, not that it describes a sensible position. The builder
+// below is for tools that set up positions piece by piece, and validates the position that they end up with.
+//
+
+/// Possible reasons that a position is not a legal chess position.
+#[derive(Clone, PartialEq, Eq, Debug, Error)]
+pub enum PositionError {
+    #[error("{0:?} has no king")]
+    MissingKing(Color),
+    #[error("{0:?} has more than one king")]
+    TooManyKings(Color),
+    #[error("pawn on back rank: {0}")]
+    PawnOnBackRank(Square),
+    #[error("castle rights without a king and rook on their starting squares")]
+    InvalidCastle,
+    #[error("invalid en-passant square: {0}")]
+    InvalidEnPassant(Square),
+    #[error("side not to move is in check")]
+    OpponentInCheck,
+}
+
+impl Position {
+    /// Checks that this position is one that could arise in a game of chess: each side has exactly one king, there are
+    /// no pawns on the back ranks, castle rights and the en-passant square agree with the pieces on the board, and the
+    /// side that just moved didn't leave its king in check.
+    pub fn validate(&self) -> Result<(), PositionError> {
+        for color in colors() {
+            match self.pieces_of_kind(color, PieceKind::King).len() {
+                0 => return Err(PositionError::MissingKing(color)),
+                1 => {}
+                _ => return Err(PositionError::TooManyKings(color)),
+            }
         }
-        buf.push(' ');
-        let mut can_castle_at_least_once = false;
-        if self.can_castle_kingside(Color::White) {
-            buf.push('K');
-            can_castle_at_least_once = true;
+
+        let back_ranks = SquareSet::all().rank(RANK_1) | SquareSet::all().rank(RANK_8);
+        let pawns = self.pawns(Color::White) | self.pawns(Color::Black);
+        if let Some(square) = (pawns & back_ranks).lsb() {
+            return Err(PositionError::PawnOnBackRank(square));
         }
-        if self.can_castle_queenside(Color::White) {
-            buf.push('Q');
-            can_castle_at_least_once = true;
+
+        for color in colors() {
+            let king = Piece {
+                color,
+                kind: PieceKind::King,
+            };
+            let rook = Piece {
+                color,
+                kind: PieceKind::Rook,
+            };
+            let king_in_place = self.piece_at(king_start(color)) == Some(king);
+            if self.can_castle_kingside(color)
+                && !(king_in_place && self.piece_at(kingside_rook(color)) == Some(rook))
+            {
+                return Err(PositionError::InvalidCastle);
+            }
+
+            if self.can_castle_queenside(color)
+                && !(king_in_place && self.piece_at(queenside_rook(color)) == Some(rook))
+            {
+                return Err(PositionError::InvalidCastle);
+            }
         }
-        if self.can_castle_kingside(Color::Black) {
-            buf.push('k');
-            can_castle_at_least_once = true;
+
+        if let Some(ep_square) = self.en_passant_square {
+            // The side that just moved pushed a pawn past the en-passant square, which must be empty.
+            let ep_rank = self.side_to_move.en_passant_rank();
+            let up = self.side_to_move.backward();
+            let pushed_pawn = Piece {
+                color: self.side_to_move.toggle(),
+                kind: PieceKind::Pawn,
+            };
+            if ep_square.rank() != ep_rank
+                || self.piece_at(ep_square).is_some()
+                || ep_square
+                    .checked_towards(up)
+                    .and_then(|sq| self.piece_at(sq))
+                    != Some(pushed_pawn)
+            {
+                return Err(PositionError::InvalidEnPassant(ep_square));
+            }
         }
-        if self.can_castle_queenside(Color::Black) {
-            buf.push('q');
-            can_castle_at_least_once = true;
+
+        if self.is_check(self.side_to_move.toggle()) {
+            return Err(PositionError::OpponentInCheck);
         }
-        if !can_castle_at_least_once {
-            buf.push('-');
+
+        Ok(())
+    }
+}
+
+/// A builder for setting up positions piece by piece. The builder starts from an empty board with White to move, no
+/// castle rights, and the move clocks at the start of the game.
+pub struct PositionBuilder {
+    pos: Position,
+}
+
+impl PositionBuilder {
+    pub fn new() -> PositionBuilder {
+        let mut pos = Position::new();
+        pos.castle_status = CastleStatus::NONE;
+        pos.fullmove_clock = 1;
+        PositionBuilder { pos }
+    }
+
+    /// Creates a builder with the pieces on the given board, indexed by square (see [`Position::to_array`]). Everything
+    /// else starts out as it does for [`PositionBuilder::new`].
+    pub fn from_array(board: &[Option<Piece>; 64]) -> PositionBuilder {
+        let mut builder = PositionBuilder::new();
+        for square in core::squares() {
+            builder
+                .pos
+                .set_piece(square, board[square.as_u8() as usize]);
         }
 
-        buf.push(' ');
-        if let Some(ep_square) = self.en_passant_square() {
-            write!(&mut buf, "{}", ep_square).unwrap();
-        } else {
-            buf.push('-');
-        }
-        buf.push(' ');
-        write!(
-            &mut buf,
-            "{} {}",
-            self.halfmove_clock(),
-            self.fullmove_clock()
-        )
-        .unwrap();
-        buf
+        builder
+    }
+
+    /// Places a piece on a square, replacing whatever was there.
+    pub fn with_piece(mut self, square: Square, piece: Piece) -> PositionBuilder {
+        self.pos.set_piece(square, Some(piece));
+        self
+    }
+
+    pub fn side_to_move(mut self, color: Color) -> PositionBuilder {
+        self.pos.side_to_move = color;
+        self
+    }
+
+    pub fn castling(mut self, status: CastleStatus) -> PositionBuilder {
+        self.pos.castle_status = status;
+        self
+    }
+
+    pub fn en_passant(mut self, square: Square) -> PositionBuilder {
+        self.pos.en_passant_square = Some(square);
+        self
+    }
+
+    pub fn halfmove(mut self, halfmove_clock: u16) -> PositionBuilder {
+        self.pos.halfmove_clock = halfmove_clock;
+        self
+    }
+
+    pub fn fullmove(mut self, fullmove_clock: u16) -> PositionBuilder {
+        self.pos.fullmove_clock = fullmove_clock;
+        self
+    }
+
+    /// Validates the position and returns it. See [`Position::validate`] for what makes a position valid.
+    pub fn build(mut self) -> Result<Position, PositionError> {
+        self.pos.validate()?;
+        self.pos.zobrist_hash = zobrist::hash_position(&self.pos);
+        Ok(self.pos)
+    }
+}
+
+impl Default for PositionBuilder {
+    fn default() -> Self {
+        PositionBuilder::new()
     }
 }
 
@@ -794,6 +1672,14 @@ impl fmt::Display for Position {
     }
 }
 
+impl FromStr for Position {
+    type Err = FenParseError;
+
+    fn from_str(s: &str) -> Result<Position, FenParseError> {
+        Position::from_fen(s)
+    }
+}
+
 impl Default for Position {
     fn default() -> Self {
         Position::new()
@@ -809,7 +1695,32 @@ impl Hash for Position {
     }
 }
 
-#[allow(dead_code)]
+impl Position {
+    /// Returns whether or not two positions are the same for the purposes of repetition: the same pieces on the same
+    /// squares, with the same side to move, castle rights, and en-passant square. Unlike `==`, the clocks aren't
+    /// compared.
+    pub fn is_same_position(&self, other: &Position) -> bool {
+        self.sets_by_piece == other.sets_by_piece
+            && self.sets_by_color == other.sets_by_color
+            && self.side_to_move == other.side_to_move
+            && self.castle_status == other.castle_status
+            && self.en_passant_square == other.en_passant_square
+    }
+}
+
+/// Positions are equal if their boards and clocks are. The move history isn't compared, so positions reached by
+/// different move orders can be equal. This doesn't rely on the Zobrist hash, so positions whose hashes collide
+/// aren't equal.
+impl PartialEq for Position {
+    fn eq(&self, other: &Position) -> bool {
+        self.is_same_position(other)
+            && self.halfmove_clock == other.halfmove_clock
+            && self.fullmove_clock == other.fullmove_clock
+    }
+}
+
+impl Eq for Position {}
+
 fn king_start(color: Color) -> Square {
     match color {
         Color::White => E1,
@@ -824,13 +1735,6 @@ fn kingside_rook(color: Color) -> Square {
     }
 }
 
-fn kingside_castle_mask(color: Color) -> CastleStatus {
-    match color {
-        Color::White => CastleStatus::WHITE_KINGSIDE,
-        Color::Black => CastleStatus::BLACK_KINGSIDE,
-    }
-}
-
 fn queenside_rook(color: Color) -> Square {
     match color {
         Color::White => A1,
@@ -838,20 +1742,6 @@ fn queenside_rook(color: Color) -> Square {
     }
 }
 
-fn queenside_castle_mask(color: Color) -> CastleStatus {
-    match color {
-        Color::White => CastleStatus::WHITE_QUEENSIDE,
-        Color::Black => CastleStatus::BLACK_QUEENSIDE,
-    }
-}
-
-fn castle_mask(color: Color) -> CastleStatus {
-    match color {
-        Color::White => CastleStatus::WHITE,
-        Color::Black => CastleStatus::BLACK,
-    }
-}
-
 #[cfg(test)]
 mod tests {
     mod fen {
@@ -862,6 +1752,49 @@ mod tests {
             position::{FenParseError, Position},
         };
 
+        #[test]
+        fn write_fen_reuses_buffer() {
+            let mut buf = String::new();
+            for fen in [
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 b - - 5 40",
+            ] {
+                let pos = Position::from_fen(fen).unwrap();
+                buf.clear();
+                pos.write_fen(&mut buf).unwrap();
+                assert_eq!(fen, buf);
+                assert_eq!(pos.as_fen(), buf);
+            }
+        }
+
+        #[test]
+        fn parse_start_position() {
+            let pos: Position = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+                .parse()
+                .unwrap();
+            assert_eq!(Position::from_start_position().as_fen(), pos.as_fen());
+            assert!("not a fen".parse::<Position>().is_err());
+        }
+
+        #[test]
+        fn uncapturable_en_passant_square_is_dropped() {
+            let with_ep =
+                Position::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1")
+                    .unwrap();
+            let without_ep =
+                Position::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1")
+                    .unwrap();
+            assert_eq!(None, with_ep.en_passant_square());
+            assert_eq!(without_ep.zobrist_hash(), with_ep.zobrist_hash());
+        }
+
+        #[test]
+        fn capturable_en_passant_square_is_kept() {
+            let pos = Position::from_fen("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1").unwrap();
+            assert_eq!(Some(E3), pos.en_passant_square());
+        }
+
         #[test]
         fn fen_smoke() {
             let pos = Position::from_fen("8/8/8/8/8/8/8/8 w - - 0 0").unwrap();
@@ -1146,113 +2079,761 @@ mod tests {
             assert!(pos.can_castle_queenside(Color::Black));
         }
 
-        #[test]
-        fn empty() {
-            let err = Position::from_fen("").unwrap_err();
-            assert_eq!(FenParseError::UnexpectedEnd, err);
+        #[test]
+        fn empty() {
+            let err = Position::from_fen("").unwrap_err();
+            assert_eq!(FenParseError::UnexpectedEnd, err);
+        }
+
+        #[test]
+        fn unknown_piece() {
+            let err = Position::from_fen("z7/8/8/8/8/8/8/8 w - - 0 0").unwrap_err();
+            assert_eq!(FenParseError::UnknownPiece('z'), err);
+        }
+
+        #[test]
+        fn invalid_digit() {
+            let err = Position::from_fen("9/8/8/8/8/8/8/8 w - - 0 0").unwrap_err();
+            assert_eq!(FenParseError::InvalidDigit, err);
+        }
+
+        #[test]
+        fn not_sum_to_8() {
+            let err = Position::from_fen("pppp5/8/8/8/8/8/8/8 w - - 0 0").unwrap_err();
+            assert_eq!(FenParseError::FileDoesNotSumToEight, err);
+        }
+
+        #[test]
+        fn bad_side_to_move() {
+            let err = Position::from_fen("8/8/8/8/8/8/8/8 c - - 0 0").unwrap_err();
+            assert_eq!(FenParseError::InvalidSideToMove, err);
+        }
+
+        #[test]
+        fn bad_castle_status() {
+            let err = Position::from_fen("8/8/8/8/8/8/8/8 w a - 0 0").unwrap_err();
+            assert_eq!(FenParseError::InvalidCastle, err);
+        }
+
+        #[test]
+        fn bad_en_passant() {
+            let err = Position::from_fen("8/8/8/8/8/8/8/8 w - 88 0 0").unwrap_err();
+            assert_eq!(FenParseError::InvalidEnPassant, err);
+        }
+
+        #[test]
+        fn empty_halfmove() {
+            let err = Position::from_fen("8/8/8/8/8/8/8/8 w - - q 0").unwrap_err();
+            assert_eq!(FenParseError::EmptyHalfmove, err);
+        }
+
+        #[test]
+        fn invalid_halfmove() {
+            let err = Position::from_fen("8/8/8/8/8/8/8/8 w - - 4294967296 0").unwrap_err();
+            assert_eq!(FenParseError::InvalidHalfmove, err);
+        }
+
+        #[test]
+        fn empty_fullmove() {
+            let err = Position::from_fen("8/8/8/8/8/8/8/8 w - - 0 q").unwrap_err();
+            assert_eq!(FenParseError::EmptyFullmove, err);
+        }
+
+        #[test]
+        fn fullmove_early_end() {
+            let err = Position::from_fen("8/8/8/8/8/8/8/8 w - - 0").unwrap_err();
+            assert_eq!(FenParseError::UnexpectedEnd, err);
+        }
+
+        #[test]
+        fn invalid_fullmove() {
+            let err = Position::from_fen("8/8/8/8/8/8/8/8 w - - 0 4294967296").unwrap_err();
+            assert_eq!(FenParseError::InvalidFullmove, err);
+        }
+
+        #[test]
+        fn start_position_roundtrip() {
+            let str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+            let pos = Position::from_fen(str).unwrap();
+            assert_eq!(pos.as_fen(), str);
+        }
+
+        #[test]
+        fn no_castle_rights_produces_dash() {
+            let fen = "1r6/1p6/1Pp1pNbk/2P1Pr1p/3P1pP1/3R4/6PP/4R1K1 b - - 0 36";
+            let pos = Position::from_fen(fen).unwrap();
+            assert_eq!(pos.as_fen(), fen);
+        }
+    }
+
+    mod legality {
+        use crate::{core::*, position::Position};
+
+        #[test]
+        fn king_pawn_check() {
+            let pos = Position::from_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1").unwrap();
+            let mov = Move::quiet(A5, B6);
+            assert!(!pos.is_legal_given_pseudolegal(mov));
+        }
+
+        #[test]
+        fn rook_pin() {
+            let pos = Position::from_fen("8/8/4r3/8/8/4B3/4K3/8 b - - 0 1").unwrap();
+            let mov = Move::capture(E6, E3);
+            assert!(pos.is_legal_given_pseudolegal(mov));
+        }
+
+        #[test]
+        fn start_position_is_quiet() {
+            assert!(Position::from_start_position().is_quiet());
+        }
+
+        #[test]
+        fn hanging_queen_is_not_quiet() {
+            let pos = Position::from_fen("4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1").unwrap();
+            assert!(!pos.is_quiet());
+        }
+
+        #[test]
+        fn check_is_not_quiet() {
+            let pos = Position::from_fen("4k3/8/8/8/8/8/8/R3K2r w - - 0 1").unwrap();
+            assert!(!pos.is_quiet());
+        }
+
+        #[test]
+        fn even_trade_is_quiet() {
+            let pos = Position::from_fen("8/8/4k3/3q4/8/8/3Q4/4K3 w - - 0 1").unwrap();
+            assert!(pos.is_quiet());
+        }
+    }
+
+    mod pseudolegal {
+        use crate::{core::*, movegen, position::Position};
+
+        const FENS: [&str; 7] = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/Pp2P3/2N2Q1p/1PPBBPPP/R3K2R b KQkq a3 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+        ];
+
+        #[test]
+        fn agrees_with_move_generation() {
+            // Moves generated for either side in any of the positions are a good source of moves that are almost, but
+            // not quite, pseudolegal in the others.
+            let positions: Vec<_> = FENS
+                .iter()
+                .map(|fen| Position::from_fen(fen).unwrap())
+                .collect();
+            let mut candidates = vec![];
+            for pos in &positions {
+                for color in colors() {
+                    movegen::generate_moves(color, pos, &mut candidates);
+                }
+            }
+
+            for pos in &positions {
+                let mut generated = vec![];
+                movegen::generate_moves(pos.side_to_move(), pos, &mut generated);
+                for &mov in &candidates {
+                    assert_eq!(
+                        generated.contains(&mov),
+                        pos.is_pseudolegal(mov),
+                        "{} in {}",
+                        mov,
+                        pos.as_fen()
+                    );
+                }
+            }
+        }
+
+        #[test]
+        fn is_legal_agrees_with_move_generation() {
+            let positions: Vec<_> = FENS
+                .iter()
+                .map(|fen| Position::from_fen(fen).unwrap())
+                .collect();
+            let mut candidates = vec![];
+            for pos in &positions {
+                for color in colors() {
+                    movegen::generate_moves(color, pos, &mut candidates);
+                }
+            }
+
+            for pos in &positions {
+                let mut legal = vec![];
+                movegen::generate_moves(pos.side_to_move(), pos, &mut legal);
+                legal.retain(|&mov| pos.is_legal_given_pseudolegal(mov));
+                for &mov in &candidates {
+                    assert_eq!(
+                        legal.contains(&mov),
+                        pos.is_legal(mov),
+                        "{} in {}",
+                        mov,
+                        pos.as_fen()
+                    );
+                }
+            }
+        }
+
+        #[test]
+        fn garbage_is_rejected() {
+            let pos = Position::from_start_position();
+            assert!(pos.is_pseudolegal(Move::double_pawn_push(E2, E4)));
+            assert!(pos.is_pseudolegal(Move::quiet(G1, F3)));
+            assert!(!pos.is_pseudolegal(Move::null()));
+            assert!(!pos.is_pseudolegal(Move::quiet(E2, E4)));
+            assert!(!pos.is_pseudolegal(Move::double_pawn_push(E7, E5)));
+            assert!(!pos.is_pseudolegal(Move::capture(D1, D7)));
+            assert!(!pos.is_pseudolegal(Move::quiet(A1, A3)));
+            assert!(!pos.is_pseudolegal(Move::quiet(E4, E5)));
+            assert!(!pos.is_pseudolegal(Move::en_passant(E2, D3)));
+            assert!(!pos.is_pseudolegal(Move::kingside_castle(E1, G1)));
+        }
+    }
+
+    mod gives_check {
+        use crate::{movegen, position::Position};
+
+        #[test]
+        fn gives_check_agrees_with_making_the_move() {
+            let fens = [
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+                "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+                // Castling kingside puts the rook on f1, in line with the king on f8.
+                "5k2/8/8/8/8/8/8/4K2R w K - 0 1",
+                // Capturing en-passant uncovers the rook on a5.
+                "8/8/8/R2pP2k/8/8/8/4K3 w - d6 0 1",
+                // Promoting to a knight checks the king on c7, while promoting to a queen doesn't.
+                "8/P1k5/8/8/8/8/8/4K3 w - - 0 1",
+                // Any move of the knight uncovers the rook on e1.
+                "4k3/8/8/8/4N3/8/8/4R2K w - - 0 1",
+            ];
+
+            let mut checks = 0;
+            for fen in fens {
+                let pos = Position::from_fen(fen).unwrap();
+                let mut moves = vec![];
+                movegen::generate_moves(pos.side_to_move(), &pos, &mut moves);
+                for mov in moves.into_iter().filter(|&m| pos.is_legal(m)) {
+                    let expected = pos
+                        .clone_and_make_move(mov)
+                        .is_check(pos.side_to_move().toggle());
+                    assert_eq!(expected, pos.gives_check(mov), "{}: {}", fen, mov);
+                    checks += expected as usize;
+                }
+            }
+
+            assert!(checks > 10);
+        }
+    }
+
+    mod checkers {
+        use crate::{core::*, position::Position};
+
+        #[test]
+        fn rook_check() {
+            let pos = Position::from_fen("4k3/8/8/8/4R3/8/8/3QK3 b - - 0 1").unwrap();
+            let mut expected = SquareSet::empty();
+            expected.insert(E4);
+            assert_eq!(expected, pos.checkers(Color::Black));
+            assert!(pos.checkers(Color::White).is_empty());
+        }
+
+        #[test]
+        fn double_check() {
+            let pos = Position::from_fen("4k3/8/3N4/8/4R3/8/8/4K3 b - - 0 1").unwrap();
+            assert_eq!(2, pos.checkers(Color::Black).len());
+        }
+    }
+
+    mod pins {
+        use crate::{core::*, position::Position};
+
+        #[test]
+        fn pinned_bishop_stays_on_the_pin_ray() {
+            let pos = Position::from_fen("4k3/8/8/8/1q6/8/3B4/4K3 w - - 0 1").unwrap();
+            let mut expected = SquareSet::empty();
+            expected.insert(D2);
+            assert_eq!(expected, pos.pinned_pieces(Color::White));
+
+            let mut ray = expected;
+            ray.insert(C3);
+            ray.insert(B4);
+            assert_eq!(ray, pos.pin_ray(D2));
+
+            assert!(pos.is_legal(Move::capture(D2, B4)));
+            assert!(pos.is_legal(Move::quiet(D2, C3)));
+            assert!(!pos.is_legal(Move::quiet(D2, E3)));
+            assert!(!pos.is_legal(Move::quiet(D2, C1)));
+        }
+
+        #[test]
+        fn pinned_rook_may_capture_the_pinner() {
+            let pos = Position::from_fen("4k3/4r3/8/8/8/4R3/4K3/8 w - - 0 1").unwrap();
+            assert!(pos.pinned_pieces(Color::White).contains(E3));
+            assert!(pos.is_legal(Move::capture(E3, E7)));
+            assert!(pos.is_legal(Move::quiet(E3, E5)));
+            assert!(!pos.is_legal(Move::quiet(E3, D3)));
+        }
+
+        #[test]
+        fn two_blockers_are_not_pinned() {
+            let pos = Position::from_fen("4k3/4r3/8/4P3/8/4R3/4K3/8 w - - 0 1").unwrap();
+            assert!(pos.pinned_pieces(Color::White).is_empty());
+            assert_eq!(SquareSet::all(), pos.pin_ray(E3));
+            assert!(pos.is_legal(Move::quiet(E3, D3)));
+        }
+    }
+
+    mod symmetry {
+        use crate::{core::*, position::Position};
+
+        #[test]
+        fn flip_horizontal_mirrors_files() {
+            let pos = Position::from_fen("4k3/8/8/3pP3/8/8/8/R3K3 w - d6 0 1").unwrap();
+            let flipped = pos.flip_horizontal();
+            let expected = Position::from_fen("3k4/8/8/3Pp3/8/8/8/3K3R w - e6 0 1").unwrap();
+            assert_eq!(expected.as_fen(), flipped.as_fen());
+            assert_eq!(expected.zobrist_hash(), flipped.zobrist_hash());
+        }
+
+        #[test]
+        fn flip_horizontal_twice_is_identity() {
+            let pos = Position::from_fen("4k3/8/8/3pP3/8/8/8/R3K3 w - d6 0 1").unwrap();
+            let flipped_twice = pos.flip_horizontal().flip_horizontal();
+            assert_eq!(pos.as_fen(), flipped_twice.as_fen());
+            assert_eq!(pos.zobrist_hash(), flipped_twice.zobrist_hash());
+        }
+
+        #[test]
+        fn flip_horizontal_clears_castling() {
+            let flipped = Position::from_start_position().flip_horizontal();
+            assert_eq!(
+                "rnbkqbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBKQBNR w - - 0 1",
+                flipped.as_fen()
+            );
+        }
+
+        #[test]
+        fn rotate_180_swaps_sides() {
+            let rotated = Position::from_start_position().rotate_180();
+            assert_eq!(
+                "rnbkqbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBKQBNR b - - 0 1",
+                rotated.as_fen()
+            );
+
+            let pos = Position::from_fen("4k3/8/8/3pP3/8/8/8/R3K3 w - d6 0 1").unwrap();
+            let rotated = pos.rotate_180();
+            assert_eq!(Color::Black, rotated.side_to_move());
+            assert_eq!(Some(E3), rotated.en_passant_square());
+            assert_eq!(pos.as_fen(), rotated.rotate_180().as_fen());
+            assert_eq!(pos.zobrist_hash(), rotated.rotate_180().zobrist_hash());
+        }
+    }
+
+    mod builder {
+        use crate::{
+            core::*,
+            position::{Position, PositionBuilder, PositionError},
+        };
+
+        fn piece(color: Color, kind: PieceKind) -> Piece {
+            Piece { color, kind }
+        }
+
+        #[test]
+        fn array_round_trip() {
+            let start = Position::from_start_position();
+            let board = start.to_array();
+            assert_eq!(Some(piece(Color::White, PieceKind::Rook)), board[0]);
+            assert_eq!(Some(piece(Color::Black, PieceKind::King)), board[60]);
+            assert_eq!(None, board[E4.as_u8() as usize]);
+
+            let pos = PositionBuilder::from_array(&board)
+                .castling(CastleStatus::all())
+                .build()
+                .unwrap();
+            assert_eq!(start.as_fen(), pos.as_fen());
+        }
+
+        #[test]
+        fn build_start_position() {
+            let back_rank = [
+                PieceKind::Rook,
+                PieceKind::Knight,
+                PieceKind::Bishop,
+                PieceKind::Queen,
+                PieceKind::King,
+                PieceKind::Bishop,
+                PieceKind::Knight,
+                PieceKind::Rook,
+            ];
+
+            let mut builder = PositionBuilder::new();
+            for (file, kind) in files().zip(back_rank) {
+                builder = builder
+                    .with_piece(Square::of(RANK_1, file), piece(Color::White, kind))
+                    .with_piece(
+                        Square::of(RANK_2, file),
+                        piece(Color::White, PieceKind::Pawn),
+                    )
+                    .with_piece(
+                        Square::of(RANK_7, file),
+                        piece(Color::Black, PieceKind::Pawn),
+                    )
+                    .with_piece(Square::of(RANK_8, file), piece(Color::Black, kind));
+            }
+
+            let pos = builder
+                .side_to_move(Color::White)
+                .castling(CastleStatus::all())
+                .halfmove(0)
+                .fullmove(1)
+                .build()
+                .unwrap();
+            assert_eq!(
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                pos.as_fen()
+            );
         }
 
         #[test]
-        fn unknown_piece() {
-            let err = Position::from_fen("z7/8/8/8/8/8/8/8 w - - 0 0").unwrap_err();
-            assert_eq!(FenParseError::UnknownPiece('z'), err);
+        fn build_en_passant() {
+            let pos = PositionBuilder::new()
+                .with_piece(E1, piece(Color::White, PieceKind::King))
+                .with_piece(E8, piece(Color::Black, PieceKind::King))
+                .with_piece(D4, piece(Color::White, PieceKind::Pawn))
+                .side_to_move(Color::Black)
+                .en_passant(D3)
+                .build()
+                .unwrap();
+            assert_eq!("4k3/8/8/8/3P4/8/8/4K3 b - d3 0 1", pos.as_fen());
         }
 
         #[test]
-        fn invalid_digit() {
-            let err = Position::from_fen("9/8/8/8/8/8/8/8 w - - 0 0").unwrap_err();
-            assert_eq!(FenParseError::InvalidDigit, err);
+        fn build_rejects_invalid_positions() {
+            let kings = PositionBuilder::new()
+                .with_piece(E1, piece(Color::White, PieceKind::King))
+                .with_piece(E8, piece(Color::Black, PieceKind::King));
+
+            assert_eq!(
+                Err(PositionError::MissingKing(Color::White)),
+                PositionBuilder::new()
+                    .with_piece(E8, piece(Color::Black, PieceKind::King))
+                    .build()
+                    .map(|_| ())
+            );
+            assert_eq!(
+                Err(PositionError::InvalidCastle),
+                PositionBuilder::new()
+                    .with_piece(E1, piece(Color::White, PieceKind::King))
+                    .with_piece(E8, piece(Color::Black, PieceKind::King))
+                    .castling(CastleStatus::WHITE_KINGSIDE)
+                    .build()
+                    .map(|_| ())
+            );
+            assert_eq!(
+                Err(PositionError::OpponentInCheck),
+                kings
+                    .with_piece(E4, piece(Color::White, PieceKind::Rook))
+                    .build()
+                    .map(|_| ())
+            );
         }
+    }
+
+    mod edit {
+        use crate::{core::*, position::Position};
 
         #[test]
-        fn not_sum_to_8() {
-            let err = Position::from_fen("pppp5/8/8/8/8/8/8/8 w - - 0 0").unwrap_err();
-            assert_eq!(FenParseError::FileDoesNotSumToEight, err);
+        fn set_piece_replaces_existing_piece() {
+            let mut pos = Position::from_start_position();
+            let knight = Piece {
+                color: Color::Black,
+                kind: PieceKind::Knight,
+            };
+            pos.set_piece(D1, Some(knight));
+            assert_eq!(Some(knight), pos.piece_at(D1));
+            assert!(!pos.queens(Color::White).contains(D1));
+
+            let expected =
+                Position::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBnKBNR w KQkq - 0 1")
+                    .unwrap();
+            assert_eq!(expected.as_fen(), pos.as_fen());
+            assert_eq!(expected.zobrist_hash(), pos.zobrist_hash());
         }
 
         #[test]
-        fn bad_side_to_move() {
-            let err = Position::from_fen("8/8/8/8/8/8/8/8 c - - 0 0").unwrap_err();
-            assert_eq!(FenParseError::InvalidSideToMove, err);
+        fn set_piece_clears_square() {
+            let mut pos = Position::from_start_position();
+            pos.set_piece(E2, None);
+            pos.set_piece(E4, None);
+            assert_eq!(None, pos.piece_at(E2));
+
+            let expected =
+                Position::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPP1PPP/RNBQKBNR w KQkq - 0 1")
+                    .unwrap();
+            assert_eq!(expected.zobrist_hash(), pos.zobrist_hash());
         }
 
         #[test]
-        fn bad_castle_status() {
-            let err = Position::from_fen("8/8/8/8/8/8/8/8 w a - 0 0").unwrap_err();
-            assert_eq!(FenParseError::InvalidCastle, err);
+        fn set_clocks() {
+            let mut pos = Position::from_start_position();
+            pos.set_halfmove_clock(17);
+            pos.set_fullmove_clock(42);
+            assert_eq!(17, pos.halfmove_clock());
+            assert_eq!(42, pos.fullmove_clock());
+
+            let fen = pos.as_fen();
+            assert_eq!(
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 17 42",
+                fen
+            );
+            let round_tripped = Position::from_fen(fen).unwrap();
+            assert_eq!(17, round_tripped.halfmove_clock());
+            assert_eq!(42, round_tripped.fullmove_clock());
         }
 
         #[test]
-        fn bad_en_passant() {
-            let err = Position::from_fen("8/8/8/8/8/8/8/8 w - 88 0 0").unwrap_err();
-            assert_eq!(FenParseError::InvalidEnPassant, err);
+        fn reset_clocks() {
+            let mut pos =
+                Position::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 17 42")
+                    .unwrap();
+            pos.reset_clocks();
+            assert_eq!(0, pos.halfmove_clock());
+            assert_eq!(1, pos.fullmove_clock());
         }
+    }
+
+    mod attackers {
+        use crate::{core::*, position::Position};
 
         #[test]
-        fn empty_halfmove() {
-            let err = Position::from_fen("8/8/8/8/8/8/8/8 w - - q 0").unwrap_err();
-            assert_eq!(FenParseError::EmptyHalfmove, err);
+        fn is_attacked_agrees_with_squares_attacking() {
+            let fens = [
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+                "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+                "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            ];
+
+            for fen in fens {
+                let pos = Position::from_fen(fen).unwrap();
+                for square in squares() {
+                    for color in colors() {
+                        assert_eq!(
+                            !pos.squares_attacking(color, square).is_empty(),
+                            pos.is_attacked(square, color),
+                            "{}: {} attacked by {:?}",
+                            fen,
+                            square,
+                            color
+                        );
+                    }
+                }
+            }
         }
 
         #[test]
-        fn invalid_halfmove() {
-            let err = Position::from_fen("8/8/8/8/8/8/8/8 w - - 4294967296 0").unwrap_err();
-            assert_eq!(FenParseError::InvalidHalfmove, err);
+        fn attackers_to_sees_both_colors() {
+            let pos = Position::from_fen("4k3/8/8/3p4/4P3/5N2/8/4K3 w - - 0 1").unwrap();
+            let occupancy = pos.pieces(Color::White) | pos.pieces(Color::Black);
+            let attackers = pos.attackers_to(D5, occupancy);
+            assert!(attackers.contains(E4));
+            assert_eq!(1, attackers.len());
+
+            let attackers = pos.attackers_to(E4, occupancy);
+            assert!(attackers.contains(D5));
+            assert_eq!(1, attackers.len());
         }
 
         #[test]
-        fn empty_fullmove() {
-            let err = Position::from_fen("8/8/8/8/8/8/8/8 w - - 0 q").unwrap_err();
-            assert_eq!(FenParseError::EmptyFullmove, err);
+        fn rook_attacks_file_up_to_blockers() {
+            let pos = Position::from_fen("4k3/8/3p4/8/8/8/8/3RK3 w - - 0 1").unwrap();
+            let attacks = pos.attacks_from(D1);
+            for square in [D2, D3, D4, D5, D6, C1, B1, A1, E1] {
+                assert!(attacks.contains(square));
+            }
+
+            assert!(!attacks.contains(D7));
+            assert_eq!(9, attacks.len());
         }
 
         #[test]
-        fn fullmove_early_end() {
-            let err = Position::from_fen("8/8/8/8/8/8/8/8 w - - 0").unwrap_err();
-            assert_eq!(FenParseError::UnexpectedEnd, err);
+        fn empty_square_attacks_nothing() {
+            let pos = Position::from_start_position();
+            assert!(pos.attacks_from(E4).is_empty());
         }
 
         #[test]
-        fn invalid_fullmove() {
-            let err = Position::from_fen("8/8/8/8/8/8/8/8 w - - 0 4294967296").unwrap_err();
-            assert_eq!(FenParseError::InvalidFullmove, err);
+        fn removing_front_attacker_reveals_xray() {
+            // The queen on d1 is behind the rook on d2, both aimed at d7.
+            let pos = Position::from_fen("4k3/3p4/8/8/8/8/3R4/3QK3 w - - 0 1").unwrap();
+            let mut occupancy = pos.pieces(Color::White) | pos.pieces(Color::Black);
+            let attackers = pos.attackers_to(D7, occupancy);
+            assert!(attackers.contains(D2));
+            assert!(!attackers.contains(D1));
+
+            occupancy.remove(D2);
+            let attackers = pos.attackers_to(D7, occupancy);
+            assert!(!attackers.contains(D2));
+            assert!(attackers.contains(D1));
         }
+    }
+
+    mod see {
+        use crate::{core::*, movegen, position::Position};
 
         #[test]
-        fn start_position_roundtrip() {
-            let str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
-            let pos = Position::from_fen(str).unwrap();
-            assert_eq!(pos.as_fen(), str);
+        fn see_ge_agrees_with_see() {
+            let fens = [
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1",
+                "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+                "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+                "1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1",
+                "1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1",
+                "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1",
+                "3r2k1/3r4/8/3p4/8/8/3R4/3RK3 w - - 0 1",
+            ];
+
+            let mut captures = 0;
+            for fen in fens {
+                let pos = Position::from_fen(fen).unwrap();
+                let mut moves = vec![];
+                movegen::generate_moves(pos.side_to_move(), &pos, &mut moves);
+                for mov in moves.into_iter().filter(|m| m.is_capture()) {
+                    captures += 1;
+                    let see = pos.see(mov);
+                    for threshold in -12..=12 {
+                        assert_eq!(
+                            see >= threshold,
+                            pos.see_ge(mov, threshold),
+                            "{}: see({}) = {}, threshold {}",
+                            fen,
+                            mov,
+                            see,
+                            threshold
+                        );
+                    }
+                }
+            }
+
+            assert!(captures > 20);
         }
 
         #[test]
-        fn no_castle_rights_produces_dash() {
-            let fen = "1r6/1p6/1Pp1pNbk/2P1Pr1p/3P1pP1/3R4/6PP/4R1K1 b - - 0 36";
-            let pos = Position::from_fen(fen).unwrap();
-            assert_eq!(pos.as_fen(), fen);
+        fn see_ge_sees_xrays() {
+            // Rxd5 wins a pawn, since the rook on d1 is behind the rook on d2 and can recapture after Rxd5.
+            let pos = Position::from_fen("3r2k1/8/8/3p4/8/8/3R4/3RK3 w - - 0 1").unwrap();
+            let mov = Move::capture(D2, D5);
+            assert!(pos.see_ge(mov, 1));
+            assert!(!pos.see_ge(mov, 2));
         }
     }
 
-    mod legality {
+    mod make {
         use crate::{core::*, position::Position};
 
+        fn recompute_material(pos: &Position, color: Color) -> i32 {
+            [
+                PieceKind::Pawn,
+                PieceKind::Knight,
+                PieceKind::Bishop,
+                PieceKind::Rook,
+                PieceKind::Queen,
+            ]
+            .into_iter()
+            .map(|kind| pos.pieces_of_kind(color, kind).len() as i32 * kind.value())
+            .sum()
+        }
+
         #[test]
-        fn king_pawn_check() {
-            let pos = Position::from_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1").unwrap();
-            let mov = Move::quiet(A5, B6);
-            assert!(!pos.is_legal_given_pseudolegal(mov));
+        fn material_balance() {
+            let mut pos = Position::from_start_position();
+            assert_eq!(0, pos.material_balance());
+            pos.remove_piece(D1).unwrap();
+            assert_eq!(-900, pos.material_balance());
+            pos.remove_piece(A7).unwrap();
+            assert_eq!(-800, pos.material_balance());
         }
 
         #[test]
-        fn rook_pin() {
-            let pos = Position::from_fen("8/8/4r3/8/8/4B3/4K3/8 b - - 0 1").unwrap();
-            let mov = Move::capture(E6, E3);
-            assert!(pos.is_legal_given_pseudolegal(mov));
+        fn piece_counts() {
+            let pos = Position::from_start_position();
+            assert_eq!(32, pos.piece_count());
+            for color in colors() {
+                assert_eq!(8, pos.piece_count_of(color, PieceKind::Pawn));
+                assert_eq!(2, pos.piece_count_of(color, PieceKind::Knight));
+                assert_eq!(1, pos.piece_count_of(color, PieceKind::Queen));
+                assert_eq!(3100, pos.non_pawn_material(color));
+            }
+
+            let pos = Position::from_fen("4k3/4p3/8/8/8/8/8/1B2K1R1 w - - 0 1").unwrap();
+            assert_eq!(5, pos.piece_count());
+            assert_eq!(800, pos.non_pawn_material(Color::White));
+            assert_eq!(0, pos.non_pawn_material(Color::Black));
         }
-    }
 
-    mod make {
-        use crate::{core::*, position::Position};
+        #[test]
+        fn material_is_maintained_incrementally() {
+            let pos = Position::from_start_position();
+            assert_eq!(39, pos.material(Color::White));
+            assert_eq!(39, pos.material(Color::Black));
+
+            // Rook takes queen, then a promotion with capture.
+            let pos = Position::from_fen("r3k2r/1P6/8/3q4/8/8/8/3RK3 w - - 0 1").unwrap();
+            let pos = pos.clone_and_make_move(Move::capture(D1, D5));
+            assert_eq!(
+                recompute_material(&pos, Color::White),
+                pos.material(Color::White)
+            );
+            assert_eq!(
+                recompute_material(&pos, Color::Black),
+                pos.material(Color::Black)
+            );
+            assert_eq!(10, pos.material(Color::Black));
+
+            let pos = pos
+                .clone_and_make_move(Move::quiet(E8, E7))
+                .clone_and_make_move(Move::promotion_capture(B7, A8, PieceKind::Queen));
+            assert_eq!(
+                recompute_material(&pos, Color::White),
+                pos.material(Color::White)
+            );
+            assert_eq!(
+                recompute_material(&pos, Color::Black),
+                pos.material(Color::Black)
+            );
+            assert_eq!(14, pos.material(Color::White));
+        }
+
+        #[test]
+        fn history_records_moves_in_order() {
+            let mut pos = Position::from_start_position();
+            let moves = [
+                Move::double_pawn_push(E2, E4),
+                Move::double_pawn_push(E7, E5),
+                Move::quiet(G1, F3),
+            ];
+            for mov in moves {
+                pos.make_move(mov);
+            }
+
+            assert_eq!(&moves[..], pos.history());
+            assert_eq!(
+                &moves[..2],
+                Position::from_start_position()
+                    .clone_and_make_move(moves[0])
+                    .clone_and_make_move(moves[1])
+                    .history()
+            );
+        }
 
         #[test]
         fn smoke_test_opening_pawn() {
@@ -1298,8 +2879,8 @@ mod tests {
 
         #[test]
         fn double_pawn_push_sets_ep() {
-            // white to move
-            let mut pos = Position::from_fen("8/8/8/8/8/8/4P3/8 w - - 0 1").unwrap();
+            // white to move, with a black pawn that can capture en-passant
+            let mut pos = Position::from_fen("8/8/8/8/3p4/8/4P3/8 w - - 0 1").unwrap();
 
             // white double-pawn pushes
             pos.make_move(Move::double_pawn_push(E2, E4));
@@ -1309,6 +2890,13 @@ mod tests {
             assert_eq!(Some(E3), pos.en_passant_square());
         }
 
+        #[test]
+        fn uncapturable_double_pawn_push_does_not_set_ep() {
+            let mut pos = Position::from_fen("8/8/8/8/8/8/4P3/8 w - - 0 1").unwrap();
+            pos.make_move(Move::double_pawn_push(E2, E4));
+            assert_eq!(None, pos.en_passant_square());
+        }
+
         #[test]
         fn basic_capture() {
             let mut pos = Position::from_fen("8/8/8/8/5p2/4P3/8/8 w - - 2 1").unwrap();
@@ -1479,5 +3067,390 @@ mod tests {
             assert_eq!(Color::White, king.color);
             assert_eq!(PieceKind::King, king.kind);
         }
+
+        #[test]
+        fn castling_advances_halfmove_clock() {
+            let mut pos = Position::from_fen("4k3/8/8/8/8/8/8/R3K2R w KQ - 7 20").unwrap();
+            pos.make_move(Move::kingside_castle(E1, G1));
+            assert_eq!(8, pos.halfmove_clock());
+
+            let mut pos = Position::from_fen("4k3/8/8/8/8/8/8/R3K2R w KQ - 7 20").unwrap();
+            pos.make_move(Move::queenside_castle(E1, C1));
+            assert_eq!(8, pos.halfmove_clock());
+        }
+
+        #[test]
+        fn promotion_resets_halfmove_clock() {
+            let mut pos = Position::from_fen("1n2k3/P7/8/8/8/8/8/4K3 w - - 12 40").unwrap();
+            pos.make_move(Move::promotion(A7, A8, PieceKind::Queen));
+            assert_eq!(0, pos.halfmove_clock());
+
+            let mut pos = Position::from_fen("1n2k3/P7/8/8/8/8/8/4K3 w - - 12 40").unwrap();
+            pos.make_move(Move::promotion_capture(A7, B8, PieceKind::Knight));
+            assert_eq!(0, pos.halfmove_clock());
+        }
+
+        #[test]
+        fn clone_and_make_move_leaves_original() {
+            let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+            let pos = Position::from_fen(fen).unwrap();
+            let hash = pos.zobrist_hash();
+
+            let child = pos.clone_and_make_move(Move::double_pawn_push(E2, E4));
+            assert_eq!(Color::Black, child.side_to_move());
+            assert!(child.piece_at(E4).is_some());
+
+            assert_eq!(fen, pos.as_fen());
+            assert_eq!(hash, pos.zobrist_hash());
+            assert!(pos.piece_at(E4).is_none());
+        }
+    }
+
+    mod null_move {
+        use crate::{core::*, position::Position};
+
+        #[test]
+        fn null_move_round_trip() {
+            let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 3 7";
+            let mut pos = Position::from_fen(fen).unwrap();
+            let hash = pos.zobrist_hash();
+
+            let undo = pos.make_null_move();
+            assert_eq!(Color::White, pos.side_to_move());
+            assert_eq!(8, pos.fullmove_clock());
+            assert_ne!(hash, pos.zobrist_hash());
+
+            pos.unmake_null_move(undo);
+            assert_eq!(fen, pos.as_fen());
+            assert_eq!(hash, pos.zobrist_hash());
+        }
+
+        #[test]
+        fn null_move_clears_and_restores_en_passant() {
+            let mut pos =
+                Position::from_fen("rnbqkbnr/ppp1pppp/8/8/3p4/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1")
+                    .unwrap();
+            pos.make_move(Move::double_pawn_push(E2, E4));
+            let hash = pos.zobrist_hash();
+            assert_eq!(Some(E3), pos.en_passant_square());
+
+            let undo = pos.make_null_move();
+            assert_eq!(None, pos.en_passant_square());
+
+            pos.unmake_null_move(undo);
+            assert_eq!(Some(E3), pos.en_passant_square());
+            assert_eq!(hash, pos.zobrist_hash());
+        }
+    }
+
+    mod zobrist {
+        use crate::{core::*, position::Position, zobrist};
+
+        fn play(pos: &mut Position, moves: &[(Square, Square)]) {
+            for &(source, dest) in moves {
+                pos.make_move(Move::quiet(source, dest));
+            }
+        }
+
+        /// Makes a move and checks that the incrementally-updated hash matches one computed from scratch.
+        fn make_and_check(pos: &mut Position, mov: Move) {
+            pos.make_move(mov);
+            assert_eq!(
+                zobrist::hash_position(pos),
+                pos.zobrist_hash(),
+                "hash out of sync after {} in {}",
+                mov,
+                pos.as_fen()
+            );
+        }
+
+        #[test]
+        fn incremental_hash_matches_recompute() {
+            let mut pos =
+                Position::from_fen("r3k2r/1P4p1/8/3p4/4P3/8/8/R3K2R w KQkq - 0 1").unwrap();
+            assert_eq!(zobrist::hash_position(&pos), pos.zobrist_hash());
+
+            // Capture, double push, en-passant, castle, promotion with capture (of a rook, taking away a castle
+            // right), and a null move.
+            make_and_check(&mut pos, Move::capture(E4, D5));
+            make_and_check(&mut pos, Move::double_pawn_push(G7, G5));
+            make_and_check(&mut pos, Move::quiet(A1, A2));
+            make_and_check(&mut pos, Move::quiet(G5, G4));
+            make_and_check(&mut pos, Move::quiet(D5, D6));
+            make_and_check(&mut pos, Move::kingside_castle(E8, G8));
+            make_and_check(&mut pos, Move::promotion_capture(B7, A8, PieceKind::Queen));
+            make_and_check(&mut pos, Move::null());
+            make_and_check(&mut pos, Move::kingside_castle(E1, G1));
+
+            let mut pos = Position::from_fen("4k3/8/8/8/5p2/8/4P3/4K3 w - - 0 1").unwrap();
+            make_and_check(&mut pos, Move::double_pawn_push(E2, E4));
+            make_and_check(&mut pos, Move::en_passant(F4, E3));
+        }
+
+        #[test]
+        fn uncapturable_double_push_is_not_hashed() {
+            // With no black pawn next to e4, the double push hashes the same as the position where the pawn was
+            // already there.
+            let mut pos = Position::from_start_position();
+            make_and_check(&mut pos, Move::double_pawn_push(E2, E4));
+            let without_ep =
+                Position::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1")
+                    .unwrap();
+            assert_eq!(without_ep.zobrist_hash(), pos.zobrist_hash());
+        }
+
+        #[test]
+        fn castle_rights_lost_different_ways() {
+            let fen = "rn2k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+
+            // White gives up both rights at once by moving the king, and then moves it again after they're gone.
+            let mut king_moves = Position::from_fen(fen).unwrap();
+            play(&mut king_moves, &[(E1, E2), (B8, C6), (E2, E1), (C6, B8)]);
+
+            // White gives up each right separately by moving the rooks.
+            let mut rook_moves = Position::from_fen(fen).unwrap();
+            play(
+                &mut rook_moves,
+                &[
+                    (A1, A2),
+                    (B8, C6),
+                    (A2, A1),
+                    (C6, B8),
+                    (H1, H2),
+                    (B8, C6),
+                    (H2, H1),
+                    (C6, B8),
+                ],
+            );
+
+            // The two positions only differ in their clocks, which aren't hashed.
+            let without_clocks = |pos: &Position| {
+                let fen = pos.as_fen();
+                fen.split(' ').take(4).collect::<Vec<_>>().join(" ")
+            };
+            assert_eq!(without_clocks(&king_moves), without_clocks(&rook_moves));
+            assert_eq!(king_moves.zobrist_hash(), rook_moves.zobrist_hash());
+            assert_ne!(
+                Position::from_fen(fen).unwrap().zobrist_hash(),
+                king_moves.zobrist_hash()
+            );
+        }
+    }
+
+    mod repetition {
+        use crate::{core::*, position::Position};
+
+        fn shuffle_knights(pos: &mut Position, moves: &[(Square, Square)]) {
+            for &(source, dest) in moves {
+                pos.make_move(Move::quiet(source, dest));
+            }
+        }
+
+        #[test]
+        fn repetition_detected_before_it_happens() {
+            // Black can return to the starting position with Ng8. The starting position has only occurred once, so
+            // three-fold repetition detection wouldn't notice anything for another four moves.
+            let mut pos = Position::from_start_position();
+            shuffle_knights(&mut pos, &[(G1, F3), (G8, F6), (F3, G1)]);
+            assert!(!pos.hash_history.contains(&pos.zobrist_hash()));
+            assert!(pos.has_upcoming_repetition(4));
+
+            // At the root, Ng8 only repeats a position once, which isn't yet a draw.
+            assert!(!pos.has_upcoming_repetition(1));
+        }
+
+        #[test]
+        fn repetition_before_root_requires_earlier_repetition() {
+            let mut pos = Position::from_start_position();
+            shuffle_knights(
+                &mut pos,
+                &[
+                    (G1, F3),
+                    (G8, F6),
+                    (F3, G1),
+                    (F6, G8),
+                    (G1, F3),
+                    (G8, F6),
+                    (F3, G1),
+                ],
+            );
+            assert!(pos.has_upcoming_repetition(1));
+        }
+
+        #[test]
+        fn no_repetition_across_irreversible_moves() {
+            let mut pos = Position::from_start_position();
+            shuffle_knights(&mut pos, &[(G1, F3), (G8, F6), (F3, G1)]);
+            pos.make_move(Move::double_pawn_push(E7, E5));
+            shuffle_knights(&mut pos, &[(G1, F3)]);
+            assert!(!pos.has_upcoming_repetition(4));
+        }
+    }
+
+    mod status {
+        use crate::{
+            core::*,
+            position::{GameStatus, Position},
+        };
+
+        fn status(fen: &str) -> GameStatus {
+            Position::from_fen(fen).unwrap().status()
+        }
+
+        #[test]
+        fn terminal_positions() {
+            assert_eq!(
+                GameStatus::Ongoing,
+                Position::from_start_position().status()
+            );
+            assert_eq!(
+                GameStatus::Checkmate,
+                status("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1")
+            );
+            assert_eq!(
+                GameStatus::Stalemate,
+                status("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1")
+            );
+            assert_eq!(
+                GameStatus::FiftyMoveRule,
+                status("4k3/8/8/8/8/8/4P3/4K3 w - - 100 80")
+            );
+        }
+
+        #[test]
+        fn insufficient_material() {
+            assert_eq!(
+                GameStatus::InsufficientMaterial,
+                status("4k3/8/8/8/8/8/8/4K3 w - - 0 1")
+            );
+            assert_eq!(
+                GameStatus::InsufficientMaterial,
+                status("4k3/8/8/8/8/8/8/4KN2 w - - 0 1")
+            );
+            assert_eq!(
+                GameStatus::InsufficientMaterial,
+                status("4kb2/8/8/8/8/8/8/2B1K3 w - - 0 1")
+            );
+            assert_eq!(
+                GameStatus::Ongoing,
+                status("4k1b1/8/8/8/8/8/8/2B1K3 w - - 0 1")
+            );
+            assert_eq!(
+                GameStatus::Ongoing,
+                status("4k3/8/8/8/8/8/8/3NKN2 w - - 0 1")
+            );
+        }
+
+        #[test]
+        fn threefold_repetition() {
+            let mut pos = Position::from_start_position();
+            for _ in 0..2 {
+                for &(source, dest) in &[(G1, F3), (G8, F6), (F3, G1), (F6, G8)] {
+                    assert_eq!(GameStatus::Ongoing, pos.status());
+                    pos.make_move(Move::quiet(source, dest));
+                }
+            }
+
+            assert_eq!(GameStatus::ThreefoldRepetition, pos.status());
+        }
+    }
+
+    mod try_make_move {
+        use crate::{
+            core::*,
+            position::{MoveError, Position},
+        };
+
+        #[test]
+        fn move_from_empty_square() {
+            let mut pos = Position::from_start_position();
+            assert_eq!(
+                Err(MoveError::NoPieceAtSource(E4)),
+                pos.try_make_move(Move::quiet(E4, E5))
+            );
+            assert_eq!(Position::from_start_position().as_fen(), pos.as_fen());
+        }
+
+        #[test]
+        fn move_of_opponent_piece() {
+            let mut pos = Position::from_start_position();
+            assert_eq!(
+                Err(MoveError::NotSideToMove(E7)),
+                pos.try_make_move(Move::double_pawn_push(E7, E5))
+            );
+        }
+
+        #[test]
+        fn move_leaving_king_in_check() {
+            // The knight on e2 is pinned against the king.
+            let mut pos = Position::from_fen("4r1k1/8/8/8/8/8/4N3/4K3 w - - 0 1").unwrap();
+            let before = pos.as_fen();
+            assert_eq!(
+                Err(MoveError::LeavesKingInCheck(Move::quiet(E2, C3))),
+                pos.try_make_move(Move::quiet(E2, C3))
+            );
+            assert_eq!(before, pos.as_fen());
+            assert!(pos.history().is_empty());
+        }
+
+        #[test]
+        fn illegal_and_legal_moves() {
+            let mut pos = Position::from_start_position();
+            assert_eq!(
+                Err(MoveError::Illegal(Move::quiet(E2, E5))),
+                pos.try_make_move(Move::quiet(E2, E5))
+            );
+            assert_eq!(Err(MoveError::NullMove), pos.try_make_move(Move::null()));
+            assert_eq!(Ok(()), pos.try_make_move(Move::double_pawn_push(E2, E4)));
+            assert_eq!(Color::Black, pos.side_to_move());
+        }
+    }
+
+    mod eq {
+        use crate::{core::*, position::Position};
+
+        #[test]
+        fn transposition_is_equal() {
+            let mut first = Position::from_start_position();
+            for mov in [
+                Move::quiet(G1, F3),
+                Move::quiet(G8, F6),
+                Move::quiet(B1, C3),
+            ] {
+                first.make_move(mov);
+            }
+
+            let mut second = Position::from_start_position();
+            for mov in [
+                Move::quiet(B1, C3),
+                Move::quiet(G8, F6),
+                Move::quiet(G1, F3),
+            ] {
+                second.make_move(mov);
+            }
+
+            assert_ne!(first.history(), second.history());
+            assert_eq!(first, second);
+        }
+
+        #[test]
+        fn clocks_are_compared() {
+            let pos = Position::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+            let later = Position::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 10 40").unwrap();
+            assert_ne!(pos, later);
+            assert!(pos.is_same_position(&later));
+        }
+
+        #[test]
+        fn hash_collision_is_not_equal() {
+            let pos = Position::from_start_position();
+            let mut other = Position::from_fen(
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            )
+            .unwrap();
+            other.zobrist_hash = pos.zobrist_hash;
+            assert_ne!(pos, other);
+            assert!(!pos.is_same_position(&other));
+        }
     }
 }
@@ -7,23 +7,29 @@
 // except according to those terms.
 
 use std::{
-    sync::atomic::{AtomicBool, Ordering},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
     time::{Duration, Instant},
 };
 
 use crate::{
     core::*,
-    eval::{evaluate, Value},
-    movegen,
+    eval::{evaluate_stm, evaluate_stm_with_bounds, Value},
+    movegen::{self, MoveList},
     position::Position,
     table::{self, NodeKind},
-    threads,
+    tablebase::{self, Wdl},
 };
 
+mod info;
 mod move_order;
 
+pub use info::{InfoSink, SearchInfo, UciInfoSink};
+
 /// Options for a search.
-#[derive(Default, Debug)]
+#[derive(Debug)]
 pub struct SearchOptions<'a> {
     /// Maximum amount of time to dedicate to this search.
     pub time_limit: Option<Duration>,
@@ -31,33 +37,173 @@ pub struct SearchOptions<'a> {
     /// Maximum amount of nodes to evaluate.
     pub node_limit: Option<u64>,
 
-    /// Reference to a hard stop flag, which (if set) should immediately terminate the search.
-    pub hard_stop: Option<&'a AtomicBool>,
+    /// A token that, once stopped, immediately terminates the search.
+    pub hard_stop: Option<StopToken>,
 
     /// Maximum depth to search.
     pub depth: u32,
+
+    /// Whether or not quiescence search skips captures that lose material according to static exchange evaluation.
+    pub see_pruning: bool,
+
+    /// Whether or not quiescence search skips captures that can't raise the score to alpha, even if the captured piece
+    /// came for free (delta pruning).
+    pub delta_pruning: bool,
+
+    /// Whether or not the search skips underpromotions to rooks and bishops below the root, where they're almost never
+    /// better than promoting to a queen.
+    pub underpromotion_pruning: bool,
+
+    /// Remaining depth at or below which quiet moves that can't plausibly raise the score to alpha are skipped
+    /// (futility pruning). Zero disables futility pruning.
+    pub futility_depth: u32,
+
+    /// The most, in centipawns per ply of remaining depth, that a quiet move is assumed to gain for the purposes of
+    /// futility pruning.
+    pub futility_margin: i16,
+
+    /// Remaining depth at or below which a node is pruned outright if its static evaluation beats beta by a margin
+    /// (reverse futility pruning). Zero disables reverse futility pruning.
+    pub reverse_futility_depth: u32,
+
+    /// The margin, in centipawns per ply of remaining depth, by which the static evaluation must beat beta for reverse
+    /// futility pruning to prune a node.
+    pub reverse_futility_margin: i16,
+
+    /// How much, in centipawns, the side to move at the root dislikes a draw. Draws are scored as this much worse than
+    /// even for the root side and this much better than even for its opponent.
+    pub contempt: i16,
+
+    /// Number of principal variations to search for and report after each iteration. Values of zero and one both search
+    /// only the best line.
+    pub multi_pv: usize,
+
+    /// Where to send reports on the search's progress. The search reports nothing if this is None.
+    pub info_sink: Option<&'a dyn InfoSink>,
+
+    /// Maximum number of plies that quiescence search goes beyond the horizon. Positions at this depth are scored by
+    /// their static evaluation, even if there are captures left to play.
+    pub max_qsearch_depth: u32,
+
+    /// If set, the search only looks for a forced mate in at most this many moves (up to `MAX_MATE_MOVES`), as UCI's
+    /// `go mate`. The search ends as soon as it finds one, and reports the null move if there isn't one. `depth` is
+    /// ignored.
+    pub mate: Option<u32>,
+
+    /// Whether or not the search uses the transposition table. Without it, the search neither consults nor records
+    /// positions there, and keeps track of its principal variation itself. This is much slower, but takes the table out
+    /// of the picture when debugging the search.
+    pub use_tt: bool,
+}
+
+/// The defaults turn off all pruning and search nothing past the root; callers are expected to set at least `depth`.
+impl Default for SearchOptions<'_> {
+    fn default() -> Self {
+        SearchOptions {
+            time_limit: None,
+            node_limit: None,
+            hard_stop: None,
+            depth: 0,
+            see_pruning: false,
+            delta_pruning: false,
+            underpromotion_pruning: false,
+            futility_depth: 0,
+            futility_margin: 0,
+            reverse_futility_depth: 0,
+            reverse_futility_margin: 0,
+            contempt: 0,
+            multi_pv: 0,
+            info_sink: None,
+            max_qsearch_depth: DEFAULT_MAX_QSEARCH_DEPTH,
+            mate: None,
+            use_tt: true,
+        }
+    }
 }
 
+/// A flag that tells a search to stop, shared between the search and whoever wants to stop it. Clones of a token share
+/// the same flag, so a token can be handed to a search running on another thread and stopped from this one.
+#[derive(Clone, Debug, Default)]
+pub struct StopToken(Arc<AtomicBool>);
+
+impl StopToken {
+    pub fn new() -> StopToken {
+        StopToken::default()
+    }
+
+    /// Tells every search holding this token to stop as soon as possible.
+    pub fn stop(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    /// Returns whether this token has been stopped.
+    pub fn is_stopped(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+
+    /// Clears the stop flag, so the token can be used for another search.
+    pub fn reset(&self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+/// A reasonable default for `SearchOptions::futility_margin`.
+pub const DEFAULT_FUTILITY_MARGIN: i16 = 200;
+
+/// A reasonable default for `SearchOptions::reverse_futility_margin`.
+pub const DEFAULT_REVERSE_FUTILITY_MARGIN: i16 = 120;
+
+/// A reasonable default for `SearchOptions::max_qsearch_depth`.
+pub const DEFAULT_MAX_QSEARCH_DEPTH: u32 = 16;
+
+/// The longest mate, in moves, that `SearchOptions::mate` can look for. Longer mates can't be scored.
+pub const MAX_MATE_MOVES: u32 = 24;
+
 struct Searcher<'a, 'b> {
     search_start_time: Instant,
+    /// Number of nodes searched so far. See `SearchStats::nodes_searched` for what counts as a node.
     nodes_searched: u64,
     options: &'a SearchOptions<'b>,
     /// Whether this searcher is terminating. This flag is set the first time our termination check reveals that we
     /// should terminate.
     terminating: bool,
+    /// Number of positions found in the transposition table during this search.
+    transposition_hits: u64,
+    /// The deepest ply reached so far, including quiescence search.
+    seldepth: u32,
+    /// The best move found so far at the root. The transposition table can't be relied on for this, since the root's
+    /// entry may have been replaced by the time the search finishes.
+    root_best_move: Option<Move>,
+    /// The best line found so far from each ply of the line currently being searched. Only kept when the
+    /// transposition table is disabled, since the table otherwise remembers the principal variation.
+    lines: Vec<Vec<Move>>,
+    /// Move lists that no node is using. See `with_move_list`.
+    move_lists: Vec<MoveList>,
+    /// If set, the only moves to consider at the root.
+    root_moves: Option<Vec<Move>>,
 }
 
 /// Statistics about the search, reported to the caller upon termination of the search.
 #[derive(Clone, Debug, Default)]
 pub struct SearchStats {
+    /// Number of nodes searched across all iterations, including an iteration that was cut short. A node is a position
+    /// reached by making a move during the search, whether in the main search or in quiescence search; the root
+    /// position isn't counted.
     pub nodes_searched: u64,
+    /// Number of nodes searched by each completed iteration, starting at depth 1.
     pub nodes_searched_per_depth: Vec<u64>,
 }
 
 #[derive(Clone, Debug)]
 pub struct SearchResult {
+    /// The best move found. When several moves share the best score, the one whose UCI form sorts first is chosen.
     pub best_move: Move,
     pub best_score: Value,
+    /// The principal variation of the deepest completed iteration, starting with the best move.
+    pub pv: Vec<Move>,
+    /// The principal variations after the first, with their scores, from best to worst. These are only searched when
+    /// `SearchOptions::multi_pv` asks for more than one line, and come from the last iteration to produce a best move.
+    pub other_pvs: Vec<(Value, Vec<Move>)>,
     pub stats: SearchStats,
 }
 
@@ -68,26 +214,149 @@ impl<'a: 'b, 'b> Searcher<'a, 'b> {
             search_start_time: Instant::now(),
             options,
             terminating: false,
+            transposition_hits: 0,
+            seldepth: 0,
+            root_best_move: None,
+            lines: vec![],
+            move_lists: vec![],
+            root_moves: None,
         }
     }
 
     fn search(&mut self, pos: &Position, depth: u32) -> Option<(Move, Value)> {
-        let alpha = Value::mated_in(0);
-        let beta = Value::mate_in(0);
+        let alpha = -Value::INFINITE;
+        let beta = Value::INFINITE;
         let score = self.alpha_beta(pos, alpha, beta, depth);
         // If this search was cut short for any reason, we can't trust the alpha, beta, or score that we ended up with.
         if !self.can_continue_search() {
             return None;
         }
 
-        let best_move = table::query(&pos)
-            .expect("t-table miss after search?")
-            .best_move()
-            .expect("search thinks that root node is an all-node?");
+        // The root always records its best move, but fall back to the table just in case.
+        let best_move = self
+            .root_best_move
+            .or_else(|| table::query(pos).and_then(|entry| entry.best_move()))?;
         Some((best_move, score))
     }
 
-    fn alpha_beta(&mut self, pos: &Position, mut alpha: Value, beta: Value, depth: u32) -> Value {
+    /// Searches the root position to the given depth as `search` does, but without considering any of the given root
+    /// moves. This is how lines after the first are found when reporting multiple principal variations. The root isn't
+    /// recorded in the transposition table, since its entry belongs to the unrestricted search.
+    ///
+    /// Returns the best remaining move, its score, and its principal variation, or None if there are no remaining
+    /// moves or if the search was cut short.
+    fn search_excluding(
+        &mut self,
+        pos: &Position,
+        depth: u32,
+        excluded: &[Move],
+    ) -> Option<(Move, Value, Vec<Move>)> {
+        let mut moves = Vec::new();
+        movegen::generate_moves(pos.side_to_move(), pos, &mut moves);
+        moves.retain(|&m| {
+            pos.is_legal_given_pseudolegal(m) && !excluded.contains(&m) && self.is_root_move(m)
+        });
+        move_order::order_moves(pos, &mut moves);
+        let mut best: Option<(Move, Value, Vec<Move>)> = None;
+        let mut alpha = -Value::INFINITE;
+        for mov in moves {
+            let child = self.make_move(pos, mov);
+            let value = -self.alpha_beta(&child, -Value::INFINITE, -alpha, depth - 1);
+            if !self.can_continue_search() {
+                return None;
+            }
+
+            if best.is_none() || value > alpha {
+                alpha = value;
+                let mut pv = vec![mov];
+                pv.extend(self.principal_variation(&child, depth - 1, 1));
+                best = Some((mov, value, pv));
+            }
+        }
+
+        best
+    }
+
+    /// Searches for a forced mate within `depth` plies, `ply` plies from the root. Positions are scored by nothing but
+    /// checkmate: a mate is scored by its distance from the root, and every other position, including any position at
+    /// the horizon, is scored as a draw. The transposition table isn't used, since its mate scores aren't relative to
+    /// any particular root.
+    ///
+    /// The principal variation of the line that raised alpha, if any, is written to `pv`.
+    fn mate_search(
+        &mut self,
+        pos: &Position,
+        mut alpha: Value,
+        beta: Value,
+        ply: u32,
+        depth: u32,
+        pv: &mut Vec<Move>,
+    ) -> Value {
+        if !self.can_continue_search() {
+            return alpha;
+        }
+
+        self.seldepth = self.seldepth.max(ply);
+        let mut moves = MoveList::new();
+        movegen::generate_move_list(pos.side_to_move(), pos, &mut moves);
+        moves.retain(|&mut m| pos.is_legal_given_pseudolegal(m));
+        if moves.is_empty() {
+            return if pos.is_check(pos.side_to_move()) {
+                Value::mated_in(ply as i16)
+            } else {
+                Value::DRAW
+            };
+        }
+
+        if depth == 0 {
+            return Value::DRAW.clamp(alpha, beta);
+        }
+
+        // Mate distance pruning: the best that we can do from here is to mate on the next ply, so there's nothing to
+        // find if that isn't enough to raise alpha.
+        if Value::mate_in(ply as i16 + 1) <= alpha {
+            return alpha;
+        }
+
+        move_order::order_moves(pos, &mut moves);
+        for mov in moves {
+            let child = self.make_move(pos, mov);
+            let mut line = vec![];
+            let value = -self.mate_search(&child, -beta, -alpha, ply + 1, depth - 1, &mut line);
+            if !self.can_continue_search() {
+                return alpha;
+            }
+
+            if value >= beta {
+                return beta;
+            }
+
+            if value > alpha {
+                alpha = value;
+                pv.clear();
+                pv.push(mov);
+                pv.extend(line);
+            }
+        }
+
+        alpha
+    }
+
+    fn alpha_beta(&mut self, pos: &Position, alpha: Value, beta: Value, depth: u32) -> Value {
+        self.with_move_list(|searcher, moves| {
+            searcher.alpha_beta_with(pos, alpha, beta, depth, moves)
+        })
+    }
+
+    /// The body of `alpha_beta`, which generates moves into the given (empty) move list.
+    fn alpha_beta_with(
+        &mut self,
+        pos: &Position,
+        mut alpha: Value,
+        beta: Value,
+        depth: u32,
+        moves: &mut MoveList,
+    ) -> Value {
         // Two places that we check for search termination, inserted in the same place that a compiler would insert safepoints for preemption:
         //   1. Function entry blocks, so we can cut off trees that we are about to search if we are out of time
         //   2. Loop back edges, so we can cut off trees that we are partially in the process of searching
@@ -95,38 +364,96 @@ impl<'a: 'b, 'b> Searcher<'a, 'b> {
             return alpha;
         }
 
+        // If we can force a repetition from here, we're guaranteed at least a draw. There's no need to search this
+        // position if a draw is already good enough to fail high. The root is excluded, since it needs to produce a
+        // move.
+        let ply = self.options.depth - depth;
+        self.seldepth = self.seldepth.max(ply);
+        if !self.options.use_tt {
+            if let Some(line) = self.lines.get_mut(ply as usize) {
+                line.clear();
+            }
+        }
+
+        if ply > 0 && alpha < self.draw_value(ply) && pos.has_upcoming_repetition(ply) {
+            alpha = self.draw_value(ply);
+            if alpha >= beta {
+                return alpha;
+            }
+        }
+
         if depth == 0 {
-            return self.quiesce(pos, alpha, beta);
+            return self.quiesce(pos, alpha, beta, ply);
         }
 
         // Consult the transposition table. Have we seen this position before and, if so, does it produce a cutoff?
-        // If so, there's no need to continue processing this position.
-        let (mut hash_move, cutoff_value) =
-            self.consider_transposition(pos, &mut alpha, beta, depth);
+        // If so, there's no need to continue processing this position. The root is always searched, since it needs to
+        // produce a move; it only takes the hash move from the table.
+        let (mut hash_move, cutoff_value) = if !self.options.use_tt {
+            (None, None)
+        } else if ply == 0 {
+            let mut root_alpha = alpha;
+            let (hash_move, _) = self.consider_transposition(pos, &mut root_alpha, beta, depth);
+            (hash_move, None)
+        } else {
+            self.consider_transposition(pos, &mut alpha, beta, depth)
+        };
         if let Some(cutoff) = cutoff_value {
             return cutoff;
         }
 
+        // Consult the endgame tablebases. The root is excluded, since it needs to produce a move and the tablebases
+        // only tell us the outcome. Probes are only accurate right after the 50-move counter is reset, which is also
+        // when captures move us into tablebase territory in the first place.
+        if depth < self.options.depth && pos.halfmove_clock() == 0 {
+            if let Some(wdl) = tablebase::probe_wdl(pos) {
+                return tablebase_value(wdl);
+            }
+        }
+
+        // If the static evaluation beats beta by a wide enough margin, it's very unlikely that our opponent can do
+        // anything about it in the few plies left before the horizon, so assume that this node fails high (reverse
+        // futility pruning). This doesn't hold when we're in check, and mate scores can't be trusted to be static.
+        if ply > 0
+            && depth <= self.options.reverse_futility_depth
+            && !beta.is_mate()
+            && !pos.is_check(pos.side_to_move())
+        {
+            let static_value = evaluate_stm(pos);
+            if !static_value.is_mate()
+                && static_value - self.options.reverse_futility_margin * depth as i16 >= beta
+            {
+                return static_value;
+            }
+        }
+
         //
         // Step 1 - Consider and evaluate the hash move.
         //
 
         // Apply a legality test. In the event of t-table collisions, the hash move might not be a legal move.
-        hash_move = hash_move.and_then(|mov| if pos.is_legal(mov) { Some(mov) } else { None });
+        hash_move =
+            hash_move.filter(|&mov| pos.is_legal(mov) && (ply > 0 || self.is_root_move(mov)));
 
         // Keep track if any move improved alpha. If so, this is a PV node.
         let mut improved_alpha = false;
+        let mut best_move = None;
         if let Some(hash_move) = hash_move {
             let hash_pos = self.make_move(pos, hash_move);
             let value = -self.alpha_beta(&hash_pos, -beta, -alpha, depth - 1);
+            if !self.can_continue_search() {
+                return alpha;
+            }
+
             if value >= beta {
-                table::record_cut(pos, hash_move, depth, value);
+                self.record_cut(pos, hash_move, depth, value);
                 return beta.step();
             }
 
             if value > alpha {
                 improved_alpha = true;
-                table::record_pv(pos, hash_move, depth, value);
+                best_move = Some(hash_move);
+                self.record_pv(pos, hash_move, depth, value);
                 alpha = value;
             }
         }
@@ -135,9 +462,10 @@ impl<'a: 'b, 'b> Searcher<'a, 'b> {
         // Step 2 - Generate moves and scan the position.
         //
 
-        let mut moves = Vec::new();
-        movegen::generate_moves(pos.side_to_move(), pos, &mut moves);
-        moves.retain(|&m| pos.is_legal_given_pseudolegal(m));
+        self.generate_moves(pos, moves);
+        moves.retain(|&mut m| {
+            pos.is_legal_given_pseudolegal(m) && (ply > 0 || self.is_root_move(m))
+        });
         if moves.len() == 0 {
             // No legal moves available. Are we in check?
             let value = if pos.is_check(pos.side_to_move()) {
@@ -145,33 +473,66 @@ impl<'a: 'b, 'b> Searcher<'a, 'b> {
                 Value::mated_in(0)
             } else {
                 // We've drawn.
-                Value::new(0)
+                self.draw_value(ply)
             };
 
-            table::record_pv(pos, Move::null(), depth, value);
+            self.record_pv(pos, Move::null(), depth, value);
             return value.step();
         }
 
+        // Close to the horizon, a quiet move can only change the evaluation by so much. If the static evaluation is so
+        // far below alpha that a quiet move can't make up the difference, there's no point in searching quiet moves.
+        // This doesn't hold when we're in check, and there's no use in it if alpha is a mate score.
+        let futility_value = if depth <= self.options.futility_depth
+            && !pos.is_check(pos.side_to_move())
+            && !alpha.is_mate()
+        {
+            Some(evaluate_stm(pos) + self.options.futility_margin * depth as i16)
+        } else {
+            None
+        };
+
         // We have at least one legal move available to us, so let's play.
         // First, we order our moves so that we maximizes the chances of good moves being searched first.
-        move_order::order_moves(pos, &mut moves);
-        for mov in moves {
+        move_order::order_moves(pos, moves);
+        for &mov in moves.iter() {
+            if let Some(futility_value) = futility_value {
+                if futility_value <= alpha && !mov.is_tactical() && !pos.gives_check(mov) {
+                    continue;
+                }
+            }
+
+            // At the root, once there is a best move, the rest are searched with a window one centipawn wider than
+            // usual. A move that ties with the best move then gets an exact score instead of failing low, so that the
+            // tie can be broken by `breaks_tie`. Mate scores can't be widened, but they rarely tie.
+            let tie_breaking = ply == 0 && best_move.is_some() && !alpha.is_mate();
+            let floor = if tie_breaking { alpha - 1 } else { alpha };
             let child = self.make_move(pos, mov);
-            let value = -self.alpha_beta(&child, -beta, -alpha, depth - 1);
+            let value = -self.alpha_beta(&child, -beta, -floor, depth - 1);
+            // If the search was cut short while searching this move, its value can't be trusted, and neither can
+            // anything that we would conclude from it.
+            if !self.can_continue_search() {
+                return alpha;
+            }
+
             if value >= beta {
-                table::record_cut(pos, mov, depth, value);
+                self.record_cut(pos, mov, depth, value);
                 return beta.step();
             }
 
-            if value > alpha {
+            let wins_tie = tie_breaking
+                && value == alpha
+                && best_move.map_or(false, |best| breaks_tie(mov, best));
+            if value > alpha || wins_tie {
                 improved_alpha = true;
-                table::record_pv(pos, mov, depth, value);
+                best_move = Some(mov);
+                self.record_pv(pos, mov, depth, value);
                 alpha = value;
             }
         }
 
         if !improved_alpha {
-            table::record_all(pos, depth, alpha);
+            self.record_all(pos, depth, alpha);
         }
 
         alpha.step()
@@ -184,17 +545,28 @@ impl<'a: 'b, 'b> Searcher<'a, 'b> {
     /// Consider a search that reaches its depth limit at a move where a queen takes a pawn that is defended by another
     /// pawn. We can't simply terminate the search there - we must continue evaluations until captures are complete,
     /// otherwise we will not see that our queen is lost.
-    fn quiesce(&mut self, pos: &Position, mut alpha: Value, beta: Value) -> Value {
+    fn quiesce(&mut self, pos: &Position, alpha: Value, beta: Value, ply: u32) -> Value {
+        self.with_move_list(|searcher, moves| searcher.quiesce_with(pos, alpha, beta, ply, moves))
+    }
+
+    /// The body of `quiesce`, which generates moves into the given (empty) move list.
+    fn quiesce_with(
+        &mut self,
+        pos: &Position,
+        mut alpha: Value,
+        beta: Value,
+        ply: u32,
+        moves: &mut MoveList,
+    ) -> Value {
+        self.seldepth = self.seldepth.max(ply);
         // The "stand pat" score is a lower bound to how bad this position is. We're interested in finding refutations
-        // to this position that drop this lower bound.
-        //
-        // Note that the evaluation function returns a number that is relative to White - positive numbers are good
-        // for White, negative numbers are good for Black. We must first flip the sign if we're evaluating a position
-        // with Black to move.
-        let mut stand_pat = evaluate(pos);
-        if pos.side_to_move() == Color::Black {
-            stand_pat = -stand_pat;
-        }
+        // to this position that drop this lower bound. Only its relation to the window matters, so a lazy evaluation
+        // will do, unless we're in check and might be checkmated.
+        let mut stand_pat = if pos.is_check(pos.side_to_move()) {
+            evaluate_stm(pos)
+        } else {
+            evaluate_stm_with_bounds(pos, alpha, beta)
+        };
 
         if stand_pat >= beta {
             // There exists a refutation in a sibling node - no point seaerching this.
@@ -204,21 +576,51 @@ impl<'a: 'b, 'b> Searcher<'a, 'b> {
             alpha = stand_pat;
         }
 
-        let mut moves = Vec::new();
-        movegen::generate_moves(pos.side_to_move(), pos, &mut moves);
-        moves.retain(|&m| pos.is_legal_given_pseudolegal(m));
-        moves.retain(|&m| m.is_capture());
+        // Long capture sequences can make quiescence search explode, so give up on resolving them past a point.
+        if ply.saturating_sub(self.options.depth) >= self.options.max_qsearch_depth {
+            return alpha;
+        }
+
+        self.generate_moves(pos, moves);
+        moves.retain(|&mut m| pos.is_legal_given_pseudolegal(m));
+        moves.retain(|&mut m| m.is_capture());
+
+        // Captures that lose material are very unlikely to improve alpha, so don't bother searching them. Captures
+        // that give check are the exception, since the check might be worth more than the material.
+        if self.options.see_pruning {
+            moves.retain(|&mut m| pos.see_ge(m, 0) || pos.gives_check(m));
+        }
+
         if moves.len() == 0 {
             return stand_pat;
         }
 
-        for capture in moves {
+        // Delta pruning is unsafe in the endgame, where the evaluation can swing wildly with little material on the
+        // board.
+        let static_eval = stand_pat;
+        let delta_pruning = self.options.delta_pruning && !is_endgame(pos);
+        for &capture in moves.iter() {
             if !self.can_continue_search() {
                 return alpha;
             }
 
+            // If winning the captured piece for free and then some still wouldn't be enough to raise alpha, this
+            // capture isn't worth searching. Promotions are exempt, since they gain much more than the captured piece.
+            if delta_pruning && !capture.is_promotion_capture() {
+                let captured_value = capture
+                    .captured_square(pos.side_to_move())
+                    .and_then(|square| pos.piece_at(square))
+                    .expect("capture with no piece at the captured square")
+                    .kind
+                    .value();
+
+                if static_eval + (captured_value as i16 * 100 + DELTA_PRUNING_MARGIN) < alpha {
+                    continue;
+                }
+            }
+
             let child = self.make_move(pos, capture);
-            stand_pat = -self.quiesce(&child, -beta, -alpha);
+            stand_pat = -self.quiesce(&child, -beta, -alpha, ply + 1);
             if stand_pat >= beta {
                 return beta;
             }
@@ -230,6 +632,17 @@ impl<'a: 'b, 'b> Searcher<'a, 'b> {
         alpha
     }
 
+    /// Lends a move list from the searcher's pool to the given function. Lists go back to the pool once the function is
+    /// done with them, so nodes reuse the lists, and any heap space that they've grown into, instead of starting from
+    /// scratch.
+    fn with_move_list<T>(&mut self, f: impl FnOnce(&mut Self, &mut MoveList) -> T) -> T {
+        let mut moves = self.move_lists.pop().unwrap_or_default();
+        let result = f(self, &mut moves);
+        moves.clear();
+        self.move_lists.push(moves);
+        result
+    }
+
     fn can_continue_search(&mut self) -> bool {
         if self.terminating {
             return false;
@@ -244,15 +657,15 @@ impl<'a: 'b, 'b> Searcher<'a, 'b> {
         }
 
         if let Some(limit) = self.options.node_limit {
-            if self.nodes_searched > limit {
+            if self.nodes_searched >= limit {
                 info!("terminating search due to nodes evaluated");
                 self.terminating = true;
                 return false;
             }
         }
 
-        if let Some(ptr) = self.options.hard_stop {
-            if ptr.load(Ordering::Acquire) {
+        if let Some(token) = &self.options.hard_stop {
+            if token.is_stopped() {
                 info!("terminating search due to explicit termination");
                 self.terminating = true;
                 return false;
@@ -262,8 +675,59 @@ impl<'a: 'b, 'b> Searcher<'a, 'b> {
         true
     }
 
+    /// Records that the given move caused a beta cutoff in the given position.
+    fn record_cut(&self, pos: &Position, mov: Move, depth: u32, value: Value) {
+        if self.options.use_tt {
+            table::record_cut(pos, mov, depth, value);
+        }
+    }
+
+    /// Records that the given move is the best move so far in the given position, with an exact value. The null move
+    /// records that the game is over.
+    fn record_pv(&mut self, pos: &Position, mov: Move, depth: u32, value: Value) {
+        let ply = (self.options.depth - depth) as usize;
+        if ply == 0 {
+            self.root_best_move = Some(mov);
+        }
+
+        if self.options.use_tt {
+            table::record_pv(pos, mov, depth, value);
+            return;
+        }
+
+        // The child's line is the one that it just finished searching.
+        let mut line = vec![];
+        if !mov.is_null() {
+            line.push(mov);
+            line.extend(self.lines.get(ply + 1).into_iter().flatten());
+        }
+
+        if self.lines.len() <= ply {
+            self.lines.resize(ply + 1, vec![]);
+        }
+
+        self.lines[ply] = line;
+    }
+
+    /// Records that no move raised alpha in the given position.
+    fn record_all(&self, pos: &Position, depth: u32, alpha: Value) {
+        if self.options.use_tt {
+            table::record_all(pos, depth, alpha);
+        }
+    }
+
+    /// Returns the principal variation from the given position, which was just searched to the given depth at the
+    /// given ply.
+    fn principal_variation(&self, pos: &Position, depth: u32, ply: u32) -> Vec<Move> {
+        if self.options.use_tt {
+            table::get_pv(pos, depth)
+        } else {
+            self.lines.get(ply as usize).cloned().unwrap_or_default()
+        }
+    }
+
     fn consider_transposition(
-        &self,
+        &mut self,
         pos: &Position,
         alpha: &mut Value,
         beta: Value,
@@ -278,6 +742,7 @@ impl<'a: 'b, 'b> Searcher<'a, 'b> {
         //      before even generating moves for the current position, in the hopes that the hash move either fails high
         //      or produces a really high alpha.
         let hash_move = if let Some(entry) = table::query(pos) {
+            self.transposition_hits += 1;
             // Transposition table hit. We might not be able to use this hit, though:
             //    1. If the entry's depth is less than the depth we are currently searching at, we shouldn't
             //       use this entry since the search we are about to do is going to be higher fidelity.
@@ -289,7 +754,7 @@ impl<'a: 'b, 'b> Searcher<'a, 'b> {
             if entry.depth() >= depth {
                 // We can actually use this node! To guard against hash collisions, we do need to apply a legality test
                 // on the hash move.
-                if hash_move.is_none() || pos.is_legal(hash_move.unwrap()) {
+                if hash_move.map_or(true, |mov| pos.is_legal(mov)) {
                     // Either we don't have a hash move (all-node) or we do and it cut off. Either way, we get to avoid
                     // doing some work.
                     match entry.kind() {
@@ -340,23 +805,118 @@ impl<'a: 'b, 'b> Searcher<'a, 'b> {
         (hash_move, None)
     }
 
+    /// Generates pseudolegal moves for a position below the root, leaving out underpromotions if the options say to.
+    /// Whenever an underpromotion is legal, so are the queen and knight promotions on the same squares, so this never
+    /// turns a position with legal moves into one without.
+    fn generate_moves(&self, pos: &Position, moves: &mut MoveList) {
+        if self.options.underpromotion_pruning {
+            movegen::generate_moves_pruned(pos.side_to_move(), pos, moves);
+        } else {
+            movegen::generate_move_list(pos.side_to_move(), pos, moves);
+        }
+    }
+
+    /// Returns whether the given legal move is one that the root is allowed to play.
+    fn is_root_move(&self, mov: Move) -> bool {
+        self.root_moves
+            .as_ref()
+            .map_or(true, |moves| moves.contains(&mov))
+    }
+
     fn make_move(&mut self, pos: &Position, mov: Move) -> Position {
         self.nodes_searched += 1;
         pos.clone_and_make_move(mov)
     }
+
+    /// The value of a draw for the side to move at the given ply, taking contempt into account.
+    fn draw_value(&self, ply: u32) -> Value {
+        if ply % 2 == 0 {
+            Value::DRAW - self.options.contempt
+        } else {
+            Value::DRAW + self.options.contempt
+        }
+    }
+}
+
+/// Safety margin, in centipawns, for delta pruning in quiescence search.
+const DELTA_PRUNING_MARGIN: i16 = 200;
+
+/// Non-pawn material, in centipawns, at or below which a position is considered to be an endgame.
+const ENDGAME_MATERIAL_THRESHOLD: i16 = 1600;
+
+/// Returns whether or not the given position is an endgame, based on the non-pawn material left on the board.
+fn is_endgame(pos: &Position) -> bool {
+    let material: i16 = colors().map(|side| pos.non_pawn_material(side)).sum();
+    material <= ENDGAME_MATERIAL_THRESHOLD
+}
+
+/// Score given to positions that the tablebases report as won. This is larger than any evaluation, but smaller than
+/// any mate score, so that the search still prefers a mate that it can see.
+const TABLEBASE_WIN: i16 = 12000;
+
+/// Converts a tablebase outcome into a score for the side to move. Cursed wins and blessed losses are scored as
+/// draws, since the 50-move rule intervenes before the game can be decided.
+fn tablebase_value(wdl: Wdl) -> Value {
+    match wdl {
+        Wdl::Win => Value::new(TABLEBASE_WIN),
+        Wdl::Loss => Value::new(-TABLEBASE_WIN),
+        Wdl::CursedWin | Wdl::Draw | Wdl::BlessedLoss => Value::DRAW,
+    }
 }
 
 pub fn search(pos: &Position, options: &SearchOptions) -> SearchResult {
+    // If the game is already over, there's no move to find. Report the null move, as UCI expects.
+    if let Some(score) = terminal_score(pos, options) {
+        if let Some(sink) = options.info_sink {
+            sink.best_move(Move::null());
+        }
+
+        return SearchResult {
+            best_move: Move::null(),
+            best_score: score,
+            pv: vec![],
+            other_pvs: vec![],
+            stats: SearchStats::default(),
+        };
+    }
+
+    if let Some(moves) = options.mate {
+        return search_for_mate(pos, options, moves);
+    }
+
+    // If the root is in the tablebases, only search the moves that keep the best outcome that it has. The tablebases
+    // can't tell which of those make progress towards a win, so the search still has to choose between them.
+    let root_moves = tablebase::root_moves(pos).map(|(moves, wdl)| {
+        info_string!(
+            "{} root moves keep the tablebase outcome {:?}",
+            moves.len(),
+            wdl
+        );
+        moves
+    });
+
     let mut stats = SearchStats::default();
     let mut current_best_move = Move::null();
-    let mut current_best_score = Value::mated_in(0);
+    let mut current_best_score = -Value::INFINITE;
+    let mut current_pv = vec![];
+    let mut current_other_pvs = vec![];
     let start_time = Instant::now();
     let mut node_count = 0;
+    if let Some(limit) = options.time_limit {
+        info_string!("allocating {}ms to this search", limit.as_millis());
+    }
+
     for depth in 1..=options.depth {
         info!("beginning iterative search of depth {}", depth);
         let time_since_start = Instant::now().duration_since(start_time);
         if let Some(limit) = options.time_limit {
             if limit < time_since_start {
+                info_string!(
+                    "not starting depth {}, {}ms of {}ms used",
+                    depth,
+                    time_since_start.as_millis(),
+                    limit.as_millis()
+                );
                 break;
             }
         }
@@ -365,54 +925,864 @@ pub fn search(pos: &Position, options: &SearchOptions) -> SearchResult {
                 .time_limit
                 .map(|limit| limit.saturating_sub(time_since_start)),
             depth,
-            hard_stop: options.hard_stop,
+            hard_stop: options.hard_stop.clone(),
             node_limit: options
                 .node_limit
                 .map(|limit| limit.saturating_sub(node_count)),
+            see_pruning: options.see_pruning,
+            delta_pruning: options.delta_pruning,
+            underpromotion_pruning: options.underpromotion_pruning,
+            futility_depth: options.futility_depth,
+            futility_margin: options.futility_margin,
+            reverse_futility_depth: options.reverse_futility_depth,
+            reverse_futility_margin: options.reverse_futility_margin,
+            contempt: options.contempt,
+            multi_pv: options.multi_pv,
+            info_sink: options.info_sink,
+            max_qsearch_depth: options.max_qsearch_depth,
+            mate: options.mate,
+            use_tt: options.use_tt,
         };
 
         let mut searcher = Searcher::new(&subsearch_opts);
+        searcher.root_moves = root_moves.clone();
         if !searcher.can_continue_search() {
             break;
         }
 
         let search_start = Instant::now();
-        if let Some((best_move, best_score)) = searcher.search(pos, depth) {
+        let result = searcher.search(pos, depth);
+        info_string!(
+            "depth {} transposition hits {}",
+            depth,
+            searcher.transposition_hits
+        );
+
+        // Nodes searched by an iteration that was cut short still count against the node limit, even though the
+        // iteration's result is thrown away.
+        node_count += searcher.nodes_searched;
+        stats.nodes_searched += searcher.nodes_searched;
+        if let Some((best_move, best_score)) = result {
             let search_time = Instant::now().duration_since(search_start);
-            node_count += searcher.nodes_searched;
-            stats.nodes_searched += searcher.nodes_searched;
             stats.nodes_searched_per_depth.push(searcher.nodes_searched);
             current_best_move = best_move;
             current_best_score = best_score;
-            let nps = searcher.nodes_searched as f64 / search_time.as_secs_f64();
-            let pv = table::get_pv(pos, depth);
-            if threads::get_worker_id() == Some(0) {
-                // TODO(swgillespie) - seldepth, how far did the qsearch go
-                let pv_str = pv
-                    .into_iter()
-                    .map(|mov| mov.as_uci())
-                    .collect::<Vec<_>>()
-                    .join(" ");
-                uci_output!(
-                    "info depth {} nodes {} nps {} time {} pv {} score {}",
+            let pv = searcher.principal_variation(pos, depth, 0);
+            if let Some(sink) = options.info_sink {
+                let multi_pv = if options.multi_pv > 1 { Some(1) } else { None };
+                sink.info(&SearchInfo {
                     depth,
-                    searcher.nodes_searched,
-                    nps.floor() as i64,
-                    search_time.as_millis(),
-                    pv_str,
-                    current_best_score.as_uci(),
-                );
+                    seldepth: searcher.seldepth,
+                    multi_pv,
+                    score: current_best_score,
+                    nodes: searcher.nodes_searched,
+                    nps: nodes_per_second(searcher.nodes_searched, search_time),
+                    time: search_time,
+                    pv: pv.clone(),
+                });
+            }
+
+            // Each additional line is the best line that doesn't start with any of the moves already found.
+            let nodes_before = searcher.nodes_searched;
+            let mut excluded = vec![best_move];
+            let mut other_pvs = vec![];
+            for line in 2..=options.multi_pv {
+                let (mov, score, line_pv) = match searcher.search_excluding(pos, depth, &excluded) {
+                    Some(line) => line,
+                    None => break,
+                };
+
+                if let Some(sink) = options.info_sink {
+                    let line_time = Instant::now().duration_since(search_start);
+                    sink.info(&SearchInfo {
+                        depth,
+                        seldepth: searcher.seldepth,
+                        multi_pv: Some(line),
+                        score,
+                        nodes: searcher.nodes_searched,
+                        nps: nodes_per_second(searcher.nodes_searched, line_time),
+                        time: line_time,
+                        pv: line_pv.clone(),
+                    });
+                }
+
+                excluded.push(mov);
+                other_pvs.push((score, line_pv));
             }
+
+            node_count += searcher.nodes_searched - nodes_before;
+            stats.nodes_searched += searcher.nodes_searched - nodes_before;
+            current_other_pvs = other_pvs;
+            current_pv = pv;
+        } else {
+            // The search was terminated partway through this iteration. Whatever stopped it will also stop any deeper
+            // iteration, so there's no point in starting one.
+            break;
         }
     }
 
-    if threads::get_worker_id() == Some(0) {
-        uci_output!("bestmove {}", current_best_move.as_uci());
+    if let Some(sink) = options.info_sink {
+        sink.best_move(current_best_move);
     }
 
     SearchResult {
         best_move: current_best_move,
         best_score: current_best_score,
+        pv: current_pv,
+        other_pvs: current_other_pvs,
         stats,
     }
 }
+
+/// Searches for a forced mate in at most the given number of moves, trying each number of moves in turn so that the
+/// shortest mate is found first. See `SearchOptions::mate`.
+fn search_for_mate(pos: &Position, options: &SearchOptions, max_moves: u32) -> SearchResult {
+    let mut stats = SearchStats::default();
+    let mut best_move = Move::null();
+    let mut best_score = Value::DRAW;
+    let mut pv = vec![];
+    let mut searcher = Searcher::new(options);
+    for moves in 1..=max_moves.min(MAX_MATE_MOVES) {
+        let depth = 2 * moves - 1;
+        let search_start = Instant::now();
+        let nodes_before = searcher.nodes_searched;
+        // Only a mate within `depth` plies can beat alpha; everything else fails low.
+        let alpha = Value::mate_in(depth as i16 + 1);
+        let mut line = vec![];
+        let score = searcher.mate_search(pos, alpha, Value::INFINITE, 0, depth, &mut line);
+        if !searcher.can_continue_search() {
+            break;
+        }
+
+        let nodes = searcher.nodes_searched - nodes_before;
+        stats.nodes_searched_per_depth.push(nodes);
+        if score > alpha {
+            best_move = line[0];
+            best_score = score;
+            pv = line;
+            if let Some(sink) = options.info_sink {
+                let search_time = Instant::now().duration_since(search_start);
+                sink.info(&SearchInfo {
+                    depth,
+                    seldepth: searcher.seldepth,
+                    multi_pv: None,
+                    score,
+                    nodes,
+                    nps: nodes_per_second(nodes, search_time),
+                    time: search_time,
+                    pv: pv.clone(),
+                });
+            }
+
+            break;
+        }
+    }
+
+    stats.nodes_searched = searcher.nodes_searched;
+    if let Some(sink) = options.info_sink {
+        sink.best_move(best_move);
+    }
+
+    SearchResult {
+        best_move,
+        best_score,
+        pv,
+        other_pvs: vec![],
+        stats,
+    }
+}
+
+/// Returns the score of the given position if the side to move has no legal moves: a loss if it's checkmated and a draw
+/// if it's stalemated.
+fn terminal_score(pos: &Position, options: &SearchOptions) -> Option<Value> {
+    let mut moves = MoveList::new();
+    movegen::generate_move_list(pos.side_to_move(), pos, &mut moves);
+    if moves.iter().any(|&m| pos.is_legal_given_pseudolegal(m)) {
+        return None;
+    }
+
+    if pos.is_check(pos.side_to_move()) {
+        Some(Value::mated_in(0))
+    } else {
+        Some(Value::DRAW - options.contempt)
+    }
+}
+
+fn nodes_per_second(nodes: u64, time: Duration) -> u64 {
+    (nodes as f64 / time.as_secs_f64()).floor() as u64
+}
+
+/// Returns whether `mov` should be chosen over `best` when the two have the same score at the root. The move whose UCI
+/// form sorts first wins, so that which of several equally good moves the engine plays doesn't depend on the order in
+/// which moves happen to be generated and searched.
+fn breaks_tie(mov: Move, best: Move) -> bool {
+    mov.as_uci() < best.as_uci()
+}
+
+/// Searches every legal move in the given position to the given depth and returns them all, with their scores, sorted
+/// from best to worst, with ties broken as they are by `search`. Unlike `search`, this produces exact scores for every
+/// move rather than just the best one, and doesn't write anything to stdout.
+pub fn analyze(pos: &Position, depth: u32) -> Vec<(Move, Value)> {
+    let options = SearchOptions {
+        depth,
+        ..Default::default()
+    };
+
+    let mut searcher = Searcher::new(&options);
+    let mut moves = Vec::new();
+    movegen::generate_moves(pos.side_to_move(), pos, &mut moves);
+    moves.retain(|&m| pos.is_legal_given_pseudolegal(m));
+    let mut scored_moves: Vec<_> = moves
+        .into_iter()
+        .map(|mov| {
+            let child = searcher.make_move(pos, mov);
+            let value = -searcher.alpha_beta(
+                &child,
+                -Value::INFINITE,
+                Value::INFINITE,
+                depth.saturating_sub(1),
+            );
+            (mov, value)
+        })
+        .collect();
+
+    scored_moves.sort_by(|(a_mov, a), (b_mov, b)| {
+        b.cmp(a).then_with(|| a_mov.as_uci().cmp(&b_mov.as_uci()))
+    });
+    scored_moves
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::RefCell,
+        time::{Duration, Instant},
+    };
+
+    use super::{
+        InfoSink, SearchInfo, SearchOptions, Searcher, StopToken, DEFAULT_FUTILITY_MARGIN,
+        DEFAULT_REVERSE_FUTILITY_MARGIN,
+    };
+    use crate::{
+        core::*,
+        eval::{UnpackedValue, Value},
+        log,
+        position::{GameStatus, Position},
+        table,
+    };
+
+    fn quiesce(pos: &Position, options: &SearchOptions) -> (Value, u64) {
+        let mut searcher = Searcher::new(options);
+        let value = searcher.quiesce(pos, Value::mated_in(0), Value::mate_in(0), 0);
+        (value, searcher.nodes_searched)
+    }
+
+    #[test]
+    fn see_pruning_reduces_quiescence_nodes() {
+        // White's queen can take any of Black's pawns, but every one of them is defended.
+        let pos = Position::from_fen("4k3/8/2p1p3/3p1p2/2P1P3/8/8/3QK3 w - - 0 1").unwrap();
+        let (unpruned_value, unpruned_nodes) = quiesce(&pos, &SearchOptions::default());
+        let (pruned_value, pruned_nodes) = quiesce(
+            &pos,
+            &SearchOptions {
+                see_pruning: true,
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(unpruned_value, pruned_value);
+        assert!(pruned_nodes < unpruned_nodes);
+    }
+
+    #[test]
+    fn delta_pruning_when_hopelessly_behind() {
+        // White is down a queen and two rooks; the pawns that the knight can take won't bring the score anywhere near
+        // the window.
+        let pos = Position::from_fen("r3k2r/1q6/2p1p3/8/3N4/8/8/4K3 w - - 0 1").unwrap();
+        let (alpha, beta) = (Value::new(-100), Value::new(100));
+        let quiesce_window = |options: &SearchOptions| {
+            let mut searcher = Searcher::new(options);
+            let value = searcher.quiesce(&pos, alpha, beta, 0);
+            (value, searcher.nodes_searched)
+        };
+
+        let (unpruned_value, unpruned_nodes) = quiesce_window(&SearchOptions::default());
+        let (pruned_value, pruned_nodes) = quiesce_window(&SearchOptions {
+            delta_pruning: true,
+            ..Default::default()
+        });
+
+        assert_eq!(alpha, pruned_value);
+        assert_eq!(unpruned_value, pruned_value);
+        assert_eq!(0, pruned_nodes);
+        assert!(pruned_nodes < unpruned_nodes);
+    }
+
+    #[test]
+    fn debug_mode_writes_info_strings() {
+        let pos = Position::from_fen("4k3/8/2p1p3/3p1p2/2P1P3/8/8/3QK3 w - - 0 1").unwrap();
+        log::set_uci_debug(true);
+        log::take_info_strings();
+        super::search(
+            &pos,
+            &SearchOptions {
+                depth: 2,
+                ..Default::default()
+            },
+        );
+        log::set_uci_debug(false);
+
+        let info_strings = log::take_info_strings();
+        assert!(!info_strings.is_empty());
+    }
+
+    #[test]
+    fn pv_starts_with_best_move() {
+        let pos =
+            Position::from_fen("r1bqkb1r/ppp3pp/2n2p2/3np3/2BP4/5N2/PPP2PPP/RNBQ1RK1 w kq - 0 7")
+                .unwrap();
+        let result = super::search(
+            &pos,
+            &SearchOptions {
+                depth: 2,
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(Some(&result.best_move), result.pv.first());
+        let mut replay = pos;
+        for &mov in &result.pv {
+            assert!(replay.is_legal(mov));
+            replay.make_move(mov);
+        }
+    }
+
+    #[test]
+    fn underpromotion_pruning_keeps_promotions() {
+        // The only way to win is to promote, which mates right away. Underpromotions are still searched at the root,
+        // and promoting to a rook mates too.
+        let pos = Position::from_fen("7k/4P3/6K1/8/8/8/8/8 w - - 0 1").unwrap();
+        let full = super::search(
+            &pos,
+            &SearchOptions {
+                depth: 3,
+                ..Default::default()
+            },
+        );
+        let pruned = super::search(
+            &pos,
+            &SearchOptions {
+                depth: 3,
+                underpromotion_pruning: true,
+                ..Default::default()
+            },
+        );
+
+        assert!(pruned.best_move.is_promotion());
+        assert_eq!(full.best_score, pruned.best_score);
+        assert!(pruned.stats.nodes_searched < full.stats.nodes_searched);
+    }
+
+    #[test]
+    fn futility_pruning_skips_hopeless_quiet_moves() {
+        // White is down a rook with no captures available, so no quiet move at the frontier can raise alpha.
+        let pos = Position::from_fen("r3k3/pppp4/8/8/8/8/PPPP4/4K3 w - - 0 1").unwrap();
+        let (alpha, beta) = (Value::new(0), Value::new(100));
+        let frontier = |futility_depth: u32| {
+            let options = SearchOptions {
+                depth: 2,
+                futility_depth,
+                futility_margin: DEFAULT_FUTILITY_MARGIN,
+                use_tt: false,
+                ..Default::default()
+            };
+
+            let mut searcher = Searcher::new(&options);
+            let value = searcher.alpha_beta(&pos, alpha, beta, 1);
+            (value, searcher.nodes_searched)
+        };
+
+        let (unpruned_value, unpruned_nodes) = frontier(0);
+        let (pruned_value, pruned_nodes) = frontier(1);
+        assert_eq!(unpruned_value, pruned_value);
+        assert!(pruned_nodes < unpruned_nodes);
+    }
+
+    #[test]
+    fn futility_pruning_keeps_tactics() {
+        // The knight can take the undefended queen.
+        let pos = Position::from_fen("4k3/8/8/3q4/8/2N5/8/4K3 w - - 0 1").unwrap();
+        let result = super::search(
+            &pos,
+            &SearchOptions {
+                depth: 3,
+                futility_depth: 1,
+                futility_margin: DEFAULT_FUTILITY_MARGIN,
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(Move::capture(C3, D5), result.best_move);
+    }
+
+    #[test]
+    fn reverse_futility_pruning_when_comfortably_ahead() {
+        // White is up a queen; nothing Black can do in two plies will bring the score back under beta.
+        let pos = Position::from_fen("4k3/pppp4/8/8/8/8/PPPP4/3QK3 w - - 0 1").unwrap();
+        let (alpha, beta) = (Value::new(0), Value::new(100));
+        let shallow = |reverse_futility_depth: u32| {
+            let options = SearchOptions {
+                depth: 3,
+                reverse_futility_depth,
+                reverse_futility_margin: DEFAULT_REVERSE_FUTILITY_MARGIN,
+                use_tt: false,
+                ..Default::default()
+            };
+
+            let mut searcher = Searcher::new(&options);
+            let value = searcher.alpha_beta(&pos, alpha, beta, 2);
+            (value, searcher.nodes_searched)
+        };
+
+        let (unpruned_value, unpruned_nodes) = shallow(0);
+        let (pruned_value, pruned_nodes) = shallow(2);
+        assert!(unpruned_value >= beta);
+        assert!(pruned_value >= beta);
+        assert!(pruned_nodes < unpruned_nodes);
+    }
+
+    #[test]
+    fn reverse_futility_pruning_keeps_tactics() {
+        // The rook can take the undefended queen.
+        let pos = Position::from_fen("4k3/8/8/8/3q4/8/8/3RK3 w - - 0 1").unwrap();
+        let result = super::search(
+            &pos,
+            &SearchOptions {
+                depth: 3,
+                reverse_futility_depth: 2,
+                reverse_futility_margin: DEFAULT_REVERSE_FUTILITY_MARGIN,
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(Move::capture(D1, D4), result.best_move);
+    }
+
+    #[test]
+    fn disabling_the_table_keeps_the_result() {
+        let pos = Position::from_fen("6k1/5ppp/8/8/8/2n5/8/R5K1 w - - 0 1").unwrap();
+        let search = |use_tt| {
+            super::search(
+                &pos,
+                &SearchOptions {
+                    depth: 3,
+                    use_tt,
+                    ..Default::default()
+                },
+            )
+        };
+
+        let with_table = search(true);
+        let without_table = search(false);
+        assert_eq!(Move::quiet(A1, A8), without_table.best_move);
+        assert_eq!(with_table.best_move, without_table.best_move);
+        assert_eq!(with_table.best_score, without_table.best_score);
+        assert_eq!(Some(&without_table.best_move), without_table.pv.first());
+        let mut replay = pos.clone();
+        for &mov in &without_table.pv {
+            assert!(replay.is_legal(mov));
+            replay.make_move(mov);
+        }
+    }
+
+    #[test]
+    fn ties_are_broken_by_uci_order() {
+        let pos = Position::from_fen("4k3/pppppppp/8/8/8/8/PPPPPPPP/4K3 w - - 0 1").unwrap();
+        let ranked = super::analyze(&pos, 2);
+        assert_eq!(ranked[0].1, ranked[1].1);
+        assert_eq!(Move::double_pawn_push(D2, D4), ranked[0].0);
+
+        for use_tt in [true, true, false] {
+            let result = super::search(
+                &pos,
+                &SearchOptions {
+                    depth: 2,
+                    use_tt,
+                    ..Default::default()
+                },
+            );
+            assert_eq!(ranked[0].0, result.best_move);
+        }
+    }
+
+    #[test]
+    fn root_is_searched_despite_table_entry() {
+        // A deep all-node entry for the root would cut the root off without a best move, if the root consulted the
+        // table for cutoffs.
+        let pos = Position::from_fen("4k3/8/8/8/8/8/3QK3/8 w - - 0 1").unwrap();
+        table::record_all(&pos, 50, -Value::INFINITE);
+        let result = super::search(
+            &pos,
+            &SearchOptions {
+                depth: 2,
+                ..Default::default()
+            },
+        );
+
+        assert!(pos.is_legal(result.best_move));
+    }
+
+    #[test]
+    fn analyze_ranks_mate_first() {
+        let pos = Position::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let ranked = super::analyze(&pos, 2);
+        assert!(ranked.len() > 1);
+
+        let (best_move, best_value) = ranked[0];
+        assert_eq!(Move::quiet(A1, A8), best_move);
+        assert!(matches!(best_value.unpack(), UnpackedValue::MateIn(_)));
+        assert!(ranked.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+    }
+
+    #[test]
+    fn node_limit_is_respected() {
+        let pos = Position::from_start_position();
+        let limit = 5000;
+        let result = super::search(
+            &pos,
+            &SearchOptions {
+                depth: 20,
+                node_limit: Some(limit),
+                ..Default::default()
+            },
+        );
+
+        // Every open frame of the search returns as soon as it notices that the limit has been hit, so the search can
+        // only overshoot by the single node that tripped the limit.
+        assert!(result.stats.nodes_searched >= limit);
+        assert!(result.stats.nodes_searched <= limit + 1);
+        assert!(!result.best_move.is_null());
+    }
+
+    #[test]
+    fn stop_token_ends_search_promptly() {
+        let pos = Position::from_start_position();
+        let token = StopToken::new();
+        let options = SearchOptions {
+            depth: 64,
+            hard_stop: Some(token.clone()),
+            ..Default::default()
+        };
+
+        let start = Instant::now();
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                std::thread::sleep(Duration::from_millis(100));
+                token.stop();
+            });
+
+            super::search(&pos, &options);
+        });
+
+        // Without the stop, a depth-64 search with no pruning would run for (much) longer than anyone's patience.
+        assert!(token.is_stopped());
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn interior_nodes_are_counted() {
+        let pos = Position::from_start_position();
+        let options = SearchOptions {
+            depth: 2,
+            use_tt: false,
+            ..Default::default()
+        };
+
+        let mut searcher = Searcher::new(&options);
+        searcher.alpha_beta(&pos, Value::mated_in(0), Value::mate_in(0), 2);
+
+        let mut root_moves = Vec::new();
+        crate::movegen::generate_moves(pos.side_to_move(), &pos, &mut root_moves);
+        assert!(searcher.nodes_searched > root_moves.len() as u64);
+    }
+
+    #[test]
+    fn excluded_root_moves_are_skipped() {
+        // White's only good move is to take the hanging queen.
+        let pos = Position::from_fen("4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1").unwrap();
+        let options = SearchOptions {
+            depth: 2,
+            ..Default::default()
+        };
+
+        let mut searcher = Searcher::new(&options);
+        let capture = Move::capture(D1, D5);
+        let (best, _, pv) = searcher.search_excluding(&pos, 2, &[]).unwrap();
+        assert_eq!(capture, best);
+        assert_eq!(Some(&capture), pv.first());
+
+        let (second, _, _) = searcher.search_excluding(&pos, 2, &[capture]).unwrap();
+        assert_ne!(capture, second);
+    }
+
+    #[test]
+    fn contempt_scores_draws_for_the_root_side() {
+        // Black is stalemated.
+        let pos = Position::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        let options = SearchOptions {
+            depth: 1,
+            contempt: 25,
+            use_tt: false,
+            ..Default::default()
+        };
+
+        let mut searcher = Searcher::new(&options);
+        let value = searcher.alpha_beta(&pos, Value::mated_in(0), Value::mate_in(0), 1);
+        assert_eq!(Value::new(-25), value);
+    }
+
+    #[test]
+    fn search_is_repeatable() {
+        let pos = Position::from_start_position();
+        let options = SearchOptions {
+            depth: 3,
+            see_pruning: true,
+            delta_pruning: true,
+            use_tt: false,
+            ..Default::default()
+        };
+
+        let search = || {
+            let mut searcher = Searcher::new(&options);
+            let value = searcher.alpha_beta(&pos, Value::mated_in(0), Value::mate_in(0), 3);
+            (value, searcher.nodes_searched)
+        };
+
+        assert_eq!(search(), search());
+    }
+
+    #[test]
+    fn root_moves_restrict_the_root() {
+        // Taking the queen is by far the best move, but it isn't one of the moves that the root may play.
+        let pos = Position::from_fen("4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1").unwrap();
+        let options = SearchOptions {
+            depth: 2,
+            use_tt: false,
+            ..Default::default()
+        };
+
+        let mut searcher = Searcher::new(&options);
+        searcher.root_moves = Some(vec![Move::quiet(E1, F2), Move::quiet(E1, E2)]);
+        let (best_move, _) = searcher.search(&pos, 2).unwrap();
+        assert!(searcher.root_moves.as_ref().unwrap().contains(&best_move));
+        assert_eq!(
+            None,
+            searcher.search_excluding(&pos, 2, &[Move::quiet(E1, F2), Move::quiet(E1, E2)])
+        );
+    }
+
+    #[test]
+    fn move_lists_are_reused() {
+        // Kiwipete, where lists with more than 64 moves are common enough to spill onto the heap.
+        let pos = Position::from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        )
+        .unwrap();
+        let options = SearchOptions {
+            depth: 2,
+            see_pruning: true,
+            delta_pruning: true,
+            use_tt: false,
+            ..Default::default()
+        };
+
+        // A searcher whose pool is already full of used lists searches exactly like a fresh one.
+        let mut fresh = Searcher::new(&options);
+        let fresh_value = fresh.alpha_beta(&pos, Value::mated_in(0), Value::mate_in(0), 2);
+        let mut reused = Searcher::new(&options);
+        reused.alpha_beta(&pos, Value::mated_in(0), Value::mate_in(0), 2);
+        let nodes_before = reused.nodes_searched;
+        let reused_value = reused.alpha_beta(&pos, Value::mated_in(0), Value::mate_in(0), 2);
+        assert_eq!(fresh_value, reused_value);
+        assert_eq!(fresh.nodes_searched, reused.nodes_searched - nodes_before);
+
+        // Every list went back to the pool, which holds no more lists than there can be `alpha_beta` and `quiesce`
+        // frames on one line.
+        let max_lines = (options.depth + 1 + options.max_qsearch_depth + 1) as usize;
+        assert!(!reused.move_lists.is_empty());
+        assert!(reused.move_lists.len() <= max_lines);
+        assert!(reused.move_lists.iter().all(|moves| moves.is_empty()));
+    }
+
+    #[derive(Debug, Default)]
+    struct CapturingSink {
+        infos: RefCell<Vec<SearchInfo>>,
+        best_moves: RefCell<Vec<Move>>,
+    }
+
+    impl InfoSink for CapturingSink {
+        fn info(&self, info: &SearchInfo) {
+            self.infos.borrow_mut().push(info.clone());
+        }
+
+        fn best_move(&self, mov: Move) {
+            self.best_moves.borrow_mut().push(mov);
+        }
+    }
+
+    #[test]
+    fn mate_search_finds_mate_in_two() {
+        // Ra6 bxa6 b7#, or Ra6 with anything else and Rxa7#.
+        let pos = Position::from_fen("kbK5/pp6/1P6/8/8/8/8/R7 w - - 0 1").unwrap();
+        let sink = CapturingSink::default();
+        let result = super::search(
+            &pos,
+            &SearchOptions {
+                mate: Some(2),
+                info_sink: Some(&sink),
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(Move::quiet(A1, A6), result.best_move);
+        assert_eq!(Value::mate_in(3), result.best_score);
+        assert_eq!(3, result.pv.len());
+        let mut end = pos;
+        for &mov in &result.pv {
+            end.make_move(mov);
+        }
+        assert_eq!(GameStatus::Checkmate, end.status());
+        assert_eq!(vec![Move::quiet(A1, A6)], *sink.best_moves.borrow());
+        assert_eq!(result.pv, sink.infos.borrow().last().unwrap().pv);
+    }
+
+    #[test]
+    fn mate_search_reports_no_mate() {
+        let pos = Position::from_fen("kbK5/pp6/1P6/8/8/8/8/R7 w - - 0 1").unwrap();
+        let result = super::search(
+            &pos,
+            &SearchOptions {
+                mate: Some(1),
+                ..Default::default()
+            },
+        );
+
+        assert!(result.best_move.is_null());
+        assert!(!result.best_score.is_mate());
+        assert!(result.pv.is_empty());
+    }
+
+    #[test]
+    fn progress_is_reported_to_the_sink() {
+        // White mates with Qg7.
+        let pos = Position::from_fen("7k/8/5QK1/8/8/8/8/8 w - - 0 1").unwrap();
+        let sink = CapturingSink::default();
+        let result = super::search(
+            &pos,
+            &SearchOptions {
+                depth: 3,
+                info_sink: Some(&sink),
+                ..Default::default()
+            },
+        );
+
+        let infos = sink.infos.borrow();
+        let depths: Vec<_> = infos.iter().map(|info| info.depth).collect();
+        assert_eq!(vec![1, 2, 3], depths);
+        for info in infos.iter() {
+            assert!(info.seldepth >= info.depth);
+            assert_eq!(None, info.multi_pv);
+            assert_eq!(Some(&result.best_move), info.pv.first());
+        }
+
+        assert_eq!(vec![result.best_move], *sink.best_moves.borrow());
+        assert_eq!(Move::quiet(F6, G7), result.best_move);
+    }
+
+    #[test]
+    fn multi_pv_lines_do_not_need_a_sink() {
+        let pos = Position::from_fen("4k3/8/8/3p4/4P3/8/8/R3K3 w Q - 0 1").unwrap();
+        let search = |sink: Option<&CapturingSink>| {
+            super::search(
+                &pos,
+                &SearchOptions {
+                    depth: 2,
+                    multi_pv: 3,
+                    info_sink: sink.map(|sink| sink as &dyn InfoSink),
+                    ..Default::default()
+                },
+            )
+        };
+
+        let result = search(None);
+        assert_eq!(2, result.other_pvs.len());
+        let mut first_moves = vec![result.best_move];
+        for (_, pv) in &result.other_pvs {
+            assert!(!first_moves.contains(&pv[0]));
+            first_moves.push(pv[0]);
+        }
+
+        // The lines reported to a sink are the ones that the search returns.
+        let sink = CapturingSink::default();
+        let result = search(Some(&sink));
+        let infos = sink.infos.borrow();
+        let reported: Vec<_> = infos[infos.len() - 2..]
+            .iter()
+            .map(|info| (info.score, info.pv.clone()))
+            .collect();
+        assert_eq!(result.other_pvs, reported);
+    }
+
+    #[test]
+    fn checkmated_root() {
+        let pos = Position::from_fen("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        let result = super::search(
+            &pos,
+            &SearchOptions {
+                depth: 3,
+                ..Default::default()
+            },
+        );
+
+        assert!(result.best_move.is_null());
+        assert!(result.best_score.is_mate());
+        assert!(result.best_score < Value::DRAW);
+        assert!(result.pv.is_empty());
+    }
+
+    #[test]
+    fn stalemated_root() {
+        let pos = Position::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        let result = super::search(
+            &pos,
+            &SearchOptions {
+                depth: 3,
+                ..Default::default()
+            },
+        );
+
+        assert!(result.best_move.is_null());
+        assert_eq!(Value::DRAW, result.best_score);
+    }
+
+    #[test]
+    fn quiescence_depth_is_capped() {
+        // Both sides pile up on d5, so there's a long sequence of captures and recaptures to resolve.
+        let pos =
+            Position::from_fen("3r2k1/3r4/1nq1bn2/3p4/5N2/1BN5/Q2R4/3R2K1 w - - 0 1").unwrap();
+        let seldepth = |max_qsearch_depth| {
+            let options = SearchOptions {
+                max_qsearch_depth,
+                use_tt: false,
+                ..Default::default()
+            };
+            let mut searcher = Searcher::new(&options);
+            searcher.quiesce(&pos, Value::mated_in(0), Value::mate_in(0), 0);
+            searcher.seldepth
+        };
+
+        assert!(seldepth(super::DEFAULT_MAX_QSEARCH_DEPTH) > 2);
+        assert_eq!(2, seldepth(2));
+        assert_eq!(0, seldepth(0));
+    }
+}
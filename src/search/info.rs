@@ -0,0 +1,73 @@
+//! Progress reports from the search.
+//!
+//! The search doesn't write anything itself. Instead, it sends structured reports to an `InfoSink`, which decides what
+//! to do with them. The UCI driver uses `UciInfoSink`, which turns them into `info` and `bestmove` lines on standard
+//! out; library users can capture them however they like.
+
+use std::{fmt, time::Duration};
+
+use crate::{core::Move, eval::Value};
+
+/// A report on a completed iteration of the search.
+#[derive(Clone, Debug)]
+pub struct SearchInfo {
+    /// The depth of the iteration.
+    pub depth: u32,
+    /// The deepest ply that the iteration reached, including quiescence search.
+    pub seldepth: u32,
+    /// The index of the line being reported, starting at 1, if more than one line is being reported.
+    pub multi_pv: Option<usize>,
+    /// The score of the line, from the perspective of the side to move.
+    pub score: Value,
+    /// The number of nodes searched by the iteration.
+    pub nodes: u64,
+    /// The number of nodes searched per second by the iteration.
+    pub nps: u64,
+    /// The time spent on the iteration.
+    pub time: Duration,
+    /// The principal variation of the line, starting with its first move.
+    pub pv: Vec<Move>,
+}
+
+/// Receives progress reports from the search.
+pub trait InfoSink: fmt::Debug {
+    /// Called after each completed iteration of the search, once for each line being reported.
+    fn info(&self, info: &SearchInfo);
+
+    /// Called once, when the search is finished, with the best move that it found.
+    fn best_move(&self, mov: Move);
+}
+
+/// Writes search reports to standard out as UCI `info` and `bestmove` lines.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct UciInfoSink;
+
+impl InfoSink for UciInfoSink {
+    fn info(&self, info: &SearchInfo) {
+        let pv_str = info
+            .pv
+            .iter()
+            .map(|mov| mov.as_uci())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let multi_pv_str = info
+            .multi_pv
+            .map(|line| format!(" multipv {}", line))
+            .unwrap_or_default();
+        uci_output!(
+            "info depth {} seldepth {}{} nodes {} nps {} time {} pv {} score {}",
+            info.depth,
+            info.seldepth,
+            multi_pv_str,
+            info.nodes,
+            info.nps,
+            info.time.as_millis(),
+            pv_str,
+            info.score.as_uci(),
+        );
+    }
+
+    fn best_move(&self, mov: Move) {
+        uci_output!("bestmove {}", mov.as_uci());
+    }
+}
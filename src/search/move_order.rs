@@ -5,12 +5,7 @@
 //! that are most likely to be good are searched first, so that the alpha-beta search can cutoff the remaining nodes
 //! as quickly as possible.
 
-use std::cmp::max;
-
-use crate::{
-    core::{Move, PieceKind, Square},
-    position::Position,
-};
+use crate::{core::Move, position::Position};
 
 /// Performs move ordering for a list of legal moves from a given position. Move ordering is crucial
 /// for alpha-beta search. It is our best defense against combinatorial explosion of the state space
@@ -43,7 +38,7 @@ pub fn order_moves(pos: &Position, moves: &mut [Move]) {
             };
             return captured_piece_value
                 + promotion_value
-                + static_exchange_evaluation(&child_pos, mov.destination());
+                + child_pos.static_exchange_evaluation(mov.destination());
         }
 
         // Things that aren't captures have a weight of zero.
@@ -121,37 +116,6 @@ fn partition_by<F: FnMut(Move) -> bool>(
     moves.split_at_mut(i)
 }
 
-fn static_exchange_evaluation(pos: &Position, target: Square) -> i32 {
-    let mut value = 0;
-    if let Some(attacker) = smallest_attacker(pos, target) {
-        let target_piece = pos.piece_at(target).unwrap();
-        let child = pos.clone_and_make_move(Move::capture(attacker, target));
-        // The term may be negative, which indicates an unprofitable recapture. We must assume that our opponent won't
-        // do that.
-        value = max(
-            target_piece.kind.value() - static_exchange_evaluation(&child, target),
-            0,
-        );
-    }
-
-    value
-}
-
-fn smallest_attacker(pos: &Position, target: Square) -> Option<Square> {
-    let attackers = pos.squares_attacking(pos.side_to_move(), target);
-    if attackers.is_empty() {
-        return None;
-    }
-
-    let mut values: Vec<(Square, PieceKind)> = attackers
-        .into_iter()
-        .map(|sq| (sq, pos.piece_at(sq).unwrap().kind))
-        .collect();
-
-    values.sort_by_key(|(_, kind)| kind.value());
-    return values.first().map(|(sq, _)| sq).cloned();
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -174,7 +138,7 @@ mod tests {
     fn see_pawn_exchange_bad_for_player() {
         let pos = Position::from_fen("8/6p1/1R3b2/8/8/2B5/8/5r2 w - - 0 1").unwrap();
         // White to move, white threatens f6 and initiates an exchange.
-        let predicted_yield = static_exchange_evaluation(&pos, F6);
+        let predicted_yield = pos.static_exchange_evaluation(F6);
 
         // White trades a bishop and a rook (8) for a pawn and a bishop (4), a loss of 4. SEE of this is zero,
         // indicating that the capture is not profitable.
@@ -185,7 +149,7 @@ mod tests {
     fn see_exchange_good_for_player() {
         let pos = Position::from_fen("8/r2q4/8/8/6B1/8/3Q4/8 w - - 0 1").unwrap();
         // White to move, white threatens Bxd7 and initiates an exchange.
-        let predicted_yield = static_exchange_evaluation(&pos, D7);
+        let predicted_yield = pos.static_exchange_evaluation(D7);
 
         // White trades a bishop (3) for a queen and a rook (14), for a win of 11.
         //
@@ -197,7 +161,7 @@ mod tests {
     #[test]
     fn see_stands_pat_if_faced_with_bad_exchange() {
         let pos = Position::from_fen("8/2q5/8/4p3/3P4/5N2/8/8 w - - 0 1").unwrap();
-        let predicted_yield = static_exchange_evaluation(&pos, E5);
+        let predicted_yield = pos.static_exchange_evaluation(E5);
 
         // Black has the option to recapture the pawn with the queen, but would never do that because it immediately
         // blunders the queen.
@@ -207,7 +171,7 @@ mod tests {
     #[test]
     fn see_exchange_queen() {
         let pos = Position::from_fen("5b2/8/3r2r1/2P5/5B2/8/3Q4/8 w - - 0 1").unwrap();
-        let predicted_yield = static_exchange_evaluation(&pos, D6);
+        let predicted_yield = pos.static_exchange_evaluation(D6);
 
         // Rook (5) - Pawn (1) + Rook (5) - Bishop (3) + Bishop(3) = 9
         //
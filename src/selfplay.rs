@@ -0,0 +1,239 @@
+// Copyright 2022 Sean Gillespie.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A self-play harness, for testing changes to a4 by playing it against itself.
+//!
+//! Games are played to the end under the rules of chess (see [`Position::status`]), but can also be adjudicated early
+//! when the outcome is clear: a side resigns once its own evaluation has been hopeless for long enough, and a game is
+//! drawn once both sides have agreed for long enough that it's level.
+
+use crate::{
+    core::{Color, Move},
+    eval::Value,
+    position::{GameStatus, Position},
+    search::{self, SearchOptions},
+};
+
+/// Thresholds for ending a game before the rules of chess do.
+#[derive(Clone, Debug)]
+pub struct Adjudication {
+    /// A side resigns when its own evaluation is at or below minus this many centipawns...
+    pub resign_threshold: Option<i16>,
+    /// ...for this many of its moves in a row.
+    pub resign_moves: u32,
+    /// The game is drawn when the evaluation is within this many centipawns of even...
+    pub draw_threshold: Option<i16>,
+    /// ...for this many plies in a row...
+    pub draw_plies: u32,
+    /// ...once this many plies have been played.
+    pub draw_min_plies: u32,
+    /// The game is drawn once this many plies have been played.
+    pub max_plies: u32,
+}
+
+impl Default for Adjudication {
+    fn default() -> Self {
+        Adjudication {
+            resign_threshold: Some(1000),
+            resign_moves: 3,
+            draw_threshold: Some(10),
+            draw_plies: 20,
+            draw_min_plies: 80,
+            max_plies: 400,
+        }
+    }
+}
+
+/// The outcome of a game.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Outcome {
+    WhiteWins,
+    BlackWins,
+    Draw,
+}
+
+/// Why a game ended.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Termination {
+    /// The game ended under the rules of chess, with the given (non-ongoing) status.
+    Rules(GameStatus),
+    /// The losing side resigned.
+    Resignation,
+    /// Both sides' evaluations were level for long enough.
+    DrawAdjudication,
+    /// The game reached `Adjudication::max_plies`.
+    MoveLimit,
+}
+
+/// The result of a self-play game.
+#[derive(Clone, Debug)]
+pub struct GameResult {
+    pub outcome: Outcome,
+    pub termination: Termination,
+    /// The moves played, starting with White's first move.
+    pub moves: Vec<Move>,
+}
+
+/// Plays a game from the starting position, with White and Black searching with the given options, using the default
+/// adjudication thresholds.
+pub fn play_game(white_opts: &SearchOptions, black_opts: &SearchOptions) -> GameResult {
+    play_game_from(
+        Position::from_start_position(),
+        white_opts,
+        black_opts,
+        &Adjudication::default(),
+    )
+}
+
+/// Plays a game from the given position, with White and Black searching with the given options.
+pub fn play_game_from(
+    mut pos: Position,
+    white_opts: &SearchOptions,
+    black_opts: &SearchOptions,
+    adjudication: &Adjudication,
+) -> GameResult {
+    let mut moves = vec![];
+    // Number of consecutive moves that each side has made while evaluating its position as lost.
+    let mut hopeless_moves = [0u32; 2];
+    // Number of consecutive plies whose evaluation was level.
+    let mut level_plies = 0;
+    let result = |outcome, termination, moves| GameResult {
+        outcome,
+        termination,
+        moves,
+    };
+
+    loop {
+        let status = pos.status();
+        match status {
+            GameStatus::Ongoing => {}
+            GameStatus::Checkmate => {
+                return result(
+                    win_for(pos.side_to_move().toggle()),
+                    Termination::Rules(status),
+                    moves,
+                )
+            }
+            _ => return result(Outcome::Draw, Termination::Rules(status), moves),
+        }
+
+        if moves.len() as u32 >= adjudication.max_plies {
+            return result(Outcome::Draw, Termination::MoveLimit, moves);
+        }
+
+        let side = pos.side_to_move();
+        let opts = match side {
+            Color::White => white_opts,
+            Color::Black => black_opts,
+        };
+        let search_result = search::search(&pos, opts);
+        let score = search_result.best_score;
+
+        if let Some(threshold) = adjudication.resign_threshold {
+            if score <= Value::new(-threshold) {
+                hopeless_moves[side as usize] += 1;
+                if hopeless_moves[side as usize] >= adjudication.resign_moves {
+                    return result(win_for(side.toggle()), Termination::Resignation, moves);
+                }
+            } else {
+                hopeless_moves[side as usize] = 0;
+            }
+        }
+
+        if let Some(threshold) = adjudication.draw_threshold {
+            if Value::new(-threshold) <= score && score <= Value::new(threshold) {
+                level_plies += 1;
+            } else {
+                level_plies = 0;
+            }
+
+            if level_plies >= adjudication.draw_plies
+                && moves.len() as u32 >= adjudication.draw_min_plies
+            {
+                return result(Outcome::Draw, Termination::DrawAdjudication, moves);
+            }
+        }
+
+        pos.make_move(search_result.best_move);
+        moves.push(search_result.best_move);
+    }
+}
+
+fn win_for(color: Color) -> Outcome {
+    match color {
+        Color::White => Outcome::WhiteWins,
+        Color::Black => Outcome::BlackWins,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Adjudication, Outcome, Termination};
+    use crate::{
+        position::{GameStatus, Position},
+        search::SearchOptions,
+    };
+
+    fn shallow() -> SearchOptions<'static> {
+        SearchOptions {
+            depth: 2,
+            see_pruning: true,
+            delta_pruning: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn short_game_terminates() {
+        let adjudication = Adjudication {
+            max_plies: 12,
+            ..Default::default()
+        };
+        let result = super::play_game_from(
+            Position::from_start_position(),
+            &shallow(),
+            &shallow(),
+            &adjudication,
+        );
+
+        assert!(result.moves.len() <= 12);
+        if result.termination == Termination::MoveLimit {
+            assert_eq!(Outcome::Draw, result.outcome);
+        }
+    }
+
+    #[test]
+    fn checkmate_ends_the_game() {
+        // White mates with Qg7.
+        let pos = Position::from_fen("7k/8/5QK1/8/8/8/8/8 w - - 0 1").unwrap();
+        let adjudication = Adjudication {
+            resign_threshold: None,
+            ..Default::default()
+        };
+        let result = super::play_game_from(pos, &shallow(), &shallow(), &adjudication);
+        assert_eq!(Outcome::WhiteWins, result.outcome);
+        assert_eq!(
+            Termination::Rules(GameStatus::Checkmate),
+            result.termination
+        );
+    }
+
+    #[test]
+    fn hopeless_side_resigns() {
+        // Black has a lone king against a queen and rook.
+        let pos = Position::from_fen("4k3/8/8/8/8/8/8/QR2K3 b - - 0 1").unwrap();
+        let adjudication = Adjudication {
+            resign_moves: 1,
+            ..Default::default()
+        };
+        let result = super::play_game_from(pos, &shallow(), &shallow(), &adjudication);
+        assert_eq!(Outcome::WhiteWins, result.outcome);
+        assert_eq!(Termination::Resignation, result.termination);
+        assert!(result.moves.is_empty());
+    }
+}
@@ -6,7 +6,46 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use std::{fmt, sync::LazyLock};
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        LazyLock, Mutex, MutexGuard, PoisonError, RwLock,
+    },
+};
+
+use crate::{core::Move, eval::Value, position::Position};
+
+/// Number of entries in each bucket of the table.
+const BUCKET_SIZE: usize = 4;
+
+/// Default size of the table, in megabytes.
+pub const DEFAULT_TABLE_MB: usize = 32;
+
+/// A read-only copy of an entry in the transposition table.
+pub struct Entry(TableEntry);
+
+impl Entry {
+    pub fn best_move(&self) -> Option<Move> {
+        self.0.best_move
+    }
+
+    pub fn depth(&self) -> u32 {
+        self.0.depth
+    }
+
+    pub fn kind(&self) -> NodeKind {
+        self.0.node
+    }
+}
+
+impl fmt::Debug for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+type Bucket = [Option<TableEntry>; BUCKET_SIZE];
 
 /// A4's transposition table, which is responsible for memoizing search results
 /// for individual positions.
@@ -34,100 +73,156 @@ use std::{fmt, sync::LazyLock};
 ///     moves were searched in this position and no move was good enough to exceed the alpha. This implies that a
 ///     a sibling node is a better move and this node does not need to be searched any deeper.
 ///
-use chashmap::{CHashMap, ReadGuard};
-
-use crate::{core::Move, eval::Value, position::Position};
-
-/// A read-only reference to an entry in the transposition table.
-pub struct Entry<'a>(ReadGuard<'a, u64, TableEntry>);
+/// # Layout
+/// The table is a fixed number of buckets, each holding `BUCKET_SIZE` entries. A position can only be stored in the
+/// bucket selected by the low bits of its Zobrist hash; each entry keeps the high bits of the hash so that `query`
+/// can tell the position apart from others that share the bucket. When a bucket is full, a new entry replaces the
+/// entry least worth keeping: one left over from a previous search, and otherwise the shallowest.
+struct Table {
+    buckets: Vec<Mutex<Bucket>>,
+    /// The current search generation. Entries written during earlier searches are the first to be replaced.
+    generation: AtomicU8,
+}
 
-impl<'a> Entry<'a> {
-    pub fn best_move(&self) -> Option<Move> {
-        self.0.best_move
+impl Table {
+    /// Creates a table that uses at most the given number of megabytes, and at least one bucket.
+    fn with_megabytes(megabytes: usize) -> Table {
+        let bytes = megabytes.saturating_mul(1024 * 1024);
+        let count = (bytes / std::mem::size_of::<Mutex<Bucket>>()).max(1);
+        // Round down to a power of two, so that a bucket can be selected by masking the hash.
+        let count = 1 << (usize::BITS - 1 - count.leading_zeros());
+        Table::with_buckets(count)
     }
 
-    pub fn depth(&self) -> u32 {
-        self.0.depth
+    fn with_buckets(count: usize) -> Table {
+        debug_assert!(count.is_power_of_two());
+        Table {
+            buckets: (0..count)
+                .map(|_| Mutex::new([None; BUCKET_SIZE]))
+                .collect(),
+            generation: AtomicU8::new(0),
+        }
     }
 
-    pub fn kind(&self) -> NodeKind {
-        self.0.node
+    /// Locks the bucket that the given key belongs to.
+    fn bucket(&self, key: u64) -> MutexGuard<'_, Bucket> {
+        lock(&self.buckets[key as usize & (self.buckets.len() - 1)])
     }
-}
 
-impl fmt::Debug for Entry<'_> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        self.0.fmt(f)
-    }
-}
+    /// Stores an entry for the given key. `replace` decides whether the entry replaces an existing entry for the same
+    /// key; an entry for a new key always goes in, evicting the least valuable entry if the bucket is full.
+    fn store(
+        &self,
+        key: u64,
+        best_move: Option<Move>,
+        depth: u32,
+        node: NodeKind,
+        replace: impl Fn(&TableEntry) -> bool,
+    ) {
+        let entry = TableEntry {
+            verification: verification_key(key),
+            best_move,
+            depth,
+            node,
+            generation: self.generation.load(Ordering::Relaxed),
+        };
 
-struct Table {
-    map: CHashMap<u64, TableEntry>,
-}
+        let mut bucket = self.bucket(key);
+        if let Some(slot) = bucket
+            .iter_mut()
+            .flatten()
+            .find(|existing| existing.verification == entry.verification)
+        {
+            if replace(slot) {
+                *slot = entry;
+            }
 
-impl Table {
-    fn new() -> Table {
-        Table {
-            map: CHashMap::new(),
+            return;
+        }
+
+        if let Some(slot) = bucket.iter_mut().find(|slot| slot.is_none()) {
+            *slot = Some(entry);
+            return;
         }
+
+        let victim = bucket
+            .iter_mut()
+            .min_by_key(|slot| {
+                let existing = slot.as_ref().unwrap();
+                (existing.generation == entry.generation, existing.depth)
+            })
+            .unwrap();
+        *victim = Some(entry);
+    }
+
+    fn probe(&self, key: u64) -> Option<Entry> {
+        let bucket = self.bucket(key);
+        bucket
+            .iter()
+            .flatten()
+            .find(|entry| entry.verification == verification_key(key))
+            .map(|entry| Entry(*entry))
     }
 
     fn record_pv(&self, pos: &Position, best_move: Move, depth: u32, value: Value) {
-        let key = pos.zobrist_hash();
-        let entry = TableEntry {
-            zobrist_key: key,
-            best_move: Some(best_move),
+        self.store(
+            pos.zobrist_hash(),
+            Some(best_move),
             depth,
-            node: NodeKind::PV(value),
-        };
-
-        self.map.insert(key, entry);
+            NodeKind::PV(value),
+            |_| true,
+        );
     }
 
     pub fn record_cut(&self, pos: &Position, best_move: Move, depth: u32, value: Value) {
-        let key = pos.zobrist_hash();
-        let entry = TableEntry {
-            zobrist_key: key,
-            best_move: Some(best_move),
+        self.store(
+            pos.zobrist_hash(),
+            Some(best_move),
             depth,
-            node: NodeKind::Cut(value),
-        };
-
-        self.map.insert(key, entry);
+            NodeKind::Cut(value),
+            |_| true,
+        );
     }
 
     pub fn record_all(&self, pos: &Position, depth: u32, value: Value) {
-        if let Some(existing) = self.map.get(&pos.zobrist_hash()) {
-            if existing.is_all() {
-                if existing.depth > depth {
-                    return;
-                }
-            } else {
-                return;
-            }
-        }
-
-        let key = pos.zobrist_hash();
-        let entry = TableEntry {
-            zobrist_key: key,
-            best_move: None,
+        // An all node doesn't have a best move, so it is worth less than any other kind of entry for this position, or
+        // an all node that was searched more deeply.
+        self.store(
+            pos.zobrist_hash(),
+            None,
             depth,
-            node: NodeKind::All(value),
-        };
+            NodeKind::All(value),
+            |existing| existing.is_all() && existing.depth <= depth,
+        );
+    }
 
-        self.map.insert(key, entry);
+    pub fn query(&self, pos: &Position) -> Option<Entry> {
+        self.probe(pos.zobrist_hash())
     }
 
-    pub fn query(&self, pos: &Position) -> Option<Entry<'_>> {
-        let key = pos.zobrist_hash();
-        self.map.get(&key).map(Entry)
+    pub fn new_search(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
     }
 
     pub fn clear(&self) {
-        self.map.clear();
+        for bucket in &self.buckets {
+            *lock(bucket) = [None; BUCKET_SIZE];
+        }
     }
 }
 
+/// Locks a bucket of the table. A bucket only ever holds whole entries, so it's still valid if a thread panicked while
+/// holding its lock. Recovering from the poisoned lock keeps one panicking search from breaking every search after it.
+fn lock(bucket: &Mutex<Bucket>) -> MutexGuard<'_, Bucket> {
+    bucket.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// The bits of a Zobrist hash that are stored in an entry to tell apart the positions that share a bucket. The low
+/// bits select the bucket, so these are the high bits.
+fn verification_key(key: u64) -> u32 {
+    (key >> 32) as u32
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum NodeKind {
     PV(Value),
@@ -135,12 +230,13 @@ pub enum NodeKind {
     Cut(Value),
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 struct TableEntry {
-    pub zobrist_key: u64,
+    pub verification: u32,
     pub best_move: Option<Move>,
     pub depth: u32,
     pub node: NodeKind,
+    pub generation: u8,
 }
 
 impl TableEntry {
@@ -149,46 +245,85 @@ impl TableEntry {
     }
 }
 
-static TABLE: LazyLock<Table> = LazyLock::new(Table::new);
+static TABLE: LazyLock<RwLock<Table>> =
+    LazyLock::new(|| RwLock::new(Table::with_megabytes(DEFAULT_TABLE_MB)));
 
 pub fn initialize() {
     LazyLock::force(&TABLE);
 }
 
 pub fn clear() {
-    TABLE.clear()
+    TABLE.read().expect("table poisoned").clear()
+}
+
+/// Replaces the table with an empty one that uses at most the given number of megabytes.
+pub fn resize(megabytes: usize) {
+    *TABLE.write().expect("table poisoned") = Table::with_megabytes(megabytes);
 }
 
-pub fn query(pos: &Position) -> Option<Entry<'_>> {
-    TABLE.query(pos)
+/// Notifies the table that a new search is starting. Entries from earlier searches are replaced before entries from
+/// the current one.
+pub fn new_search() {
+    TABLE.read().expect("table poisoned").new_search()
+}
+
+pub fn query(pos: &Position) -> Option<Entry> {
+    TABLE.read().expect("table poisoned").query(pos)
 }
 
 pub fn record_pv(pos: &Position, best_move: Move, depth: u32, value: Value) {
-    TABLE.record_pv(pos, best_move, depth, value);
+    TABLE
+        .read()
+        .expect("table poisoned")
+        .record_pv(pos, best_move, depth, value);
 }
 
 pub fn record_cut(pos: &Position, best_move: Move, depth: u32, value: Value) {
-    TABLE.record_cut(pos, best_move, depth, value);
+    TABLE
+        .read()
+        .expect("table poisoned")
+        .record_cut(pos, best_move, depth, value);
 }
 
 pub fn record_all(pos: &Position, depth: u32, value: Value) {
-    TABLE.record_all(pos, depth, value);
+    TABLE
+        .read()
+        .expect("table poisoned")
+        .record_all(pos, depth, value);
+}
+
+/// Panics while holding the lock on the given position's bucket, as a search that panics partway through updating the
+/// table would.
+#[cfg(test)]
+pub fn panic_while_locked(pos: &Position) {
+    let table = TABLE.read().expect("table poisoned");
+    let _bucket = table.bucket(pos.zobrist_hash());
+    panic!("panicked while holding a table bucket lock");
 }
 
 /// Looks up the principal variation from the given position to the given depth. This is the line that the engine
 /// is pursuing.
+///
+/// The walk stops early if the table leads to a position that has already been visited on the PV, so that
+/// repetitions don't send it around in circles, or if it produces a move that isn't legal due to a hash collision.
 pub fn get_pv(pos: &Position, depth: u32) -> Vec<Move> {
     let mut pv = vec![];
     let mut pv_clone = pos.clone();
+    let mut seen = vec![pv_clone.zobrist_hash()];
     for _ in 0..depth {
         if let Some(best_move) = query(&pv_clone).and_then(|e| e.best_move()) {
             // The PV ends in the end of a game.
-            if best_move.is_null() {
+            if best_move.is_null() || !pv_clone.is_legal(best_move) {
                 break;
             }
 
             pv.push(best_move);
             pv_clone.make_move(best_move);
+            if seen.contains(&pv_clone.zobrist_hash()) {
+                break;
+            }
+
+            seen.push(pv_clone.zobrist_hash());
         } else {
             break;
         }
@@ -196,3 +331,103 @@ pub fn get_pv(pos: &Position, depth: u32) -> Vec<Move> {
 
     pv
 }
+
+#[cfg(test)]
+mod tests {
+    use std::panic::{self, AssertUnwindSafe};
+
+    use super::{NodeKind, Table, BUCKET_SIZE, DEFAULT_TABLE_MB};
+    use crate::{core::*, eval::Value, position::Position};
+
+    fn record(table: &Table, key: u64, depth: u32) {
+        table.store(key, None, depth, NodeKind::PV(Value::new(0)), |_| true);
+    }
+
+    #[test]
+    fn full_bucket_keeps_deepest_entries() {
+        let table = Table::with_buckets(1);
+        let keys: Vec<u64> = (1..=BUCKET_SIZE as u64 * 2).map(|i| i << 32).collect();
+        for (depth, &key) in keys.iter().enumerate() {
+            record(&table, key, depth as u32);
+        }
+
+        // Each new entry evicts the shallowest, so the most recent (and deepest) entries survive.
+        let (evicted, kept) = keys.split_at(BUCKET_SIZE);
+        assert!(evicted.iter().all(|&key| table.probe(key).is_none()));
+        for (i, &key) in kept.iter().enumerate() {
+            assert_eq!((BUCKET_SIZE + i) as u32, table.probe(key).unwrap().depth());
+        }
+
+        // A shallow entry goes in over the shallowest existing entry, not the deepest.
+        record(&table, 100 << 32, 0);
+        assert!(table.probe(kept[0]).is_none());
+        assert!(table.probe(*kept.last().unwrap()).is_some());
+    }
+
+    #[test]
+    fn older_generation_is_replaced_first() {
+        let table = Table::with_buckets(1);
+        for i in 0..BUCKET_SIZE as u64 {
+            record(&table, (i + 1) << 32, 10 + i as u32);
+        }
+
+        table.new_search();
+        record(&table, 100 << 32, 1);
+        record(&table, 101 << 32, 0);
+        assert!(table.probe(100 << 32).is_some());
+        assert!(table.probe(101 << 32).is_some());
+    }
+
+    #[test]
+    fn collision_is_not_a_hit() {
+        let table = Table::with_buckets(4);
+        record(&table, 0x0000_0001_0000_0002, 5);
+
+        // Same bucket, different verification key.
+        assert!(table.probe(0x0000_0002_0000_0002).is_none());
+        assert_eq!(5, table.probe(0x0000_0001_0000_0002).unwrap().depth());
+    }
+
+    #[test]
+    fn all_node_does_not_replace_pv_node() {
+        let table = Table::with_buckets(1);
+        let pos = Position::from_start_position();
+        table.record_pv(&pos, Move::double_pawn_push(E2, E4), 3, Value::new(20));
+        table.record_all(&pos, 8, Value::new(0));
+        assert!(matches!(table.query(&pos).unwrap().kind(), NodeKind::PV(_)));
+    }
+
+    #[test]
+    fn poisoned_bucket_is_still_usable() {
+        let table = Table::with_buckets(1);
+        record(&table, 1 << 32, 3);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let _bucket = table.bucket(0);
+            panic!("panicked while holding a table bucket lock");
+        }));
+        assert!(result.is_err());
+
+        assert_eq!(3, table.probe(1 << 32).unwrap().depth());
+        record(&table, 2 << 32, 5);
+        assert_eq!(5, table.probe(2 << 32).unwrap().depth());
+        table.clear();
+        assert!(table.probe(1 << 32).is_none());
+    }
+
+    #[test]
+    fn table_size_is_a_power_of_two() {
+        for megabytes in [1, 3, DEFAULT_TABLE_MB] {
+            let table = Table::with_megabytes(megabytes);
+            assert!(table.buckets.len().is_power_of_two());
+            assert!(
+                table.buckets.len() * std::mem::size_of_val(&table.buckets[0]) <= megabytes << 20
+            );
+        }
+
+        assert_eq!(
+            1 << 18,
+            Table::with_megabytes(DEFAULT_TABLE_MB).buckets.len()
+        );
+        assert_eq!(1, Table::with_megabytes(0).buckets.len());
+    }
+}
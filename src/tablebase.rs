@@ -0,0 +1,1129 @@
+// Copyright 2022 Sean Gillespie.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Syzygy endgame tablebase probing.
+//!
+//! Syzygy tablebases store the game-theoretic outcome of every position with a small number of pieces. a4 currently
+//! only understands the WDL ("win/draw/loss") tables, which are the `.rtbw` files of a Syzygy set; the DTZ tables,
+//! which are needed to make progress towards a win, are not read.
+//!
+//! The table format is not documented outside of its reference implementation. The decoder here follows that
+//! implementation closely: a table stores a compressed array of outcomes, indexed by a canonical encoding of the
+//! position that folds away board symmetries. Probing consists of computing that index and then decompressing the
+//! single value that lives there.
+
+use std::{
+    cmp,
+    collections::HashMap,
+    fs, io, ops,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        LazyLock, OnceLock, RwLock,
+    },
+};
+
+use crate::{core::*, movegen, position::Position};
+
+/// Magic bytes at the start of every WDL table file.
+const WDL_MAGIC: [u8; 4] = [0x71, 0xE8, 0x23, 0x5D];
+
+/// Maximum number of pieces that can appear in a Syzygy table.
+const MAX_PIECES: usize = 7;
+
+/// Table flag indicating that every position in a table has the same value.
+const SINGLE_VALUE: u8 = 0x80;
+
+/// The outcome of a position, from the perspective of the side to move.
+///
+/// Cursed wins and blessed losses are positions that are won (or lost) with perfect play, but not before the 50-move
+/// rule would declare the game drawn.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Wdl {
+    Loss,
+    BlessedLoss,
+    Draw,
+    CursedWin,
+    Win,
+}
+
+impl Wdl {
+    fn from_table_value(value: u8) -> Option<Wdl> {
+        let wdl = match value {
+            0 => Wdl::Loss,
+            1 => Wdl::BlessedLoss,
+            2 => Wdl::Draw,
+            3 => Wdl::CursedWin,
+            4 => Wdl::Win,
+            _ => return None,
+        };
+
+        Some(wdl)
+    }
+}
+
+impl ops::Neg for Wdl {
+    type Output = Wdl;
+
+    fn neg(self) -> Self::Output {
+        match self {
+            Wdl::Loss => Wdl::Win,
+            Wdl::BlessedLoss => Wdl::CursedWin,
+            Wdl::Draw => Wdl::Draw,
+            Wdl::CursedWin => Wdl::BlessedLoss,
+            Wdl::Win => Wdl::Loss,
+        }
+    }
+}
+
+/// A set of Syzygy tables, discovered from one or more directories on disk. Tables are only read into memory the
+/// first time that they are probed.
+pub struct Tablebase {
+    tables: Vec<Table>,
+    /// Index into `tables` for each material key, e.g. `KQvK`. Every table is registered under both of its keys so
+    /// that it can be found regardless of which color holds which pieces.
+    by_material: HashMap<String, usize>,
+    max_pieces: usize,
+}
+
+impl Tablebase {
+    /// Discovers all WDL tables in the given list of directories, separated by `:`.
+    pub fn new(paths: &str) -> io::Result<Tablebase> {
+        let mut tablebase = Tablebase {
+            tables: vec![],
+            by_material: HashMap::new(),
+            max_pieces: 0,
+        };
+
+        for dir in paths.split(':').filter(|dir| !dir.is_empty()) {
+            for entry in fs::read_dir(dir)? {
+                let path = entry?.path();
+                if path.extension().and_then(|ext| ext.to_str()) != Some("rtbw") {
+                    continue;
+                }
+
+                if let Some(table) = Table::new(&path) {
+                    tablebase.add(table);
+                }
+            }
+        }
+
+        Ok(tablebase)
+    }
+
+    fn add(&mut self, table: Table) {
+        if self.by_material.contains_key(&table.key) {
+            return;
+        }
+
+        let index = self.tables.len();
+        self.max_pieces = cmp::max(self.max_pieces, table.piece_count);
+        self.by_material.insert(table.key.clone(), index);
+        self.by_material.insert(table.flipped_key(), index);
+        self.tables.push(table);
+    }
+
+    /// Returns the largest number of pieces for which this tablebase has a table.
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    /// Probes the WDL tables for the given position, returning its outcome for the side to move. Returns None if the
+    /// position can't be found in the tablebase.
+    pub fn probe_wdl(&self, pos: &Position) -> Option<Wdl> {
+        // Tablebases don't encode castling rights.
+        if colors().any(|color| pos.can_castle_kingside(color) || pos.can_castle_queenside(color)) {
+            return None;
+        }
+
+        if pos.piece_count() as usize > self.max_pieces {
+            return None;
+        }
+
+        self.search(pos)
+    }
+
+    /// Returns the legal moves in the root position that preserve the best outcome available to the side to move,
+    /// along with that outcome. Returns None if the position or any of its children can't be found in the tablebase.
+    ///
+    /// Since only WDL tables are read, every one of these moves is as good as the others as far as the tablebase can
+    /// tell, even though some of them make no progress towards a win. The search has to choose between them.
+    pub fn root_moves(&self, pos: &Position) -> Option<(Vec<Move>, Wdl)> {
+        if pos.piece_count() as usize > self.max_pieces {
+            return None;
+        }
+
+        let mut moves = Vec::new();
+        movegen::generate_moves(pos.side_to_move(), pos, &mut moves);
+        moves.retain(|&m| pos.is_legal_given_pseudolegal(m));
+
+        let mut values = Vec::with_capacity(moves.len());
+        for &mov in &moves {
+            values.push(-self.probe_wdl(&pos.clone_and_make_move(mov))?);
+        }
+
+        let best = values.iter().cloned().max()?;
+        let best_moves = moves
+            .into_iter()
+            .zip(values)
+            .filter(|&(_, value)| value == best)
+            .map(|(mov, _)| mov)
+            .collect();
+        Some((best_moves, best))
+    }
+
+    /// Resolves the value of a position, taking captures into account.
+    ///
+    /// The table generator treats positions where the side to move has a winning capture as "don't care" positions
+    /// and stores whatever value compresses best. It can also store a loss for positions that are drawn by a capture.
+    /// To get an accurate value, we have to probe the results of all captures in addition to the position itself.
+    fn search(&self, pos: &Position) -> Option<Wdl> {
+        let mut moves = Vec::new();
+        movegen::generate_moves(pos.side_to_move(), pos, &mut moves);
+        moves.retain(|&m| pos.is_legal_given_pseudolegal(m));
+
+        let mut best = Wdl::Loss;
+        let mut capture_count = 0;
+        for &mov in moves.iter().filter(|mov| mov.is_capture()) {
+            capture_count += 1;
+            let value = -self.search(&pos.clone_and_make_move(mov))?;
+            if value > best {
+                best = value;
+                if value == Wdl::Win {
+                    return Some(value);
+                }
+            }
+        }
+
+        // If every legal move is a capture, we've already searched everything and the stored value can't be trusted.
+        // Tables don't know about en-passant, for example.
+        if capture_count != 0 && capture_count == moves.len() {
+            return Some(best);
+        }
+
+        Some(cmp::max(best, self.probe_table(pos)?))
+    }
+
+    fn probe_table(&self, pos: &Position) -> Option<Wdl> {
+        // Bare kings aren't stored in a table.
+        if pos.piece_count() == 2 {
+            return Some(Wdl::Draw);
+        }
+
+        let table = &self.tables[*self.by_material.get(&material_key(pos, Color::White))?];
+        table.probe(pos)
+    }
+}
+
+/// A single WDL table file.
+struct Table {
+    path: PathBuf,
+    /// Material key of this table, with the side that the table considers to be White first.
+    key: String,
+    piece_count: usize,
+    has_pawns: bool,
+    /// Whether or not any side has exactly one of a piece that isn't a king.
+    has_unique_pieces: bool,
+    /// Pawn counts for the leading color and the other color. The leading color is the one with fewer pawns, if both
+    /// sides have them.
+    pawn_count: [usize; 2],
+    data: OnceLock<Option<TableData>>,
+}
+
+impl Table {
+    fn new(path: &Path) -> Option<Table> {
+        let key = path.file_stem()?.to_str()?.to_owned();
+        let (white, black) = key.split_once('v')?;
+        let valid_side =
+            |side: &str| side.starts_with('K') && side.chars().all(|c| "KQRBNP".contains(c));
+        if !valid_side(white) || !valid_side(black) || white.len() + black.len() > MAX_PIECES {
+            return None;
+        }
+
+        let count = |side: &str, piece: char| side.chars().filter(|&c| c == piece).count();
+        let has_unique_pieces = "QRBNP"
+            .chars()
+            .any(|piece| count(white, piece) == 1 || count(black, piece) == 1);
+        let (white_pawns, black_pawns) = (count(white, 'P'), count(black, 'P'));
+        let white_leads = black_pawns == 0 || (white_pawns != 0 && black_pawns >= white_pawns);
+        let pawn_count = if white_leads {
+            [white_pawns, black_pawns]
+        } else {
+            [black_pawns, white_pawns]
+        };
+
+        Some(Table {
+            path: path.to_owned(),
+            piece_count: white.len() + black.len(),
+            has_pawns: white_pawns + black_pawns != 0,
+            has_unique_pieces,
+            pawn_count,
+            key,
+            data: OnceLock::new(),
+        })
+    }
+
+    fn flipped_key(&self) -> String {
+        let (white, black) = self.key.split_once('v').unwrap();
+        format!("{}v{}", black, white)
+    }
+
+    fn is_symmetric(&self) -> bool {
+        self.key == self.flipped_key()
+    }
+
+    fn data(&self) -> Option<&TableData> {
+        self.data
+            .get_or_init(|| {
+                let data = self.load();
+                if data.is_none() {
+                    warn!("failed to load tablebase file {}", self.path.display());
+                }
+
+                data
+            })
+            .as_ref()
+    }
+
+    /// Reads and parses the table file. Offsets recorded in the returned [`TableData`] are relative to the start of
+    /// the file.
+    fn load(&self) -> Option<TableData> {
+        let bytes = fs::read(&self.path).ok()?;
+        if bytes.get(0..4)? != WDL_MAGIC {
+            return None;
+        }
+
+        // The byte following the magic encodes whether the table is split by side to move and whether it has pawns;
+        // both are already known from the file name.
+        let flags = *bytes.get(4)?;
+        if (flags & 1 == 0) != self.is_symmetric() || (flags & 2 != 0) != self.has_pawns {
+            return None;
+        }
+
+        let mut items: [[PairsData; 4]; 2] = Default::default();
+        let sides = if self.is_symmetric() { 1 } else { 2 };
+        let files = if self.has_pawns { 4 } else { 1 };
+        let pp = self.has_pawns && self.pawn_count[1] != 0;
+        let mut cursor = 5;
+        let [white_items, black_items] = &mut items;
+        for (file, (white, black)) in white_items
+            .iter_mut()
+            .zip(black_items.iter_mut())
+            .enumerate()
+            .take(files)
+        {
+            let first = *bytes.get(cursor)?;
+            let second = if pp { *bytes.get(cursor + 1)? } else { 0xFF };
+            let order = [[first & 0xF, second & 0xF], [first >> 4, second >> 4]];
+            cursor += 1 + pp as usize;
+            for (white_piece, black_piece) in white
+                .pieces
+                .iter_mut()
+                .zip(black.pieces.iter_mut())
+                .take(self.piece_count)
+            {
+                let byte = *bytes.get(cursor)?;
+                *white_piece = byte & 0xF;
+                *black_piece = byte >> 4;
+                cursor += 1;
+            }
+
+            self.set_groups(white, order[0], file);
+            if sides == 2 {
+                self.set_groups(black, order[1], file);
+            }
+        }
+
+        cursor += cursor & 1;
+        for file in 0..files {
+            for side in items.iter_mut().take(sides) {
+                cursor = side[file].set_sizes(&bytes, cursor)?;
+            }
+        }
+
+        for file in 0..files {
+            for side in items.iter_mut().take(sides) {
+                side[file].sparse_index = cursor;
+                cursor += side[file].sparse_index_size * 6;
+            }
+        }
+
+        for file in 0..files {
+            for side in items.iter_mut().take(sides) {
+                side[file].block_lengths = cursor;
+                cursor += side[file].block_lengths_size * 2;
+            }
+        }
+
+        for file in 0..files {
+            for side in items.iter_mut().take(sides) {
+                // Compressed data is aligned to 64 bytes.
+                cursor = (cursor + 0x3F) & !0x3F;
+                side[file].data = cursor;
+                cursor += side[file].num_blocks * side[file].block_size;
+            }
+        }
+
+        if cursor > bytes.len() {
+            return None;
+        }
+
+        Some(TableData { bytes, items })
+    }
+
+    /// Groups together pieces that are encoded together and computes the multiplier used for each group's index.
+    ///
+    /// A group generally consists of pieces of the same kind and color, except for the leading group. Without pawns,
+    /// the leading group is either the first three pieces (if there is a unique piece) or the two kings. With pawns,
+    /// the leading group is the leading color's pawns. For example, KRvKN is grouped as KRK + N and KPPvKP as
+    /// PP + P + K + K.
+    fn set_groups(&self, d: &mut PairsData, order: [u8; 2], file: usize) {
+        let mut n = 0;
+        let mut first_len: i32 = if self.has_pawns {
+            0
+        } else if self.has_unique_pieces {
+            3
+        } else {
+            2
+        };
+        d.group_len[0] = 1;
+        for i in 1..self.piece_count {
+            first_len -= 1;
+            if first_len > 0 || d.pieces[i] == d.pieces[i - 1] {
+                d.group_len[n] += 1;
+            } else {
+                n += 1;
+                d.group_len[n] = 1;
+            }
+        }
+
+        n += 1;
+        d.group_len[n] = 0;
+
+        // The groups are not necessarily encoded in the order that they appear. The table stores the position of the
+        // leading group in `order[0]` and the position of the other color's pawns, if any, in `order[1]`. The
+        // remaining groups are encoded in order around them.
+        let pp = self.has_pawns && self.pawn_count[1] != 0;
+        let mut next = if pp { 2 } else { 1 };
+        let mut free_squares = 64 - d.group_len[0] - if pp { d.group_len[1] } else { 0 };
+        let mut idx = 1;
+        let mut k = 0;
+        while next < n || k == order[0] as usize || k == order[1] as usize {
+            if k == order[0] as usize {
+                d.group_idx[0] = idx;
+                idx *= if self.has_pawns {
+                    ENCODING.lead_pawns_size[d.group_len[0]][file]
+                } else if self.has_unique_pieces {
+                    31332
+                } else {
+                    462
+                };
+            } else if k == order[1] as usize {
+                d.group_idx[1] = idx;
+                idx *= ENCODING.binomial[d.group_len[1]][48 - d.group_len[0]];
+            } else {
+                d.group_idx[next] = idx;
+                idx *= ENCODING.binomial[d.group_len[next]][free_squares];
+                free_squares -= d.group_len[next];
+                next += 1;
+            }
+
+            k += 1;
+        }
+
+        d.group_idx[n] = idx;
+    }
+
+    /// Looks up the value of a position in this table.
+    fn probe(&self, pos: &Position) -> Option<Wdl> {
+        let data = self.data()?;
+        let mut squares = [0usize; MAX_PIECES];
+        let mut pieces = [0u8; MAX_PIECES];
+        let mut size = 0;
+
+        // Tables are stored with the stronger side as White. If the position has the colors the other way around,
+        // swap the colors and flip the board vertically. Symmetric tables only store positions with White to move,
+        // so they get the same treatment when Black is to move.
+        let flip = material_key(pos, Color::White) != self.key
+            || (self.is_symmetric() && pos.side_to_move() == Color::Black);
+        let flip_color = if flip { 8 } else { 0 };
+        let flip_squares = if flip { 0o70 } else { 0 };
+        let stm = flip as usize ^ pos.side_to_move() as usize;
+
+        // With pawns, the table is split into four tables depending on the file of the leading pawn.
+        let mut lead_pawns = SquareSet::empty();
+        let mut lead_pawns_count = 0;
+        let mut file = 0;
+        if self.has_pawns {
+            let lead_color = if (data.items[0][0].pieces[0] ^ flip_color) & 8 == 0 {
+                Color::White
+            } else {
+                Color::Black
+            };
+
+            lead_pawns = pos.pawns(lead_color);
+            for sq in lead_pawns {
+                squares[size] = sq.as_u8() as usize ^ flip_squares;
+                size += 1;
+            }
+
+            lead_pawns_count = size;
+            let lead = (0..lead_pawns_count).max_by_key(|&i| ENCODING.map_pawns[squares[i]])?;
+            squares.swap(0, lead);
+            file = cmp::min(file_of(squares[0]), file_of(squares[0] ^ 7));
+        }
+
+        let occupancy = pos.pieces(Color::White) | pos.pieces(Color::Black);
+        for sq in occupancy & !lead_pawns {
+            let piece = pos.piece_at(sq)?;
+            squares[size] = sq.as_u8() as usize ^ flip_squares;
+            pieces[size] = table_piece(piece) ^ flip_color;
+            size += 1;
+        }
+
+        // Reorder the pieces into the sequence that the table was encoded with.
+        let d = &data.items[stm][file];
+        for i in lead_pawns_count..size - 1 {
+            for j in i + 1..size {
+                if d.pieces[i] == pieces[j] {
+                    pieces.swap(i, j);
+                    squares.swap(i, j);
+                    break;
+                }
+            }
+        }
+
+        // Flip horizontally so that the leading piece is on files A through D.
+        if file_of(squares[0]) > 3 {
+            for sq in &mut squares[..size] {
+                *sq ^= 7;
+            }
+        }
+
+        let mut idx;
+        if self.has_pawns {
+            idx = ENCODING.lead_pawn_idx[lead_pawns_count][squares[0]];
+            squares[1..lead_pawns_count].sort_by_key(|&sq| ENCODING.map_pawns[sq]);
+            for (i, &sq) in squares.iter().enumerate().take(lead_pawns_count).skip(1) {
+                idx += ENCODING.binomial[i][ENCODING.map_pawns[sq]];
+            }
+        } else {
+            // Without pawns, also flip vertically so that the leading piece is on ranks 1 through 4...
+            if rank_of(squares[0]) > 3 {
+                for sq in &mut squares[..size] {
+                    *sq ^= 0o70;
+                }
+            }
+
+            // ... and along the A1-H8 diagonal, so that the first piece of the leading group that's off of the
+            // diagonal is below it.
+            for i in 0..d.group_len[0] {
+                let off = off_diagonal(squares[i]);
+                if off == 0 {
+                    continue;
+                }
+
+                if off > 0 {
+                    for sq in &mut squares[i..size] {
+                        *sq = ((*sq >> 3) | (*sq << 3)) & 63;
+                    }
+                }
+
+                break;
+            }
+
+            idx = if self.has_unique_pieces {
+                encode_unique_pieces(squares[0], squares[1], squares[2])
+            } else {
+                ENCODING.map_kk[ENCODING.map_a1d1d4[squares[0]]][squares[1]]
+            };
+        }
+
+        // Encode the remaining groups. Each square is mapped down by the number of squares already occupied by earlier
+        // groups that precede it.
+        idx *= d.group_idx[0];
+        let mut group_start = d.group_len[0];
+        let mut remaining_pawns = self.has_pawns && self.pawn_count[1] != 0;
+        let mut next = 1;
+        while d.group_len[next] != 0 {
+            let len = d.group_len[next];
+            squares[group_start..group_start + len].sort_unstable();
+            let mut n = 0;
+            for i in 0..len {
+                let sq = squares[group_start + i];
+                let adjust = squares[..group_start].iter().filter(|&&s| sq > s).count();
+                let pawn_adjust = if remaining_pawns { 8 } else { 0 };
+                n += ENCODING.binomial[i + 1][sq - adjust - pawn_adjust];
+            }
+
+            remaining_pawns = false;
+            idx += n * d.group_idx[next];
+            group_start += len;
+            next += 1;
+        }
+
+        Wdl::from_table_value(d.decompress(&data.bytes, idx)?)
+    }
+}
+
+/// The contents of a table file, along with the parsed indexing information for each of its subtables.
+struct TableData {
+    bytes: Vec<u8>,
+    /// Subtables, indexed by side to move and then by the file of the leading pawn.
+    items: [[PairsData; 4]; 2],
+}
+
+/// Indexing and decompression information for a single subtable. Fields that point into the table file are byte
+/// offsets from the start of the file.
+#[derive(Default)]
+struct PairsData {
+    flags: u8,
+    block_size: usize,
+    span: usize,
+    num_blocks: usize,
+    /// Length, in bits, of the shortest Huffman symbol. If this subtable has only a single value, this is that value.
+    min_sym_len: u8,
+    /// Offset of the lowest symbol of each length.
+    lowest_sym: usize,
+    /// Offset of the pairing tree, which expands each symbol into its left and right children.
+    btree: usize,
+    /// Offset of the number of values (minus one) stored in each block.
+    block_lengths: usize,
+    block_lengths_size: usize,
+    /// Offset of the sparse index, which points into block lengths every `span` values.
+    sparse_index: usize,
+    sparse_index_size: usize,
+    /// Offset of the compressed data.
+    data: usize,
+    /// The lowest symbol of each length, left-aligned in 64 bits.
+    base64: Vec<u64>,
+    /// Number of values (minus one) that each symbol expands to.
+    symlen: Vec<u8>,
+    pieces: [u8; MAX_PIECES],
+    group_idx: [u64; MAX_PIECES + 1],
+    group_len: [usize; MAX_PIECES + 1],
+}
+
+impl PairsData {
+    /// Reads the sizes of this subtable's structures, returning the offset of the data that follows them.
+    fn set_sizes(&mut self, bytes: &[u8], mut cursor: usize) -> Option<usize> {
+        self.flags = *bytes.get(cursor)?;
+        cursor += 1;
+        if self.flags & SINGLE_VALUE != 0 {
+            self.min_sym_len = *bytes.get(cursor)?;
+            return Some(cursor + 1);
+        }
+
+        let groups = self.group_len.iter().position(|&len| len == 0)?;
+        let table_size = self.group_idx[groups];
+        self.block_size = 1 << *bytes.get(cursor)?;
+        self.span = 1 << *bytes.get(cursor + 1)?;
+        self.sparse_index_size = ((table_size + self.span as u64 - 1) / self.span as u64) as usize;
+        let padding = *bytes.get(cursor + 2)? as usize;
+        self.num_blocks = read_u32_le(bytes, cursor + 3)? as usize;
+        self.block_lengths_size = self.num_blocks + padding;
+        let max_sym_len = *bytes.get(cursor + 7)?;
+        self.min_sym_len = *bytes.get(cursor + 8)?;
+        cursor += 9;
+
+        // The Huffman code is canonical, with longer symbols having lower numeric values. From the lowest symbol of
+        // each length, compute the lowest symbol of each length padded to 64 bits, so that the length of a symbol can
+        // be found by comparing against them.
+        self.lowest_sym = cursor;
+        let lengths = (max_sym_len.checked_sub(self.min_sym_len)? + 1) as usize;
+        self.base64 = vec![0; lengths];
+        for i in (0..lengths - 1).rev() {
+            let lowest = read_u16_le(bytes, self.lowest_sym + 2 * i)? as u64;
+            let next_lowest = read_u16_le(bytes, self.lowest_sym + 2 * (i + 1))? as u64;
+            self.base64[i] = self.base64[i + 1]
+                .wrapping_add(lowest)
+                .wrapping_sub(next_lowest)
+                / 2;
+        }
+
+        for (i, base) in self.base64.iter_mut().enumerate() {
+            *base = base
+                .checked_shl((64 - i - self.min_sym_len as usize) as u32)
+                .unwrap_or(0);
+        }
+
+        cursor += lengths * 2;
+        let symbols = read_u16_le(bytes, cursor)? as usize;
+        cursor += 2;
+        self.btree = cursor;
+        self.symlen = vec![0; symbols];
+        let mut visited = vec![false; symbols];
+        for sym in 0..symbols {
+            if !visited[sym] {
+                self.symlen[sym] = self.set_symlen(bytes, sym, &mut visited)?;
+            }
+        }
+
+        Some(cursor + symbols * 3 + (symbols & 1))
+    }
+
+    /// Computes the number of values (minus one) that a symbol expands to. Symbols represent either a single value
+    /// or a pair of other symbols.
+    fn set_symlen(&mut self, bytes: &[u8], sym: usize, visited: &mut [bool]) -> Option<u8> {
+        visited[sym] = true;
+        let (left, right) = self.children(bytes, sym)?;
+        if right == 0xFFF {
+            return Some(0);
+        }
+
+        for child in [left, right] {
+            if !*visited.get(child)? {
+                self.symlen[child] = self.set_symlen(bytes, child, visited)?;
+            }
+        }
+
+        Some(
+            self.symlen[left]
+                .wrapping_add(self.symlen[right])
+                .wrapping_add(1),
+        )
+    }
+
+    /// Returns the left and right children of a symbol in the pairing tree. For symbols that represent a single
+    /// value, the left child is the value.
+    fn children(&self, bytes: &[u8], sym: usize) -> Option<(usize, usize)> {
+        let entry = bytes.get(self.btree + 3 * sym..self.btree + 3 * sym + 3)?;
+        let left = ((entry[1] as usize & 0xF) << 8) | entry[0] as usize;
+        let right = ((entry[2] as usize) << 4) | (entry[1] as usize >> 4);
+        Some((left, right))
+    }
+
+    /// Decompresses the value stored at the given index.
+    fn decompress(&self, bytes: &[u8], idx: u64) -> Option<u8> {
+        if self.flags & SINGLE_VALUE != 0 {
+            return Some(self.min_sym_len);
+        }
+
+        // Values are stored in blocks, each of which holds a variable number of values. The sparse index records,
+        // every `span` values, which block holds the value in the middle of the span and its offset within the block.
+        // Start from there and walk to the block that contains our index.
+        let span = self.span as u64;
+        let sparse_entry = self.sparse_index + 6 * (idx / span) as usize;
+        let mut block = read_u32_le(bytes, sparse_entry)? as usize;
+        let mut offset = read_u16_le(bytes, sparse_entry + 4)? as i64;
+        offset += (idx % span) as i64 - (span / 2) as i64;
+        let block_length =
+            |block: usize| read_u16_le(bytes, self.block_lengths + 2 * block).map(|len| len as i64);
+
+        while offset < 0 {
+            block = block.checked_sub(1)?;
+            offset += block_length(block)? + 1;
+        }
+
+        while offset > block_length(block)? {
+            offset -= block_length(block)? + 1;
+            block += 1;
+        }
+
+        // Walk the Huffman-coded symbols in the block until we reach the one that contains our value.
+        let mut cursor = self.data + block * self.block_size;
+        let mut buf = read_u64_be(bytes, cursor)?;
+        let mut buf_size = 64;
+        cursor += 8;
+        let mut sym;
+        loop {
+            let mut len = 0;
+            while buf < *self.base64.get(len)? {
+                len += 1;
+            }
+
+            let shift = (64 - len - self.min_sym_len as usize) as u32;
+            sym = ((buf - self.base64[len]).checked_shr(shift).unwrap_or(0)) as usize;
+            sym += read_u16_le(bytes, self.lowest_sym + 2 * len)? as usize;
+            let sym_values = *self.symlen.get(sym)? as i64 + 1;
+            if offset < sym_values {
+                break;
+            }
+
+            offset -= sym_values;
+            len += self.min_sym_len as usize;
+            buf = buf.checked_shl(len as u32).unwrap_or(0);
+            buf_size -= len;
+            if buf_size <= 32 {
+                buf_size += 32;
+                buf |= (read_u32_be(bytes, cursor)? as u64) << (64 - buf_size);
+                cursor += 4;
+            }
+        }
+
+        // Expand the symbol until we reach the single value that we're looking for.
+        while self.symlen[sym] != 0 {
+            let (left, right) = self.children(bytes, sym)?;
+            let left_values = *self.symlen.get(left)? as i64 + 1;
+            if offset < left_values {
+                sym = left;
+            } else {
+                offset -= left_values;
+                sym = right;
+            }
+        }
+
+        Some(self.children(bytes, sym)?.0 as u8)
+    }
+}
+
+/// Lookup tables used to compute the index of a position.
+struct Encoding {
+    /// Maps squares A2-H7 to 0..47, in order of preference for the leading pawn.
+    map_pawns: [usize; 64],
+    /// Maps squares below the A1-H8 diagonal to 0..27.
+    map_b1h1h7: [usize; 64],
+    /// Maps squares in the A1-D1-D4 triangle to 0..9, with the diagonal last.
+    map_a1d1d4: [usize; 64],
+    /// Maps the 462 legal placements of two kings, where the first is in the A1-D1-D4 triangle.
+    map_kk: [[u64; 64]; 10],
+    /// `binomial[k][n]` is the number of ways to choose `k` squares out of `n`.
+    binomial: [[u64; 64]; MAX_PIECES - 1],
+    lead_pawn_idx: [[u64; 64]; MAX_PIECES - 1],
+    lead_pawns_size: [[u64; 4]; MAX_PIECES - 1],
+}
+
+impl Encoding {
+    fn new() -> Encoding {
+        let mut enc = Encoding {
+            map_pawns: [0; 64],
+            map_b1h1h7: [0; 64],
+            map_a1d1d4: [0; 64],
+            map_kk: [[0; 64]; 10],
+            binomial: [[0; 64]; MAX_PIECES - 1],
+            lead_pawn_idx: [[0; 64]; MAX_PIECES - 1],
+            lead_pawns_size: [[0; 4]; MAX_PIECES - 1],
+        };
+
+        let mut code = 0;
+        for sq in 0..64 {
+            if off_diagonal(sq) < 0 {
+                enc.map_b1h1h7[sq] = code;
+                code += 1;
+            }
+        }
+
+        let mut diagonal = vec![];
+        code = 0;
+        for sq in 0..=D4.as_u8() as usize {
+            if off_diagonal(sq) < 0 && file_of(sq) <= 3 {
+                enc.map_a1d1d4[sq] = code;
+                code += 1;
+            } else if off_diagonal(sq) == 0 && file_of(sq) <= 3 {
+                diagonal.push(sq);
+            }
+        }
+
+        for sq in diagonal {
+            enc.map_a1d1d4[sq] = code;
+            code += 1;
+        }
+
+        // If the first king is on the diagonal, the second is kept on or below it. Positions with both kings on the
+        // diagonal are encoded last.
+        let mut both_on_diagonal = vec![];
+        let mut code = 0;
+        for idx in 0..10 {
+            for s1 in 0..=D4.as_u8() as usize {
+                if enc.map_a1d1d4[s1] != idx || (idx == 0 && s1 != B1.as_u8() as usize) {
+                    continue;
+                }
+
+                let king = Square::try_from(s1 as u8).unwrap();
+                for s2 in 0..64 {
+                    let other = Square::try_from(s2 as u8).unwrap();
+                    if king == other || king_attacks(king).contains(other) {
+                        continue;
+                    }
+
+                    if off_diagonal(s1) == 0 && off_diagonal(s2) > 0 {
+                        continue;
+                    }
+
+                    if off_diagonal(s1) == 0 && off_diagonal(s2) == 0 {
+                        both_on_diagonal.push((idx, s2));
+                    } else {
+                        enc.map_kk[idx][s2] = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+
+        for (idx, s2) in both_on_diagonal {
+            enc.map_kk[idx][s2] = code;
+            code += 1;
+        }
+
+        enc.binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..cmp::min(MAX_PIECES - 1, n + 1) {
+                enc.binomial[k][n] = if k > 0 { enc.binomial[k - 1][n - 1] } else { 0 }
+                    + if k < n { enc.binomial[k][n - 1] } else { 0 };
+            }
+        }
+
+        // The leading pawn is the one with the highest value here: the one closest to the edge and, among those, the
+        // one with the lowest rank. The value is also the number of squares left for the remaining leading pawns.
+        let mut available_squares = 48;
+        for lead_pawns_count in 1..MAX_PIECES - 1 {
+            for file in 0..4 {
+                let mut idx = 0;
+                for rank in 1..7 {
+                    let sq = 8 * rank + file;
+                    if lead_pawns_count == 1 {
+                        enc.map_pawns[sq] = available_squares - 1;
+                        enc.map_pawns[sq ^ 7] = available_squares - 2;
+                        available_squares -= 2;
+                    }
+
+                    enc.lead_pawn_idx[lead_pawns_count][sq] = idx;
+                    idx += enc.binomial[lead_pawns_count - 1][enc.map_pawns[sq]];
+                }
+
+                enc.lead_pawns_size[lead_pawns_count][file] = idx;
+            }
+        }
+
+        enc
+    }
+}
+
+static ENCODING: LazyLock<Encoding> = LazyLock::new(Encoding::new);
+
+/// Computes the index of the leading group when it consists of three pieces.
+fn encode_unique_pieces(s0: usize, s1: usize, s2: usize) -> u64 {
+    let adjust1 = (s1 > s0) as usize;
+    let adjust2 = (s2 > s0) as usize + (s2 > s1) as usize;
+    let (rank0, rank1, rank2) = (rank_of(s0), rank_of(s1), rank_of(s2));
+    let idx = if off_diagonal(s0) != 0 {
+        // First piece is below the diagonal.
+        (ENCODING.map_a1d1d4[s0] * 63 + (s1 - adjust1)) * 62 + s2 - adjust2
+    } else if off_diagonal(s1) != 0 {
+        // First piece is on the diagonal, second is below it.
+        (6 * 63 + rank0 * 28 + ENCODING.map_b1h1h7[s1]) * 62 + s2 - adjust2
+    } else if off_diagonal(s2) != 0 {
+        // First two pieces are on the diagonal, third is below it.
+        6 * 63 * 62
+            + 4 * 28 * 62
+            + rank0 * 7 * 28
+            + (rank1 - adjust1) * 28
+            + ENCODING.map_b1h1h7[s2]
+    } else {
+        // All three pieces are on the diagonal.
+        6 * 63 * 62
+            + 4 * 28 * 62
+            + 4 * 7 * 28
+            + rank0 * 7 * 6
+            + (rank1 - adjust1) * 6
+            + (rank2 - adjust2)
+    };
+
+    idx as u64
+}
+
+const fn rank_of(sq: usize) -> usize {
+    sq >> 3
+}
+
+const fn file_of(sq: usize) -> usize {
+    sq & 7
+}
+
+/// Returns how far a square is above (positive) or below (negative) the A1-H8 diagonal.
+const fn off_diagonal(sq: usize) -> i32 {
+    rank_of(sq) as i32 - file_of(sq) as i32
+}
+
+/// Encodes a piece in the format used by table files: kinds are numbered from 1, and black pieces have bit 3 set.
+fn table_piece(piece: Piece) -> u8 {
+    let color = if piece.color == Color::White { 0 } else { 8 };
+    piece.kind as u8 + 1 + color
+}
+
+/// Returns the material key of a position (e.g. `KRvKN`), with the given color's pieces first.
+fn material_key(pos: &Position, first: Color) -> String {
+    let side = |color: Color| -> String {
+        [
+            (PieceKind::King, 'K'),
+            (PieceKind::Queen, 'Q'),
+            (PieceKind::Rook, 'R'),
+            (PieceKind::Bishop, 'B'),
+            (PieceKind::Knight, 'N'),
+            (PieceKind::Pawn, 'P'),
+        ]
+        .iter()
+        .flat_map(|&(kind, c)| {
+            std::iter::repeat(c).take(pos.pieces_of_kind(color, kind).len() as usize)
+        })
+        .collect()
+    };
+
+    format!("{}v{}", side(first), side(first.toggle()))
+}
+
+fn read_u16_le(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        bytes.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32_le(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        bytes.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32_be(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        bytes.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn read_u64_be(bytes: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(
+        bytes.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
+
+static TABLEBASE: RwLock<Option<Tablebase>> = RwLock::new(None);
+static TABLEBASE_MAX_PIECES: AtomicUsize = AtomicUsize::new(0);
+
+/// Loads the tablebase from the given list of directories, separated by `:`, replacing any previously loaded
+/// tablebase. An empty string unloads the tablebase.
+pub fn initialize(paths: &str) -> io::Result<()> {
+    let tablebase = Tablebase::new(paths)?;
+    TABLEBASE_MAX_PIECES.store(tablebase.max_pieces(), Ordering::Release);
+    *TABLEBASE
+        .write()
+        .expect("failed to acquire tablebase write lock") = Some(tablebase);
+    Ok(())
+}
+
+/// Returns the largest number of pieces for which the loaded tablebase has a table.
+pub fn max_pieces() -> usize {
+    TABLEBASE_MAX_PIECES.load(Ordering::Acquire)
+}
+
+/// Probes the loaded tablebase for the outcome of the given position.
+pub fn probe_wdl(pos: &Position) -> Option<Wdl> {
+    // Cheap early-out, so that positions with lots of pieces don't have to contend for the lock.
+    if pos.piece_count() as usize > max_pieces() {
+        return None;
+    }
+
+    TABLEBASE
+        .read()
+        .expect("failed to acquire tablebase read lock")
+        .as_ref()?
+        .probe_wdl(pos)
+}
+
+/// Returns the root moves that preserve the best outcome according to the loaded tablebase, along with that outcome.
+/// See `Tablebase::root_moves`.
+pub fn root_moves(pos: &Position) -> Option<(Vec<Move>, Wdl)> {
+    if pos.piece_count() as usize > max_pieces() {
+        return None;
+    }
+
+    TABLEBASE
+        .read()
+        .expect("failed to acquire tablebase read lock")
+        .as_ref()?
+        .root_moves(pos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{material_key, Tablebase, Wdl, ENCODING};
+    use crate::{core::*, position::Position};
+
+    #[test]
+    fn king_pair_encoding_is_dense() {
+        let mut codes: Vec<_> = ENCODING
+            .map_kk
+            .iter()
+            .flat_map(|row| row.iter())
+            .cloned()
+            .filter(|&code| code != 0)
+            .collect();
+        codes.sort_unstable();
+        codes.dedup();
+
+        // Code 0 is filtered above, since it's indistinguishable from an unset entry.
+        assert_eq!(codes.len(), 461);
+        assert_eq!(*codes.last().unwrap(), 461);
+    }
+
+    #[test]
+    fn binomial() {
+        assert_eq!(ENCODING.binomial[2][5], 10);
+        assert_eq!(ENCODING.binomial[1][48], 48);
+        assert_eq!(ENCODING.binomial[0][17], 1);
+    }
+
+    #[test]
+    fn leading_pawn_map() {
+        assert_eq!(ENCODING.map_pawns[A2.as_u8() as usize], 47);
+        assert_eq!(ENCODING.map_pawns[H2.as_u8() as usize], 46);
+        assert_eq!(ENCODING.map_pawns[D7.as_u8() as usize], 1);
+        assert_eq!(ENCODING.map_pawns[E7.as_u8() as usize], 0);
+    }
+
+    #[test]
+    fn material_keys() {
+        let pos = Position::from_fen("8/8/8/3k4/8/2n5/8/2KR4 w - - 0 1").unwrap();
+        assert_eq!(material_key(&pos, Color::White), "KRvKN");
+        assert_eq!(material_key(&pos, Color::Black), "KNvKR");
+    }
+
+    #[test]
+    fn wdl_negate() {
+        assert_eq!(-Wdl::Win, Wdl::Loss);
+        assert_eq!(-Wdl::CursedWin, Wdl::BlessedLoss);
+        assert_eq!(-Wdl::Draw, Wdl::Draw);
+    }
+
+    #[test]
+    fn empty_tablebase_misses() {
+        let tablebase = Tablebase::new("").unwrap();
+        let pos = Position::from_fen("4k3/8/8/8/8/8/8/3QK3 w - - 0 1").unwrap();
+        assert_eq!(tablebase.max_pieces(), 0);
+        assert_eq!(tablebase.probe_wdl(&pos), None);
+    }
+
+    #[test]
+    fn empty_tablebase_has_no_root_moves() {
+        let tablebase = Tablebase::new("").unwrap();
+        let pos = Position::from_fen("4k3/8/8/8/8/8/8/3QK3 w - - 0 1").unwrap();
+        assert_eq!(tablebase.root_moves(&pos), None);
+    }
+
+    /// Probes real tables from the directory given in `A4_SYZYGY_PATH`. Run with `cargo test -- --ignored`.
+    #[test]
+    #[ignore = "needs Syzygy tables for KQvK in A4_SYZYGY_PATH"]
+    fn kqvk_is_won() {
+        let path =
+            std::env::var("A4_SYZYGY_PATH").expect("A4_SYZYGY_PATH must be set to run this test");
+        let tablebase = Tablebase::new(&path).unwrap();
+        let white_to_move = Position::from_fen("4k3/8/8/8/8/8/8/3QK3 w - - 0 1").unwrap();
+        assert_eq!(tablebase.probe_wdl(&white_to_move), Some(Wdl::Win));
+        let black_to_move = Position::from_fen("4k3/8/8/8/8/8/8/3QK3 b - - 0 1").unwrap();
+        assert_eq!(tablebase.probe_wdl(&black_to_move), Some(Wdl::Loss));
+
+        let (moves, wdl) = tablebase.root_moves(&white_to_move).unwrap();
+        assert_eq!(wdl, Wdl::Win);
+        assert!(!moves.is_empty());
+        for mov in moves {
+            assert_eq!(
+                tablebase.probe_wdl(&white_to_move.clone_and_make_move(mov)),
+                Some(Wdl::Loss)
+            );
+        }
+    }
+}
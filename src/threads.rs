@@ -14,9 +14,10 @@
 //!  2. Worker threads, which perform search work as coordinated by the main thread.
 
 use std::{
-    cell::RefCell,
+    cell::{Cell, RefCell},
+    panic::{self, AssertUnwindSafe},
     sync::{
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicUsize, Ordering},
         mpsc,
         mpsc::{Receiver, SyncSender},
         Condvar, Mutex, Once, OnceLock, RwLock,
@@ -26,13 +27,17 @@ use std::{
 };
 
 use crate::{
+    core::Move,
+    movegen,
     position::Position,
-    search::{self, SearchOptions},
+    search::{self, InfoSink, SearchInfo, SearchOptions, StopToken, UciInfoSink},
+    table,
 };
 
 #[derive(Clone, Default)]
 pub struct SearchRequest {
-    /// Maximum amount of time to dedicate to this search.
+    /// Maximum amount of time to dedicate to this search. For a ponder search, this only starts counting down on
+    /// `ponderhit`.
     pub time_limit: Option<Duration>,
 
     /// Maximum amount of nodes to evaluate.
@@ -40,17 +45,45 @@ pub struct SearchRequest {
 
     /// Maximum depth to search.
     pub depth: Option<u32>,
+
+    /// How much, in centipawns, the engine dislikes a draw.
+    pub contempt: i16,
+
+    /// Number of principal variations to report.
+    pub multi_pv: usize,
+
+    /// If set, search only for a mate in at most this many moves.
+    pub mate: Option<u32>,
+
+    /// Whether this is a ponder search, which searches on the opponent's time. A ponder search has no depth limit
+    /// unless it's given one, and holds on to its best move until it's told to stop or the opponent plays the move
+    /// that it's pondering on.
+    pub ponder: bool,
 }
 
+/// Depth to search to when a search request doesn't give one.
+const DEFAULT_DEPTH: u32 = 10;
+
+/// Depth to search to when a ponder search request doesn't give one. Ponder searches are meant to run until they're
+/// told to stop, so this is only here to keep the search's ply counts in range.
+const PONDER_DEPTH: u32 = 64;
+
 pub enum Request {
     Search,
     Stop,
+    PonderHit,
 }
 
 pub struct MainThread {
     tx: SyncSender<Request>,
     position: RwLock<Option<Position>>,
     search: RwLock<Option<SearchRequest>>,
+    /// Number of searches started so far, so that a timer can tell whether the search that it was set for is still
+    /// the current one.
+    searches_started: AtomicUsize,
+    /// Whether a ponder search is still waiting on `stop` or `ponderhit`.
+    pondering: Mutex<bool>,
+    pondering_cv: Condvar,
 }
 
 impl MainThread {
@@ -67,10 +100,13 @@ impl MainThread {
             tx,
             position: RwLock::new(None),
             search: RwLock::new(None),
+            searches_started: AtomicUsize::new(0),
+            pondering: Mutex::new(false),
+            pondering_cv: Condvar::new(),
         }
     }
 
-    fn position(&self) -> Option<Position> {
+    pub fn position(&self) -> Option<Position> {
         self.position
             .read()
             .expect("failed to acquire position read lock")
@@ -109,20 +145,69 @@ impl MainThread {
             .send(Request::Stop)
             .expect("failed to send message to main thread");
     }
+
+    /// Tells a ponder search that the opponent played the move that it's pondering on, so it's now searching on our
+    /// own time.
+    pub fn ponderhit(&self) {
+        self.tx
+            .send(Request::PonderHit)
+            .expect("failed to send message to main thread");
+    }
+
+    fn set_pondering(&self, pondering: bool) {
+        *self
+            .pondering
+            .lock()
+            .expect("failed to acquire pondering lock") = pondering;
+        self.pondering_cv.notify_all();
+    }
+
+    /// Blocks until the current ponder search, if any, is told to stop or gets a ponderhit.
+    fn wait_until_done_pondering(&self) {
+        let pondering = self
+            .pondering
+            .lock()
+            .expect("failed to acquire pondering lock");
+        let _pondering = self
+            .pondering_cv
+            .wait_while(pondering, |pondering| *pondering)
+            .expect("failed to wait on condvar");
+    }
+
+    /// Stops the current search once the given amount of time has passed, unless another search has started by then.
+    fn stop_after(&'static self, limit: Duration) {
+        let search = self.searches_started.load(Ordering::Acquire);
+        thread::Builder::new()
+            .name("a4 ponderhit timer".into())
+            .spawn(move || {
+                thread::sleep(limit);
+                if self.searches_started.load(Ordering::Acquire) == search {
+                    self.stop();
+                }
+            })
+            .expect("failed to spawn timer thread");
+    }
 }
 
 fn main_thread_loop(rx: Receiver<Request>) {
     info!("starting");
     while let Ok(req) = rx.recv() {
+        let main_thread = get_main_thread();
         match req {
             Request::Search => {
                 info!("sending start signal to workers");
+                main_thread.searches_started.fetch_add(1, Ordering::AcqRel);
+                main_thread
+                    .set_pondering(main_thread.search().map_or(false, |search| search.ponder));
+                table::new_search();
                 for worker in get_worker_threads() {
                     worker.start();
                 }
             }
             Request::Stop => {
                 info!("sending stop signal to workers");
+                // A ponder search won't go idle until it has reported its best move, which it holds on to until now.
+                main_thread.set_pondering(false);
                 for worker in get_worker_threads() {
                     worker.stop();
                     worker.wait_until_idle()
@@ -130,6 +215,19 @@ fn main_thread_loop(rx: Receiver<Request>) {
 
                 info!("all workers are now idle");
             }
+            Request::PonderHit => {
+                // The ponder search carries on as a normal search, with the time limit that it would have had if it
+                // had started just now. If it already finished, it reports its best move right away.
+                let time_limit = main_thread
+                    .search()
+                    .filter(|search| search.ponder)
+                    .and_then(|search| search.time_limit);
+                info!("ponderhit, time limit {:?}", time_limit);
+                main_thread.set_pondering(false);
+                if let Some(limit) = time_limit {
+                    main_thread.stop_after(limit);
+                }
+            }
         }
     }
 }
@@ -138,7 +236,7 @@ pub struct WorkerThread {
     id: usize,
     idle_lock: Mutex<bool>,
     idle_cv: Condvar,
-    stop_flag: AtomicBool,
+    stop_flag: StopToken,
 }
 
 impl WorkerThread {
@@ -147,18 +245,21 @@ impl WorkerThread {
             id,
             idle_lock: Mutex::new(true),
             idle_cv: Condvar::new(),
-            stop_flag: AtomicBool::new(false),
+            stop_flag: StopToken::new(),
         }
     }
 
     fn start(&self) {
         let mut idle = self.idle_lock.lock().expect("failed to acquire idle lock");
+        // A stop that arrived while this worker was idle, such as one from a ponderhit timer that outlived its search,
+        // is meant for the search that already ended.
+        self.stop_flag.reset();
         *idle = false;
         self.idle_cv.notify_all();
     }
 
     fn stop(&self) {
-        self.stop_flag.store(true, Ordering::Release);
+        self.stop_flag.stop();
     }
 
     fn wait_until_idle(&self) {
@@ -187,15 +288,49 @@ impl WorkerThread {
                     .position()
                     .expect("search requested with no position?");
 
+                // A ponder search's best move is held back until the GUI tells it to stop or the opponent plays the
+                // move that it's pondering on, as UCI requires.
+                let ponder_sink = PonderSink {
+                    inner: &UciInfoSink,
+                    best_move: Cell::new(None),
+                };
+                let uci_sink: &dyn InfoSink = if search.ponder {
+                    &ponder_sink
+                } else {
+                    &UciInfoSink
+                };
+
                 let opts = SearchOptions {
-                    time_limit: search.time_limit,
+                    time_limit: if search.ponder {
+                        None
+                    } else {
+                        search.time_limit
+                    },
                     node_limit: search.node_limit,
-                    hard_stop: Some(&self.stop_flag),
-                    depth: search.depth.unwrap_or(10),
+                    hard_stop: Some(self.stop_flag.clone()),
+                    depth: search.depth.unwrap_or(if search.ponder {
+                        PONDER_DEPTH
+                    } else {
+                        DEFAULT_DEPTH
+                    }),
+                    see_pruning: true,
+                    delta_pruning: true,
+                    underpromotion_pruning: true,
+                    futility_depth: 1,
+                    futility_margin: search::DEFAULT_FUTILITY_MARGIN,
+                    reverse_futility_depth: 3,
+                    reverse_futility_margin: search::DEFAULT_REVERSE_FUTILITY_MARGIN,
+                    max_qsearch_depth: search::DEFAULT_MAX_QSEARCH_DEPTH,
+                    contempt: search.contempt,
+                    multi_pv: search.multi_pv,
+                    mate: search.mate,
+                    use_tt: true,
+                    // The 0th worker thread is the one that reports its progress to the GUI.
+                    info_sink: if self.id == 0 { Some(uci_sink) } else { None },
                 };
 
                 info!("search: {:?}", opts);
-                search::search(&position, &opts);
+                search_with_watchdog(&position, opts);
 
                 // The 0th worker thread is special in that it is responsible for printing its search results to stdout.
                 if self.id == 0 {
@@ -208,18 +343,100 @@ impl WorkerThread {
                         worker.stop();
                         worker.wait_until_idle()
                     }
+
+                    if search.ponder {
+                        main_thread.wait_until_done_pondering();
+                        if let Some(mov) = ponder_sink.best_move.get() {
+                            ponder_sink.inner.best_move(mov);
+                        }
+                    }
                 }
             } else {
                 warn!("worker going back to sleep due to no search work");
             }
 
-            self.stop_flag.store(false, Ordering::Release);
+            self.stop_flag.reset();
             *idle = true;
             info!("worker is idle");
         }
     }
 }
 
+/// Runs a search, making sure that the GUI gets a `bestmove` no matter how the search ends. GUIs wait for `bestmove`
+/// forever, so a search that panics, or that ends without a move to report, must not leave them hanging. Catching
+/// the panic relies on the release profile unwinding rather than aborting.
+///
+/// If the search doesn't report a move, or reports the null move when there are legal moves to play, the first legal
+/// move is reported instead; the null move is only reported when there are no legal moves at all.
+fn search_with_watchdog(pos: &Position, opts: SearchOptions<'_>) {
+    let sink = opts.info_sink.map(|inner| WatchdogSink {
+        inner,
+        reported: Cell::new(false),
+    });
+    let opts = SearchOptions {
+        info_sink: sink.as_ref().map(|sink| sink as &dyn InfoSink),
+        ..opts
+    };
+    if let Err(e) = panic::catch_unwind(AssertUnwindSafe(|| search::search(pos, &opts))) {
+        warn!("search panicked: {:?}", e);
+    }
+
+    if let Some(sink) = &sink {
+        if !sink.reported.get() {
+            let mut moves = Vec::new();
+            movegen::generate_moves(pos.side_to_move(), pos, &mut moves);
+            let fallback = moves
+                .into_iter()
+                .find(|&mov| pos.is_legal_given_pseudolegal(mov))
+                .unwrap_or_else(Move::null);
+            warn!(
+                "search ended without a best move, falling back to {}",
+                fallback
+            );
+            sink.inner.best_move(fallback);
+        }
+    }
+}
+
+/// Passes search reports through to another sink, except for the null move as a best move, and remembers whether a
+/// best move was reported.
+#[derive(Debug)]
+struct WatchdogSink<'a> {
+    inner: &'a dyn InfoSink,
+    reported: Cell<bool>,
+}
+
+impl InfoSink for WatchdogSink<'_> {
+    fn info(&self, info: &SearchInfo) {
+        self.inner.info(info);
+    }
+
+    fn best_move(&self, mov: Move) {
+        if !mov.is_null() {
+            self.reported.set(true);
+            self.inner.best_move(mov);
+        }
+    }
+}
+
+/// Passes search reports through to another sink, except for the best move, which is kept until the ponder search
+/// that reported it is allowed to report it.
+#[derive(Debug)]
+struct PonderSink<'a> {
+    inner: &'a dyn InfoSink,
+    best_move: Cell<Option<Move>>,
+}
+
+impl InfoSink for PonderSink<'_> {
+    fn info(&self, info: &SearchInfo) {
+        self.inner.info(info);
+    }
+
+    fn best_move(&self, mov: Move) {
+        self.best_move.set(Some(mov));
+    }
+}
+
 pub fn get_main_thread() -> &'static MainThread {
     static MAIN_THREAD: OnceLock<MainThread> = OnceLock::new();
 
@@ -276,3 +493,103 @@ pub fn get_worker_id() -> Option<usize> {
 pub fn initialize() {
     let _ = get_main_thread();
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::{Cell, RefCell},
+        panic,
+    };
+
+    use super::search_with_watchdog;
+    use crate::{
+        core::*,
+        position::Position,
+        search::{InfoSink, SearchInfo, SearchOptions, StopToken},
+        table,
+    };
+
+    #[derive(Debug, Default)]
+    struct RecordingSink {
+        panic_on_info: bool,
+        infos: Cell<usize>,
+        best_moves: RefCell<Vec<Move>>,
+    }
+
+    impl InfoSink for RecordingSink {
+        fn info(&self, _: &SearchInfo) {
+            if self.panic_on_info {
+                panic!("sink panicked");
+            }
+
+            self.infos.set(self.infos.get() + 1);
+        }
+
+        fn best_move(&self, mov: Move) {
+            self.best_moves.borrow_mut().push(mov);
+        }
+    }
+
+    fn watchdog_best_moves(fen: &str, sink: &RecordingSink, hard_stop: Option<StopToken>) {
+        let pos = Position::from_fen(fen).unwrap();
+        search_with_watchdog(
+            &pos,
+            SearchOptions {
+                depth: 2,
+                hard_stop,
+                info_sink: Some(sink),
+                ..Default::default()
+            },
+        );
+    }
+
+    #[test]
+    fn checkmated_root_reports_null_move() {
+        let sink = RecordingSink::default();
+        watchdog_best_moves("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1", &sink, None);
+        assert_eq!(vec![Move::null()], *sink.best_moves.borrow());
+    }
+
+    #[test]
+    fn stopped_search_still_reports_a_move() {
+        let sink = RecordingSink::default();
+        let token = StopToken::new();
+        token.stop();
+        watchdog_best_moves(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            &sink,
+            Some(token),
+        );
+        let best_moves = sink.best_moves.borrow();
+        assert_eq!(1, best_moves.len());
+        assert!(Position::from_start_position().is_legal(best_moves[0]));
+    }
+
+    #[test]
+    fn panicking_search_still_reports_a_move() {
+        let sink = RecordingSink {
+            panic_on_info: true,
+            ..Default::default()
+        };
+        watchdog_best_moves("4k3/8/8/8/8/8/8/4K2R w K - 0 1", &sink, None);
+        let best_moves = sink.best_moves.borrow();
+        assert_eq!(1, best_moves.len());
+        assert!(!best_moves[0].is_null());
+    }
+
+    #[test]
+    fn search_after_panic_with_table_locked() {
+        // A search that panics while updating the transposition table leaves a bucket's lock poisoned. The next
+        // search has to get through it on its own, rather than by falling back to the first legal move.
+        let fen = "4k3/8/8/8/8/8/8/4K2R w K - 0 1";
+        let pos = Position::from_fen(fen).unwrap();
+        assert!(panic::catch_unwind(|| table::panic_while_locked(&pos)).is_err());
+
+        let sink = RecordingSink::default();
+        watchdog_best_moves(fen, &sink, None);
+        assert_eq!(2, sink.infos.get());
+        let best_moves = sink.best_moves.borrow();
+        assert_eq!(1, best_moves.len());
+        assert!(pos.is_legal(best_moves[0]));
+    }
+}
@@ -11,26 +11,38 @@
 
 use std::{
     io::{self, BufRead},
-    sync::atomic::{AtomicUsize, Ordering},
+    sync::{
+        atomic::{AtomicBool, AtomicI16, AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread::{self, JoinHandle},
     time::Duration,
 };
 
 use anyhow::anyhow;
 
 use crate::{
-    core::Move,
+    bench,
+    core::{self, Color, Move},
+    eval::cache,
     log::{self, LogLevel},
     position::Position,
-    table, threads,
+    table, tablebase, threads,
     threads::SearchRequest,
 };
 
 struct Options {
     threads: AtomicUsize,
+    ponder: AtomicBool,
+    multi_pv: AtomicUsize,
+    contempt: AtomicI16,
 }
 
 static OPTIONS: Options = Options {
     threads: AtomicUsize::new(1),
+    ponder: AtomicBool::new(false),
+    multi_pv: AtomicUsize::new(1),
+    contempt: AtomicI16::new(0),
 };
 
 pub fn run() -> io::Result<()> {
@@ -40,98 +52,210 @@ pub fn run() -> io::Result<()> {
     let locked_stdin = stdin.lock();
     for maybe_line in locked_stdin.lines() {
         let line = maybe_line?;
-        info!("uci <= {}", line);
-        let components: Vec<_> = line.split_whitespace().collect();
-        let (&command, arguments) = components.split_first().unwrap_or((&"", &[]));
-        match (command, arguments) {
-            ("uci", []) => handle_uci(),
-            ("debug", ["on"]) => {}
-            ("debug", ["off"]) => {}
-            ("isready", []) => handle_isready(),
-            ("ucinewgame", []) => handle_ucinewgame(),
-            ("position", args) => handle_position(args),
-            ("go", args) => handle_go(args),
-            ("stop", []) => handle_stop(),
-            ("quit", []) => return Ok(()),
-            ("setoption", ["name", name, "value", value]) => handle_setoption(name, value),
-            // a4 extensions to UCI, for debugging purposes
-            ("table", args) => handle_table(args),
-            _ => uci_output!("unrecognized command: {} {:?}", command, arguments),
+        if !handle_command(&line) {
+            break;
         }
     }
 
     Ok(())
 }
 
+/// Handles one line of input from the GUI, returning false if the GUI asked a4 to quit.
+fn handle_command(line: &str) -> bool {
+    info!("uci <= {}", line);
+    let components: Vec<_> = line.split_whitespace().collect();
+    let (&command, arguments) = components.split_first().unwrap_or((&"", &[]));
+    match (command, arguments) {
+        ("uci", []) => handle_uci(),
+        ("debug", ["on"]) => log::set_uci_debug(true),
+        ("debug", ["off"]) => log::set_uci_debug(false),
+        ("isready", []) => handle_isready(),
+        // a4 is free software and doesn't need to be registered, so there's nothing to do with `register later` or
+        // `register name ... code ...`.
+        ("register", _) => {}
+        ("ucinewgame", []) => handle_ucinewgame(),
+        ("position", args) => handle_position(args),
+        ("go", args) => handle_go(args),
+        ("stop", []) => handle_stop(),
+        ("ponderhit", []) => handle_ponderhit(),
+        ("quit", []) => return false,
+        ("setoption", ["name", name, "value", value]) => handle_setoption(name, value),
+        // a4 extensions to UCI, for debugging purposes
+        ("table", args) => handle_table(args),
+        ("bench", args) => handle_bench(args),
+        ("d", []) => handle_d(),
+        _ => uci_output!("unrecognized command: {} {:?}", command, arguments),
+    }
+
+    true
+}
+
 fn handle_uci() {
-    uci_output!(
-        "id name {} {}",
-        env!("CARGO_PKG_NAME"),
-        env!("CARGO_PKG_VERSION")
-    );
-    uci_output!("id author {}", env!("CARGO_PKG_AUTHORS"));
-    uci_output!("option name Threads type spin default 1 min 1 max 32");
-    uci_output!("option name DebugLogEnabled type check default false");
-    uci_output!("option name DebugLogLevel type spin default 0 min 0 max 3");
-    uci_output!("option name DebugLogPath type string");
-    uci_output!("uciok");
+    for line in uci_response() {
+        uci_output!("{}", line);
+    }
 }
 
+/// The lines that a4 sends in response to `uci`: its identity, the options that it supports, and `uciok`.
+fn uci_response() -> Vec<String> {
+    vec![
+        format!(
+            "id name {} {}",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION")
+        ),
+        format!("id author {}", env!("CARGO_PKG_AUTHORS")),
+        format!(
+            "option name Hash type spin default {} min 1 max {}",
+            table::DEFAULT_TABLE_MB,
+            MAX_HASH_MB
+        ),
+        "option name Threads type spin default 1 min 1 max 32".to_owned(),
+        "option name Ponder type check default false".to_owned(),
+        format!(
+            "option name MultiPV type spin default 1 min 1 max {}",
+            MAX_MULTI_PV
+        ),
+        format!(
+            "option name Contempt type spin default 0 min {} max {}",
+            -MAX_CONTEMPT, MAX_CONTEMPT
+        ),
+        format!(
+            "option name EvalCache type spin default {} min 1 max {}",
+            cache::DEFAULT_EVAL_CACHE_MB,
+            MAX_EVAL_CACHE_MB
+        ),
+        "option name UCI_Chess960 type check default false".to_owned(),
+        "option name DebugLogEnabled type check default false".to_owned(),
+        "option name DebugLogLevel type spin default 0 min 0 max 3".to_owned(),
+        "option name DebugLogPath type string".to_owned(),
+        "option name SyzygyPath type string default <empty>".to_owned(),
+        "uciok".to_owned(),
+    ]
+}
+
+/// The most principal variations that a4 will report at once.
+const MAX_MULTI_PV: usize = 256;
+
+/// The largest contempt, in centipawns, that a4 accepts in either direction.
+const MAX_CONTEMPT: i16 = 100;
+
+/// The largest evaluation cache, in megabytes, that a4 will allocate.
+const MAX_EVAL_CACHE_MB: usize = 1024;
+
+/// The largest transposition table, in megabytes, that a4 will allocate.
+const MAX_HASH_MB: usize = 4096;
+
+/// Number of moves assumed to be left until the next time control, if the GUI doesn't say.
+const DEFAULT_MOVES_TO_GO: u64 = 30;
+
+/// Time, in milliseconds, kept in reserve on every move for the GUI and the operating system to do their part.
+const MOVE_OVERHEAD_MS: u64 = 50;
+
 fn handle_stop() {
     threads::get_main_thread().stop();
 }
 
+fn handle_ponderhit() {
+    // The opponent played the move that we were pondering on, so the ponder search carries on with our own clock.
+    threads::get_main_thread().ponderhit();
+}
+
 fn handle_isready() {
-    // TODO(swgillespie) ask the main thread if it's idle and all worker threads are idle?
-    uci_output!("readyok");
+    for line in isready_response() {
+        uci_output!("{}", line);
+    }
+}
+
+/// The lines that a4 sends in response to `isready`. GUIs expect an answer right away, even while a4 is searching or
+/// doing something slow in the background, so this must never wait on either.
+fn isready_response() -> Vec<String> {
+    vec!["readyok".to_owned()]
+}
+
+/// The most recently started background task, if any.
+static BACKGROUND_TASK: Mutex<Option<JoinHandle<()>>> = Mutex::new(None);
+
+/// Runs a slow task, like reallocating a cache, off of the UCI thread, so that commands like `isready` keep getting
+/// answered while it runs. Background tasks run one at a time, in the order that they were started.
+fn run_in_background(task: impl FnOnce() + Send + 'static) {
+    let mut current = BACKGROUND_TASK
+        .lock()
+        .expect("failed to acquire background task lock");
+    let previous = current.take();
+    *current = Some(thread::spawn(move || {
+        if let Some(previous) = previous {
+            let _ = previous.join();
+        }
+
+        task();
+    }));
 }
 
 fn handle_position(args: &[&str]) {
-    let mut position = Position::new();
+    match parse_position(args) {
+        Ok(position) => threads::get_main_thread().set_position(position),
+        Err(e) => uci_output!("invalid position command: {}", e),
+    }
+}
+
+/// Parses the arguments to a `position` command: either `startpos` or `fen` followed by the fields of a FEN string,
+/// and then optionally `moves` followed by moves to play from that position.
+fn parse_position(args: &[&str]) -> anyhow::Result<Position> {
     let mut iter = args.iter().cloned().peekable();
-    let result: anyhow::Result<()> = try {
-        loop {
-            match iter.next() {
-                Some("fen") => {
-                    let mut fen_str = Vec::new();
-                    while let Some(next) = iter.peek() {
-                        if *next == "moves" {
-                            break;
-                        }
-
-                        let next = iter.next().unwrap();
-                        fen_str.push(next.to_owned());
-                    }
-                    let fen = fen_str.join(" ");
-                    position = Position::from_fen(fen)?;
-                }
-                Some("startpos") => {
-                    position = Position::from_start_position();
-                }
-                Some("moves") => {
-                    while let Some(mov_str) = iter.next() {
-                        let mov = Move::from_uci(&position, mov_str)
-                            .ok_or_else(|| anyhow!("invalid move: {}", mov_str))?;
-                        position.make_move(mov);
-                    }
+    let mut position = match iter.next() {
+        Some("startpos") => Position::from_start_position(),
+        Some("fen") => {
+            // The FEN string is split into its (at most six) fields, so gather them back up. Anything after them other
+            // than the moves is an error.
+            let mut fen_str = Vec::new();
+            while fen_str.len() < 6 {
+                match iter.next_if(|&tok| tok != "moves") {
+                    Some(field) => fen_str.push(field),
+                    None => break,
                 }
-                Some(tok) => {
-                    Err(anyhow!("unknown token: {}", tok))?;
-                }
-                None => break,
             }
+
+            if fen_str.is_empty() {
+                return Err(anyhow!("expected FEN string after fen"));
+            }
+
+            Position::from_fen(fen_str.join(" "))?
         }
+        Some(tok) => return Err(anyhow!("expected startpos or fen, got: {}", tok)),
+        None => return Err(anyhow!("expected startpos or fen")),
     };
 
-    match result {
-        Ok(()) => threads::get_main_thread().set_position(position),
-        Err(e) => uci_output!("invalid position command: {}", e),
+    match iter.next() {
+        Some("moves") => {
+            for mov_str in iter {
+                let mov = Move::from_uci(&position, mov_str)
+                    .ok_or_else(|| anyhow!("invalid move: {}", mov_str))?;
+                // try_make_move rejects null moves, but GUIs are allowed to send them.
+                if mov.is_null() {
+                    position.make_move(mov);
+                } else {
+                    position.try_make_move(mov)?;
+                }
+            }
+        }
+        Some(tok) => return Err(anyhow!("unknown token: {}", tok)),
+        None => {}
     }
+
+    Ok(position)
 }
 
 fn handle_go(args: &[&str]) {
     let mut iter = args.iter().cloned();
-    let mut options: SearchRequest = Default::default();
+    let mut options = SearchRequest {
+        contempt: OPTIONS.contempt.load(Ordering::Relaxed),
+        multi_pv: OPTIONS.multi_pv.load(Ordering::Relaxed),
+        ..Default::default()
+    };
+    let mut clock = Clock::default();
+    let mut movetime = None;
+    let mut infinite = false;
     let result: anyhow::Result<()> = try {
         loop {
             match iter.next() {
@@ -139,42 +263,42 @@ fn handle_go(args: &[&str]) {
                     // TODO(swgillespie) restricting the initial set of search moves
                 }
                 Some("ponder") => {
-                    // TODO(swgillespie) pondering
+                    options.ponder = OPTIONS.ponder.load(Ordering::Relaxed);
                 }
                 Some("wtime") => {
-                    let _time: u64 = iter
+                    let time: u64 = iter
                         .next()
                         .ok_or_else(|| anyhow!("expected duration after wtime"))?
                         .parse()?;
-                    // TODO(swgillespie) clock management
+                    clock.time[Color::White as usize] = Some(time);
                 }
                 Some("btime") => {
-                    let _time: u64 = iter
+                    let time: u64 = iter
                         .next()
                         .ok_or_else(|| anyhow!("expected duration after btime"))?
                         .parse()?;
-                    // TODO(swgillespie) clock management
+                    clock.time[Color::Black as usize] = Some(time);
                 }
                 Some("winc") => {
-                    let _inc: u64 = iter
+                    let inc: u64 = iter
                         .next()
                         .ok_or_else(|| anyhow!("expected duration after winc"))?
                         .parse()?;
-                    // TODO(swgillespie) clock management
+                    clock.increment[Color::White as usize] = inc;
                 }
                 Some("binc") => {
-                    let _inc: u64 = iter
+                    let inc: u64 = iter
                         .next()
                         .ok_or_else(|| anyhow!("expected duration after binc"))?
                         .parse()?;
-                    // TODO(swgillespie) clock management
+                    clock.increment[Color::Black as usize] = inc;
                 }
                 Some("movestogo") => {
-                    let _movestogo: u64 = iter
+                    let movestogo: u64 = iter
                         .next()
                         .ok_or_else(|| anyhow!("expected move count after movestogo"))?
                         .parse()?;
-                    // TODO(swgillespie) clock management
+                    clock.moves_to_go = Some(movestogo);
                 }
                 Some("depth") => {
                     let maxdepth: u32 = iter
@@ -191,17 +315,21 @@ fn handle_go(args: &[&str]) {
                     options.node_limit = Some(nodes);
                 }
                 Some("mate") => {
-                    // TODO(swgillespie) mate search
+                    let moves: u32 = iter
+                        .next()
+                        .ok_or_else(|| anyhow!("expected move count after mate"))?
+                        .parse()?;
+                    options.mate = Some(moves);
                 }
                 Some("movetime") => {
                     let msec: u64 = iter
                         .next()
                         .ok_or_else(|| anyhow!("expected msec count after movetime"))?
                         .parse()?;
-                    options.time_limit = Some(Duration::from_millis(msec));
+                    movetime = Some(Duration::from_millis(msec));
                 }
                 Some("infinite") => {
-                    options.time_limit = None;
+                    infinite = true;
                 }
                 Some(tok) => Err(anyhow!("unexpected token: {}", tok))?,
                 None => break,
@@ -209,6 +337,15 @@ fn handle_go(args: &[&str]) {
         }
     };
 
+    // A ponder search keeps this time limit, but only starts using it once the opponent makes the move that we're
+    // pondering on.
+    if !infinite {
+        options.time_limit = movetime.or_else(|| {
+            let side = threads::get_main_thread().position()?.side_to_move();
+            clock.allocate(side)
+        });
+    }
+
     match result {
         Ok(()) => {
             threads::get_main_thread().set_search(options);
@@ -218,10 +355,32 @@ fn handle_go(args: &[&str]) {
     }
 }
 
+/// The state of the clocks, as given by `go`. Times are in milliseconds and are indexed by color.
+#[derive(Copy, Clone, Debug, Default)]
+struct Clock {
+    time: [Option<u64>; 2],
+    increment: [u64; 2],
+    moves_to_go: Option<u64>,
+}
+
+impl Clock {
+    /// Decides how much time to spend on a move for the given side: an even share of its remaining time over the moves
+    /// left until the next time control, plus its increment, without ever running its clock down past
+    /// `MOVE_OVERHEAD_MS`. Returns None if the GUI didn't give the side's remaining time.
+    fn allocate(&self, side: Color) -> Option<Duration> {
+        let time = self.time[side as usize]?;
+        let moves_to_go = self.moves_to_go.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+        let share = time / moves_to_go + self.increment[side as usize];
+        let msec = share.min(time.saturating_sub(MOVE_OVERHEAD_MS));
+        Some(Duration::from_millis(msec))
+    }
+}
+
 fn handle_ucinewgame() {
     threads::get_main_thread().set_position(Position::new());
     threads::initialize_worker_threads(OPTIONS.threads.load(Ordering::Relaxed));
     table::clear();
+    cache::clear();
 }
 
 fn handle_table(args: &[&str]) {
@@ -237,6 +396,54 @@ fn handle_table(args: &[&str]) {
     uci_output!("{:?}", entry);
 }
 
+fn handle_bench(args: &[&str]) {
+    let depth = match args {
+        [] => bench::DEFAULT_BENCH_DEPTH,
+        [depth] => match depth.parse() {
+            Ok(depth) => depth,
+            Err(e) => {
+                uci_output!("invalid bench depth: {:?}", e);
+                return;
+            }
+        },
+        _ => {
+            uci_output!("usage: bench [depth]");
+            return;
+        }
+    };
+
+    table::clear();
+    let result = bench::run(depth);
+    uci_output!("nodes {}", result.nodes);
+    uci_output!("nps {}", result.nps());
+}
+
+fn handle_d() {
+    match threads::get_main_thread().position() {
+        Some(pos) => {
+            for line in describe_position(&pos) {
+                uci_output!("{}", line);
+            }
+        }
+        None => uci_output!("no position"),
+    }
+}
+
+/// The lines that a4 sends in response to `d`: the board, its FEN, its Zobrist hash, and the pieces giving check.
+fn describe_position(pos: &Position) -> Vec<String> {
+    let mut lines: Vec<_> = pos.to_string().lines().map(str::to_owned).collect();
+    lines.push(format!("Fen: {}", pos.as_fen()));
+    lines.push(format!("Key: {:016X}", pos.zobrist_hash()));
+    let checkers = pos
+        .checkers(pos.side_to_move())
+        .into_iter()
+        .map(|sq| sq.to_string())
+        .collect::<Vec<_>>()
+        .join(" ");
+    lines.push(format!("Checkers: {}", checkers));
+    lines
+}
+
 fn handle_setoption(name: &str, value: &str) {
     match name {
         "Threads" => {
@@ -250,6 +457,98 @@ fn handle_setoption(name: &str, value: &str) {
 
             OPTIONS.threads.store(count, Ordering::Relaxed);
         }
+        "Hash" => {
+            let megabytes: usize = match value.parse() {
+                Ok(v) if (1..=MAX_HASH_MB).contains(&v) => v,
+                Ok(_) => {
+                    uci_output!("invalid Hash value, must be in range [1, {}]", MAX_HASH_MB);
+                    return;
+                }
+                Err(e) => {
+                    uci_output!("invalid Hash value: {:?}", e);
+                    return;
+                }
+            };
+
+            run_in_background(move || table::resize(megabytes));
+        }
+        "Ponder" => {
+            let value: bool = match value.parse() {
+                Ok(v) => v,
+                Err(e) => {
+                    uci_output!("invalid Ponder value: {:?}", e);
+                    return;
+                }
+            };
+
+            OPTIONS.ponder.store(value, Ordering::Relaxed);
+        }
+        "MultiPV" => {
+            let count: usize = match value.parse() {
+                Ok(v) if (1..=MAX_MULTI_PV).contains(&v) => v,
+                Ok(_) => {
+                    uci_output!(
+                        "invalid MultiPV value, must be in range [1, {}]",
+                        MAX_MULTI_PV
+                    );
+                    return;
+                }
+                Err(e) => {
+                    uci_output!("invalid MultiPV value: {:?}", e);
+                    return;
+                }
+            };
+
+            OPTIONS.multi_pv.store(count, Ordering::Relaxed);
+        }
+        "Contempt" => {
+            let contempt: i16 = match value.parse() {
+                Ok(v) if (-MAX_CONTEMPT..=MAX_CONTEMPT).contains(&v) => v,
+                Ok(_) => {
+                    uci_output!(
+                        "invalid Contempt value, must be in range [{}, {}]",
+                        -MAX_CONTEMPT,
+                        MAX_CONTEMPT
+                    );
+                    return;
+                }
+                Err(e) => {
+                    uci_output!("invalid Contempt value: {:?}", e);
+                    return;
+                }
+            };
+
+            OPTIONS.contempt.store(contempt, Ordering::Relaxed);
+        }
+        "UCI_Chess960" => {
+            let value: bool = match value.parse() {
+                Ok(v) => v,
+                Err(e) => {
+                    uci_output!("invalid UCI_Chess960 value: {:?}", e);
+                    return;
+                }
+            };
+
+            core::set_chess960(value);
+        }
+        "EvalCache" => {
+            let megabytes: usize = match value.parse() {
+                Ok(v) if (1..=MAX_EVAL_CACHE_MB).contains(&v) => v,
+                Ok(_) => {
+                    uci_output!(
+                        "invalid EvalCache value, must be in range [1, {}]",
+                        MAX_EVAL_CACHE_MB
+                    );
+                    return;
+                }
+                Err(e) => {
+                    uci_output!("invalid EvalCache value: {:?}", e);
+                    return;
+                }
+            };
+
+            run_in_background(move || cache::resize(megabytes));
+        }
         "DebugLogEnabled" => {
             let value: bool = match value.parse() {
                 Ok(v) => v,
@@ -291,8 +590,160 @@ fn handle_setoption(name: &str, value: &str) {
 
             log::set_level(level);
         }
+        "SyzygyPath" => {
+            let paths = if value == "<empty>" { "" } else { value };
+            if let Err(e) = tablebase::initialize(paths) {
+                uci_output!("failed to load tablebases: {:?}", e);
+                return;
+            }
+
+            info!(
+                "loaded tablebases with up to {} pieces",
+                tablebase::max_pieces()
+            );
+        }
         e => {
             uci_output!("unknown option: {}", e);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, thread, time::Duration};
+
+    use super::{
+        describe_position, handle_command, isready_response, parse_position, run_in_background,
+        uci_response, Clock,
+    };
+    use crate::{core::Color, position::Position};
+
+    #[test]
+    fn uci_advertises_options() {
+        let response = uci_response();
+        for option in [
+            "option name Hash type spin",
+            "option name Threads type spin",
+            "option name Ponder type check",
+            "option name MultiPV type spin default 1 min 1",
+            "option name Contempt type spin",
+            "option name EvalCache type spin",
+            "option name UCI_Chess960 type check",
+        ] {
+            assert!(
+                response.iter().any(|line| line.starts_with(option)),
+                "missing `{}` in {:#?}",
+                option,
+                response
+            );
+        }
+
+        assert_eq!(Some("uciok"), response.last().map(String::as_str));
+    }
+
+    #[test]
+    fn isready_during_slow_resize() {
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let (done_tx, done_rx) = mpsc::channel();
+        run_in_background(move || {
+            let _ = release_rx.recv();
+            done_tx.send(()).unwrap();
+        });
+
+        // The background task can't finish until it's released, so `readyok` has to come back while it's still
+        // running. If answering waited on the task, this would time out rather than hang.
+        let (response_tx, response_rx) = mpsc::channel();
+        thread::spawn(move || response_tx.send(isready_response()).unwrap());
+        assert_eq!(
+            vec!["readyok".to_owned()],
+            response_rx.recv_timeout(Duration::from_secs(5)).unwrap()
+        );
+        assert!(done_rx.try_recv().is_err());
+        release_tx.send(()).unwrap();
+        done_rx.recv().unwrap();
+    }
+
+    #[test]
+    fn clock_allocation() {
+        let clock = Clock {
+            time: [Some(60_000), Some(1_000)],
+            increment: [500, 0],
+            moves_to_go: None,
+        };
+        assert_eq!(
+            Some(Duration::from_millis(2_500)),
+            clock.allocate(Color::White)
+        );
+        assert_eq!(
+            Some(Duration::from_millis(33)),
+            clock.allocate(Color::Black)
+        );
+
+        let sudden_death = Clock {
+            time: [Some(60_000), None],
+            moves_to_go: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(
+            Some(Duration::from_millis(59_950)),
+            sudden_death.allocate(Color::White)
+        );
+        assert_eq!(None, sudden_death.allocate(Color::Black));
+    }
+
+    #[test]
+    fn register_is_accepted() {
+        assert!(handle_command("register later"));
+        assert!(handle_command("register name a4 user code 1234"));
+        assert!(!handle_command("quit"));
+    }
+
+    #[test]
+    fn d_describes_the_position() {
+        let pos = Position::from_fen("4k3/8/8/8/4R3/8/8/4K3 b - - 0 1").unwrap();
+        let lines = describe_position(&pos);
+        assert!(lines.contains(&"Fen: 4k3/8/8/8/4R3/8/8/4K3 b - - 0 1".to_owned()));
+        assert!(lines.contains(&format!("Key: {:016X}", pos.zobrist_hash())));
+        assert_eq!(Some("Checkers: e4"), lines.last().map(String::as_str));
+    }
+
+    fn parse(command: &str) -> anyhow::Result<String> {
+        let args: Vec<_> = command.split_whitespace().collect();
+        parse_position(&args).map(|pos| pos.as_fen())
+    }
+
+    #[test]
+    fn position_startpos_moves() {
+        assert_eq!(
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1",
+            parse("startpos moves e2e4").unwrap()
+        );
+        assert_eq!(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            parse("startpos").unwrap()
+        );
+    }
+
+    #[test]
+    fn position_fen_moves() {
+        assert_eq!(
+            "r3k2r/8/8/8/8/8/8/R4RK1 b kq - 1 1",
+            parse("fen r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1 moves e1g1").unwrap()
+        );
+        assert_eq!(
+            "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1",
+            parse("fen 4k3/8/8/8/8/8/4P3/4K3 w - - 0 1").unwrap()
+        );
+    }
+
+    #[test]
+    fn malformed_position_commands() {
+        assert!(parse("").is_err());
+        assert!(parse("fen").is_err());
+        assert!(parse("fen moves e2e4").is_err());
+        assert!(parse("moves e2e4").is_err());
+        assert!(parse("startpos e2e4").is_err());
+        assert!(parse("startpos moves e2e5").is_err());
+        assert!(parse("fen 4k3/8/8/8/8/8/4P3/4K3 w - - 0 1 e2e4").is_err());
+    }
+}
@@ -5,9 +5,18 @@
 // <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
-use std::sync::LazyLock;
 
-use crate::core::{Color, Piece, PieceKind, Square};
+//! Zobrist hashing of positions.
+//!
+//! Positions keep their hashes up to date as moves are made, using the `modify_*` functions here with the engine's
+//! default keys. Tools that need hashes from a different set of keys can generate their own [`Keys`] from a seed.
+
+use std::{mem, sync::LazyLock};
+
+use crate::{
+    core::{self, Color, Piece, PieceKind, Square, SquareSet},
+    position::Position,
+};
 
 struct Xorshift64 {
     state: u64,
@@ -32,22 +41,31 @@ const SIDE_TO_MOVE_INDEX: usize = 768;
 const CASTLING_RIGHTS_INDEX: usize = 769;
 const EN_PASSANT_INDEX: usize = 773;
 
-struct ZobristHasher {
+/// A set of Zobrist keys: one random number for each piece on each square, for the side to move, for each castling
+/// right, and for each file that an en-passant square can be on. A position's hash is the XOR of the keys of every
+/// feature that the position has.
+///
+/// Keys are generated by a pseudo-random number generator from a seed, so the same seed always produces the same keys.
+/// The engine uses the keys generated from [`DEFAULT_SEED`], which are available through [`keys`].
+pub struct Keys {
     magic_hashes: [u64; 781],
 }
 
-impl ZobristHasher {
-    pub fn new(seed: u64) -> ZobristHasher {
+impl Keys {
+    /// Generates a set of keys from the given seed. The seed must not be zero.
+    pub fn from_seed(seed: u64) -> Keys {
+        assert!(seed != 0, "zobrist seed must not be zero");
         let mut rng = Xorshift64::new(seed);
         let mut magic_hashes = [0; 781];
         for entry in magic_hashes.iter_mut() {
             *entry = rng.next();
         }
 
-        ZobristHasher { magic_hashes }
+        Keys { magic_hashes }
     }
 
-    pub fn square_hash(&self, kind: PieceKind, color: Color, square: Square) -> u64 {
+    /// Returns the key for a piece of the given kind and color on the given square.
+    pub fn piece(&self, kind: PieceKind, color: Color, square: Square) -> u64 {
         // The layout of this table is:
         // [square]
         //   0 white pawn hash
@@ -67,53 +85,238 @@ impl ZobristHasher {
         self.magic_hashes[(offset + color_offset + piece_offset) as usize]
     }
 
-    pub fn side_to_move_hash(&self, side: Color) -> u64 {
+    /// Returns the key for the given side to move. White to move has no key.
+    pub fn side_to_move(&self, side: Color) -> u64 {
         match side {
             Color::White => 0,
             Color::Black => self.magic_hashes[SIDE_TO_MOVE_INDEX],
         }
     }
 
-    pub fn en_passant_hash(&self, square: Square) -> u64 {
+    /// Returns the key for an en-passant square. Only the file of the square contributes to the key.
+    pub fn en_passant(&self, square: Square) -> u64 {
         self.magic_hashes[square.file().as_u8() as usize + EN_PASSANT_INDEX]
     }
 
+    /// Returns the key for the given color's right to castle kingside.
+    pub fn kingside_castle(&self, color: Color) -> u64 {
+        let offset = if color == Color::White { 0 } else { 2 };
+        self.castle_hash(offset)
+    }
+
+    /// Returns the key for the given color's right to castle queenside.
+    pub fn queenside_castle(&self, color: Color) -> u64 {
+        let offset = if color == Color::White { 1 } else { 3 };
+        self.castle_hash(offset)
+    }
+
     fn castle_hash(&self, offset: usize) -> u64 {
         self.magic_hashes[offset + CASTLING_RIGHTS_INDEX]
     }
+
+    /// Computes the hash of a position from scratch using these keys.
+    pub fn hash_position(&self, pos: &Position) -> u64 {
+        let mut hash = 0;
+        for square in pos.pieces(Color::White) | pos.pieces(Color::Black) {
+            let piece = pos.piece_at(square).unwrap();
+            hash ^= self.piece(piece.kind, piece.color, square);
+        }
+
+        hash ^= self.side_to_move(pos.side_to_move());
+        for color in core::colors() {
+            if pos.can_castle_kingside(color) {
+                hash ^= self.kingside_castle(color);
+            }
+
+            if pos.can_castle_queenside(color) {
+                hash ^= self.queenside_castle(color);
+            }
+        }
+
+        if let Some(square) = pos.en_passant_square() {
+            hash ^= self.en_passant(square);
+        }
+
+        hash
+    }
 }
 
-const ZOBRIST_SEED: u64 = 0xf68e34a4e8ccf09a;
+/// The seed of the keys that the engine uses.
+pub const DEFAULT_SEED: u64 = 0xf68e34a4e8ccf09a;
+
+static KEYS: LazyLock<Keys> = LazyLock::new(|| Keys::from_seed(DEFAULT_SEED));
 
-static ZOBRIST_HASHER: LazyLock<ZobristHasher> = LazyLock::new(|| ZobristHasher::new(ZOBRIST_SEED));
+/// Returns the keys that the engine uses to hash positions.
+pub fn keys() -> &'static Keys {
+    &KEYS
+}
 
 pub fn modify_piece(hash: &mut u64, square: Square, piece: Piece) {
-    *hash ^= ZOBRIST_HASHER.square_hash(piece.kind, piece.color, square);
+    *hash ^= KEYS.piece(piece.kind, piece.color, square);
 }
 
 pub fn modify_side_to_move(hash: &mut u64) {
-    *hash ^= ZOBRIST_HASHER.side_to_move_hash(Color::Black);
+    *hash ^= KEYS.side_to_move(Color::Black);
 }
 
 pub fn modify_kingside_castle(hash: &mut u64, color: Color) {
-    let offset = if color == Color::White { 0 } else { 2 };
-    *hash ^= ZOBRIST_HASHER.castle_hash(offset);
+    *hash ^= KEYS.kingside_castle(color);
 }
 
 pub fn modify_queenside_castle(hash: &mut u64, color: Color) {
-    let offset = if color == Color::White { 1 } else { 3 };
-    *hash ^= ZOBRIST_HASHER.castle_hash(offset);
+    *hash ^= KEYS.queenside_castle(color);
 }
 
 pub fn modify_en_passant(hash: &mut u64, old: Option<Square>, new: Option<Square>) {
     match (old, new) {
         (Some(old), Some(new)) => {
-            *hash ^= ZOBRIST_HASHER.en_passant_hash(old);
-            *hash ^= ZOBRIST_HASHER.en_passant_hash(new);
+            *hash ^= KEYS.en_passant(old);
+            *hash ^= KEYS.en_passant(new);
         }
         (Some(sq), _) | (_, Some(sq)) => {
-            *hash ^= ZOBRIST_HASHER.en_passant_hash(sq);
+            *hash ^= KEYS.en_passant(sq);
         }
         _ => {}
     }
 }
+
+/// Computes the Zobrist hash of a position from scratch. Positions maintain their hashes incrementally as moves are
+/// made, which must always agree with this.
+pub fn hash_position(pos: &Position) -> u64 {
+    KEYS.hash_position(pos)
+}
+
+/// Number of entries in the cuckoo table. Each reversible move hashes to two slots in the table.
+const CUCKOO_SIZE: usize = 8192;
+
+fn cuckoo_h1(key: u64) -> usize {
+    (key & 0x1fff) as usize
+}
+
+fn cuckoo_h2(key: u64) -> usize {
+    ((key >> 16) & 0x1fff) as usize
+}
+
+/// Marcel van Kervinck's cuckoo table of reversible moves. Every non-pawn move that a piece can make on an empty board
+/// is stored here, keyed by the change that the move makes to a position's Zobrist hash. Given the hashes of two
+/// positions, a lookup in this table tells us whether a single reversible move could take one to the other.
+struct CuckooTable {
+    keys: [u64; CUCKOO_SIZE],
+    moves: [Option<(Square, Square)>; CUCKOO_SIZE],
+}
+
+impl CuckooTable {
+    fn new(keys: &Keys) -> CuckooTable {
+        let mut table = CuckooTable {
+            keys: [0; CUCKOO_SIZE],
+            moves: [None; CUCKOO_SIZE],
+        };
+
+        let kinds = [
+            PieceKind::Knight,
+            PieceKind::Bishop,
+            PieceKind::Rook,
+            PieceKind::Queen,
+            PieceKind::King,
+        ];
+        for color in core::colors() {
+            for kind in kinds {
+                for s1 in core::squares() {
+                    let targets = core::attacks(kind, color, s1, SquareSet::empty());
+                    for s2 in core::squares() {
+                        if s1.as_u8() >= s2.as_u8() || !targets.contains(s2) {
+                            continue;
+                        }
+
+                        let mut key = keys.piece(kind, color, s1)
+                            ^ keys.piece(kind, color, s2)
+                            ^ keys.side_to_move(Color::Black);
+                        let mut mov = Some((s1, s2));
+                        let mut slot = cuckoo_h1(key);
+                        // Insert the move, kicking out whatever was in its slot and re-inserting that into its other
+                        // slot, until we land in an empty slot.
+                        loop {
+                            mem::swap(&mut table.keys[slot], &mut key);
+                            mem::swap(&mut table.moves[slot], &mut mov);
+                            if mov.is_none() {
+                                break;
+                            }
+
+                            slot = if slot == cuckoo_h1(key) {
+                                cuckoo_h2(key)
+                            } else {
+                                cuckoo_h1(key)
+                            };
+                        }
+                    }
+                }
+            }
+        }
+
+        table
+    }
+
+    fn lookup(&self, key: u64) -> Option<(Square, Square)> {
+        [cuckoo_h1(key), cuckoo_h2(key)]
+            .into_iter()
+            .find(|&slot| self.keys[slot] == key)
+            .and_then(|slot| self.moves[slot])
+    }
+}
+
+static CUCKOO_TABLE: LazyLock<CuckooTable> = LazyLock::new(|| CuckooTable::new(&KEYS));
+
+/// Given the difference (XOR) between the hashes of two positions, returns the endpoints of the reversible move that
+/// takes one position to the other, if there is one. The piece making the move may be on either square.
+pub fn reversible_move(key: u64) -> Option<(Square, Square)> {
+    CUCKOO_TABLE.lookup(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{keys, Keys, CUCKOO_TABLE, DEFAULT_SEED};
+    use crate::{core::*, position::Position};
+
+    #[test]
+    fn same_seed_same_keys() {
+        let a = Keys::from_seed(0x1234_5678_9abc_def0);
+        let b = Keys::from_seed(0x1234_5678_9abc_def0);
+        let c = Keys::from_seed(0x0fed_cba9_8765_4321);
+        for square in squares() {
+            assert_eq!(
+                a.piece(PieceKind::Knight, Color::Black, square),
+                b.piece(PieceKind::Knight, Color::Black, square)
+            );
+        }
+
+        assert_ne!(
+            a.piece(PieceKind::Pawn, Color::White, E4),
+            c.piece(PieceKind::Pawn, Color::White, E4)
+        );
+    }
+
+    #[test]
+    fn default_keys_are_stable() {
+        let pos = Position::from_start_position();
+        assert_eq!(
+            Keys::from_seed(DEFAULT_SEED).hash_position(&pos),
+            keys().hash_position(&pos)
+        );
+        assert_eq!(pos.zobrist_hash(), keys().hash_position(&pos));
+        // The hash of the starting position is baked into anything that persists hashes, like opening books and
+        // transposition table dumps, so it must never change by accident.
+        assert_eq!(0xdecd_6363_994a_18d4, keys().hash_position(&pos));
+    }
+
+    #[test]
+    fn cuckoo_table_holds_every_reversible_move() {
+        // 3668 is the number of distinct non-pawn moves on an empty board, counting both colors and treating a move
+        // and its reverse as the same move.
+        let count = CUCKOO_TABLE
+            .moves
+            .iter()
+            .filter(|mov| mov.is_some())
+            .count();
+        assert_eq!(3668, count);
+    }
+}
@@ -0,0 +1,66 @@
+// Copyright 2022 Sean Gillespie.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Move generation into a `MoveList` is what lets the search avoid allocating at every node, so check that it really
+//! doesn't allocate, using an allocator that counts the allocations made on each thread. The allocator replaces the
+//! global allocator for the whole test binary, which is why these tests live in their own crate.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+};
+
+use a4::{
+    movegen::{self, MoveList},
+    position::Position,
+};
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn count_allocations(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.with(|count| count.get());
+    f();
+    ALLOCATIONS.with(|count| count.get()) - before
+}
+
+#[test]
+fn move_list_does_not_allocate() {
+    let pos =
+        Position::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+            .unwrap();
+
+    let vec_allocations = count_allocations(|| {
+        let mut moves = Vec::new();
+        movegen::generate_moves(pos.side_to_move(), &pos, &mut moves);
+    });
+    let list_allocations = count_allocations(|| {
+        let mut moves = MoveList::new();
+        movegen::generate_move_list(pos.side_to_move(), &pos, &mut moves);
+    });
+
+    assert!(vec_allocations > 0);
+    assert_eq!(0, list_allocations);
+}